@@ -0,0 +1,54 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::library;
+
+// A folder whose files haven't changed for this long is taken to be
+// finished downloading.
+const SETTLED: Duration = Duration::from_secs(60);
+
+// A finished album (or any folder of tracks) in the downloads folder.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Arrival {
+    pub dir: PathBuf,
+    pub tracks: usize,
+    pub modified: SystemTime,
+}
+
+// Folders under `downloads` holding audio files that have all settled,
+// newest first. Soulseek clients keep partial files elsewhere, but a
+// folder still being written to waits until it's quiet.
+pub fn scan(downloads: &Path) -> Vec<Arrival> {
+    let mut folders: BTreeMap<PathBuf, (usize, SystemTime)> = BTreeMap::new();
+    for file in library::audio_files(downloads) {
+        let Some(dir) = file.parent() else {
+            continue;
+        };
+        let modified = fs::metadata(&file)
+            .and_then(|m| m.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let folder = folders
+            .entry(dir.to_path_buf())
+            .or_insert((0, SystemTime::UNIX_EPOCH));
+        folder.0 += 1;
+        folder.1 = folder.1.max(modified);
+    }
+
+    let now = SystemTime::now();
+    let mut arrivals: Vec<Arrival> = folders
+        .into_iter()
+        .filter(|(_, (_, modified))| {
+            now.duration_since(*modified)
+                .is_ok_and(|age| age >= SETTLED)
+        })
+        .map(|(dir, (tracks, modified))| Arrival {
+            dir,
+            tracks,
+            modified,
+        })
+        .collect();
+    arrivals.sort_by_key(|arrival| std::cmp::Reverse(arrival.modified));
+    arrivals
+}
@@ -0,0 +1,340 @@
+use anyhow::{Result, anyhow};
+use rodio::cpal::traits::HostTrait;
+use rodio::dynamic_mixer::{self, DynamicMixerController};
+use rodio::{DeviceTrait, OutputStream, OutputStreamHandle, Sink, Source};
+use std::env;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::decode::BoxedSource;
+use crate::snapcast;
+
+// How often a crossfade turns the outgoing track down.
+const FADE_STEP: Duration = Duration::from_millis(50);
+
+// What an in-process mixer puts out, 20ms at a time.
+const MIXER_RATE: u32 = 48000;
+const MIXER_CHANNELS: u16 = 2;
+const CHUNK_FRAMES: usize = 960;
+
+const STREAM_PROPERTIES: &[(&str, &str)] = &[
+    ("PULSE_PROP_application.name", "Leek"),
+    ("PULSE_PROP_application.icon_name", "audio-x-generic"),
+    ("PULSE_PROP_media.role", "music"),
+    (
+        "PIPEWIRE_PROPS",
+        "{ application.name = Leek application.icon-name = audio-x-generic media.role = Music media.category = Playback }",
+    ),
+];
+
+// PulseAudio and PipeWire read these when the ALSA stream opens, so mixers
+// like pavucontrol list leek by name under the music role instead of as
+// "ALSA plug-in". Values the user already set are left alone. Call this
+// before any other thread starts. There's no per-track media.title: the
+// ALSA plugins only read these once, when the stream opens, and the
+// stream stays open across tracks.
+pub fn set_stream_properties() {
+    if !cfg!(target_os = "linux") {
+        return;
+    }
+    for (key, value) in STREAM_PROPERTIES {
+        if env::var_os(key).is_none() {
+            // SAFETY: called at startup, before leek spawns any threads.
+            unsafe { env::set_var(key, value) };
+        }
+    }
+}
+
+// The output side of the player. Each `play` replaces whatever was playing.
+pub trait AudioBackend {
+    fn play(&mut self, source: BoxedSource);
+    // Starts `source` over `duration` while what was playing fades out.
+    fn crossfade(&mut self, source: BoxedSource, duration: Duration);
+    fn stop(&mut self);
+    fn pause(&mut self);
+    fn resume(&mut self);
+    fn is_paused(&self) -> bool;
+    // True once the current source has finished, or when nothing was played.
+    fn is_empty(&self) -> bool;
+    fn set_volume(&mut self, volume: f32);
+}
+
+// The name of the device that "default" plays through right now. On Linux
+// that is the PulseAudio or PipeWire default sink, which changes when
+// headphones are plugged in, rather than ALSA's fixed "default".
+pub fn output_device() -> Option<String> {
+    if cfg!(target_os = "linux")
+        && let Ok(output) = Command::new("pactl").arg("get-default-sink").output()
+        && output.status.success()
+    {
+        let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if !name.is_empty() {
+            return Some(name);
+        }
+    }
+    rodio::cpal::default_host()
+        .default_output_device()?
+        .name()
+        .ok()
+}
+
+// Where the sinks play: an audio device, or a mixer whose output is written
+// somewhere by a thread of its own.
+enum Target {
+    Device {
+        _stream: OutputStream,
+        handle: OutputStreamHandle,
+    },
+    Mixer(Arc<DynamicMixerController<f32>>),
+}
+
+// A mixer pulled at real time by a thread of its own, which hands each
+// chunk to `write` as 16-bit little-endian PCM. Nothing downstream sets
+// the pace, so the thread keeps to the clock itself.
+fn realtime_mixer(
+    mut write: impl FnMut(&[u8]) + Send + 'static,
+) -> Arc<DynamicMixerController<f32>> {
+    let (controller, mut mixer) = dynamic_mixer::mixer(MIXER_CHANNELS, MIXER_RATE);
+    thread::spawn(move || {
+        let mut chunk = Vec::with_capacity(CHUNK_FRAMES * MIXER_CHANNELS as usize * 2);
+        let start = Instant::now();
+        let mut frames: u64 = 0;
+        loop {
+            chunk.clear();
+            for _ in 0..CHUNK_FRAMES * MIXER_CHANNELS as usize {
+                // The mixer runs dry between tracks.
+                let sample = mixer.next().unwrap_or(0.0).clamp(-1.0, 1.0);
+                chunk.extend_from_slice(&((sample * i16::MAX as f32) as i16).to_le_bytes());
+            }
+            write(&chunk);
+
+            frames += CHUNK_FRAMES as u64;
+            let due = start + Duration::from_secs_f64(frames as f64 / MIXER_RATE as f64);
+            if let Some(wait) = due.checked_duration_since(Instant::now()) {
+                thread::sleep(wait);
+            }
+        }
+    });
+    controller
+}
+
+impl Target {
+    fn sink(&self) -> Result<Sink> {
+        match self {
+            Target::Device { handle, .. } => Ok(Sink::try_new(handle)?),
+            Target::Mixer(mixer) => {
+                // Dropping the sink ends the queue, which takes it out of
+                // the mixer.
+                let (sink, queue) = Sink::new_idle();
+                mixer.add(queue);
+                Ok(sink)
+            }
+        }
+    }
+}
+
+pub struct RodioBackend {
+    target: Target,
+    sink: Sink,
+    // The outgoing track during a crossfade.
+    fading: Option<Arc<Sink>>,
+    volume: f32,
+}
+
+impl RodioBackend {
+    pub fn new() -> Result<Self> {
+        let (stream, stream_handle) = OutputStream::try_default()?;
+        Self::from_stream(stream, stream_handle)
+    }
+
+    // Registers a JACK client called `<name>_out` with ports `out_0` and
+    // `out_1`, connected to the system playback ports.
+    #[cfg(all(feature = "jack", target_os = "linux"))]
+    pub fn jack(name: &str) -> Result<Self> {
+        let mut host = cpal::platform::JackHost::new()?;
+        let device = host
+            .output_device_with_name(name)
+            .ok_or_else(|| anyhow!("no JACK server running"))?;
+        let (stream, stream_handle) = OutputStream::try_from_device(&device.into())?;
+        Self::from_stream(stream, stream_handle)
+    }
+
+    #[cfg(not(all(feature = "jack", target_os = "linux")))]
+    pub fn jack(_name: &str) -> Result<Self> {
+        Err(anyhow!("built without JACK support"))
+    }
+
+    // Feeds a Snapcast server's pipe or TCP source.
+    pub fn snapcast(target: &str) -> Result<Self> {
+        let write = snapcast::writer(target)?;
+        Self::from_target(Target::Mixer(realtime_mixer(write)))
+    }
+
+    // Plays nothing but keeps time as if it did, for a leek that is only
+    // there to be watched and controlled, e.g. over SSH on a machine with
+    // no sound card.
+    pub fn silent() -> Result<Self> {
+        Self::from_target(Target::Mixer(realtime_mixer(|_| {})))
+    }
+
+    fn from_stream(stream: OutputStream, handle: OutputStreamHandle) -> Result<Self> {
+        Self::from_target(Target::Device {
+            _stream: stream,
+            handle,
+        })
+    }
+
+    fn from_target(target: Target) -> Result<Self> {
+        let sink = target.sink()?;
+        Ok(Self {
+            target,
+            sink,
+            fading: None,
+            volume: 1.0,
+        })
+    }
+}
+
+impl AudioBackend for RodioBackend {
+    // A fresh sink per source, since rodio 0.17 can't clear a sink's queue.
+    fn play(&mut self, source: BoxedSource) {
+        self.stop();
+        if let Ok(sink) = self.target.sink() {
+            self.sink = sink;
+        }
+        self.sink.set_volume(self.volume);
+        self.sink.append(source);
+        self.sink.play();
+    }
+
+    // The outgoing sink is turned down from a thread of its own, since the
+    // player only ticks a few times a second.
+    fn crossfade(&mut self, source: BoxedSource, duration: Duration) {
+        let Ok(sink) = self.target.sink() else {
+            return self.play(source);
+        };
+        if let Some(fading) = self.fading.take() {
+            fading.stop();
+        }
+        sink.set_volume(self.volume);
+        sink.append(Source::fade_in(source, duration));
+        let outgoing = Arc::new(std::mem::replace(&mut self.sink, sink));
+        self.fading = Some(outgoing.clone());
+
+        let volume = self.volume;
+        thread::spawn(move || {
+            let steps = (duration.as_millis() / FADE_STEP.as_millis()).max(1) as u32;
+            for step in 1..=steps {
+                thread::sleep(FADE_STEP);
+                // Stopped early by a skip or another crossfade.
+                if outgoing.empty() {
+                    return;
+                }
+                outgoing.set_volume(volume * (1.0 - step as f32 / steps as f32));
+            }
+            outgoing.stop();
+        });
+    }
+
+    fn stop(&mut self) {
+        self.sink.stop();
+        if let Some(fading) = self.fading.take() {
+            fading.stop();
+        }
+    }
+
+    fn pause(&mut self) {
+        self.sink.pause();
+        if let Some(fading) = &self.fading {
+            fading.pause();
+        }
+    }
+
+    fn resume(&mut self) {
+        self.sink.play();
+        if let Some(fading) = &self.fading {
+            fading.play();
+        }
+    }
+
+    fn is_paused(&self) -> bool {
+        self.sink.is_paused()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.sink.empty()
+    }
+
+    fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+        self.sink.set_volume(volume);
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MockState {
+    pub loaded: bool,
+    pub paused: bool,
+    pub volume: f32,
+    pub plays: usize,
+}
+
+// A silent backend for tests. Clones share state, so a test can keep one
+// to inspect the player and to end tracks with `finish`.
+#[derive(Clone, Default)]
+pub struct MockBackend {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn state(&self) -> MockState {
+        self.state.lock().unwrap().clone()
+    }
+
+    pub fn finish(&self) {
+        self.state.lock().unwrap().loaded = false;
+    }
+}
+
+impl AudioBackend for MockBackend {
+    fn play(&mut self, _source: BoxedSource) {
+        let mut state = self.state.lock().unwrap();
+        state.loaded = true;
+        state.paused = false;
+        state.plays += 1;
+    }
+
+    fn crossfade(&mut self, source: BoxedSource, _duration: Duration) {
+        self.play(source);
+    }
+
+    fn stop(&mut self) {
+        self.state.lock().unwrap().loaded = false;
+    }
+
+    fn pause(&mut self) {
+        self.state.lock().unwrap().paused = true;
+    }
+
+    fn resume(&mut self) {
+        self.state.lock().unwrap().paused = false;
+    }
+
+    fn is_paused(&self) -> bool {
+        self.state.lock().unwrap().paused
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.state.lock().unwrap().loaded
+    }
+
+    fn set_volume(&mut self, volume: f32) {
+        self.state.lock().unwrap().volume = volume;
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::db::PlayCount;
+use crate::library::Library;
+
+const DAY: i64 = 24 * 60 * 60;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ChartKind {
+    #[default]
+    Tracks,
+    Artists,
+    Albums,
+}
+
+impl ChartKind {
+    pub fn next(self) -> ChartKind {
+        match self {
+            ChartKind::Tracks => ChartKind::Artists,
+            ChartKind::Artists => ChartKind::Albums,
+            ChartKind::Albums => ChartKind::Tracks,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Window {
+    Week,
+    #[default]
+    Month,
+    Year,
+    AllTime,
+}
+
+impl Window {
+    pub fn next(self) -> Window {
+        match self {
+            Window::Week => Window::Month,
+            Window::Month => Window::Year,
+            Window::Year => Window::AllTime,
+            Window::AllTime => Window::Week,
+        }
+    }
+
+    // The earliest play that counts, in seconds since the epoch.
+    pub fn since(self) -> i64 {
+        let days = match self {
+            Window::Week => 7,
+            Window::Month => 30,
+            Window::Year => 365,
+            Window::AllTime => return 0,
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        now - days * DAY
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ChartEntry {
+    pub label: String,
+    pub plays: u64,
+    // The tracks behind the entry, most played first.
+    pub paths: Vec<PathBuf>,
+}
+
+// Ranks tracks, or adds up their plays per artist or album. Untagged files
+// count towards their folder's album but not towards any artist.
+pub fn build(counts: &[PlayCount], library: &Library, kind: ChartKind) -> Vec<ChartEntry> {
+    let mut entries: Vec<ChartEntry> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+
+    for count in counts {
+        let tags = library.find(&count.path).map(|track| &track.tags);
+        let label = match kind {
+            ChartKind::Tracks => match tags.and_then(|t| t.artist.as_ref().zip(t.title.as_ref())) {
+                Some((artist, title)) => format!("{} - {}", artist, title),
+                None => file_name(&count.path),
+            },
+            ChartKind::Artists => match tags.and_then(|t| t.artist.clone()) {
+                Some(artist) => artist,
+                None => continue,
+            },
+            ChartKind::Albums => {
+                let album = tags
+                    .and_then(|t| t.album.clone())
+                    .or_else(|| count.path.parent().map(file_name))
+                    .unwrap_or_default();
+                match tags.and_then(|t| t.display_album_artist()) {
+                    Some(artist) => format!("{} - {}", artist, album),
+                    None => album,
+                }
+            }
+        };
+
+        let key = label.to_lowercase();
+        match index.get(&key) {
+            Some(&i) if kind != ChartKind::Tracks => {
+                entries[i].plays += count.plays;
+                entries[i].paths.push(count.path.clone());
+            }
+            _ => {
+                index.insert(key, entries.len());
+                entries.push(ChartEntry {
+                    label,
+                    plays: count.plays,
+                    paths: vec![count.path.clone()],
+                });
+            }
+        }
+    }
+
+    entries.sort_by(|a, b| b.plays.cmp(&a.plays).then_with(|| a.label.cmp(&b.label)));
+    entries
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
@@ -0,0 +1,57 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// The broken-down local time of `secs` past the epoch. The C library knows
+// the time zone (TZ, or /etc/localtime), which the standard library doesn't.
+fn local(secs: i64) -> Option<libc::tm> {
+    let time = secs as libc::time_t;
+    // SAFETY: tm is plain data, and localtime_r only writes to it.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::localtime_r(&time, &mut tm) };
+    (!result.is_null()).then_some(tm)
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() as i64)
+}
+
+// Minutes since local midnight.
+pub fn local_minutes() -> u32 {
+    local(now()).map_or(0, |tm| tm.tm_hour as u32 * 60 + tm.tm_min as u32)
+}
+
+// "2024-03-01 18:04:12" in the local time zone.
+pub fn local_time(secs: u64) -> String {
+    match local(secs as i64) {
+        Some(tm) => format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            tm.tm_year + 1900,
+            tm.tm_mon + 1,
+            tm.tm_mday,
+            tm.tm_hour,
+            tm.tm_min,
+            tm.tm_sec
+        ),
+        None => secs.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minutes_stay_within_a_day() {
+        assert!(local_minutes() < 24 * 60);
+        let tm = local(0).unwrap();
+        assert!((1969..=1970).contains(&(tm.tm_year + 1900)));
+    }
+
+    #[test]
+    fn times_are_written_out_in_full() {
+        let time = local_time(1_709_316_252);
+        assert_eq!(time.len(), "2024-03-01 18:04:12".len());
+        assert!(time.starts_with("2024-03-0"));
+    }
+}
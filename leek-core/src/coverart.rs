@@ -0,0 +1,102 @@
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use crate::covers;
+use crate::log;
+use crate::paths;
+use crate::tags;
+
+// MusicBrainz turns away requests without a descriptive agent.
+const USER_AGENT: &str = concat!(
+    "leek/",
+    env!("CARGO_PKG_VERSION"),
+    " ( https://github.com/Steinzu/leek )"
+);
+
+// Front covers from the Cover Art Archive for tracks without local art.
+// The release comes from the MUSICBRAINZ_ALBUMID tag, or else from a
+// MusicBrainz search on the album and artist. Downloads are kept in the
+// cache, and so are misses, so each release is asked about once.
+pub fn fetch(path: &Path) -> Option<Vec<u8>> {
+    let release = tags::release_id(path);
+    let key = match &release {
+        Some(id) => format!("release-{}", id),
+        None => {
+            let tags = tags::read(path).ok()?;
+            let album = tags.album.clone()?;
+            let artist = tags.display_album_artist().unwrap_or_default();
+            format!(
+                "album-{:016x}",
+                covers::hash(format!("{}\0{}", artist, album).as_bytes())
+            )
+        }
+    };
+    let dir = paths::cache_dir()?.join("coverart");
+    fs::create_dir_all(&dir).ok()?;
+    let file = dir.join(key);
+    if let Ok(bytes) = fs::read(&file) {
+        return (!bytes.is_empty()).then_some(bytes);
+    }
+
+    let result = match release {
+        Some(id) => front_cover(&id),
+        None => search_release(path).and_then(|id| front_cover(&id)),
+    };
+    let bytes = match result {
+        Ok(bytes) => Some(bytes),
+        Err(e) => {
+            log::warn(&format!("No cover online for {}: {}", path.display(), e));
+            None
+        }
+    };
+    let _ = fs::write(&file, bytes.as_deref().unwrap_or_default());
+    bytes
+}
+
+fn front_cover(release: &str) -> Result<Vec<u8>> {
+    let url = format!("https://coverartarchive.org/release/{}/front-250", release);
+    Ok(ureq::get(&url)
+        .header("User-Agent", USER_AGENT)
+        .call()?
+        .body_mut()
+        .read_to_vec()?)
+}
+
+fn search_release(path: &Path) -> Result<String> {
+    #[derive(Deserialize)]
+    struct Response {
+        #[serde(default)]
+        releases: Vec<Release>,
+    }
+    #[derive(Deserialize)]
+    struct Release {
+        id: String,
+        #[serde(default)]
+        score: u32,
+    }
+
+    let tags = tags::read(path)?;
+    let album = tags.album.clone().ok_or_else(|| anyhow!("no album tag"))?;
+    let mut query = format!("release:\"{}\"", album.replace('"', ""));
+    if let Some(artist) = tags.display_album_artist() {
+        query.push_str(&format!(" AND artist:\"{}\"", artist.replace('"', "")));
+    }
+    let text = ureq::get("https://musicbrainz.org/ws/2/release/")
+        .header("User-Agent", USER_AGENT)
+        .query("query", &query)
+        .query("fmt", "json")
+        .query("limit", "1")
+        .call()?
+        .body_mut()
+        .read_to_string()?;
+    let response: Response = serde_json::from_str(&text)?;
+    response
+        .releases
+        .into_iter()
+        // Below this MusicBrainz is guessing.
+        .find(|release| release.score >= 90)
+        .map(|release| release.id)
+        .ok_or_else(|| anyhow!("no matching release"))
+}
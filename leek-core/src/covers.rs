@@ -0,0 +1,153 @@
+use image::ColorType;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::UNIX_EPOCH;
+
+use crate::folder::{self, Thumbnail};
+use crate::log;
+use crate::paths;
+use crate::remote;
+
+// Thumbnails kept in memory by the service; the disk cache holds the rest.
+const MEMORY_LIMIT: usize = 256;
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+
+// FNV-1a, which stays the same across builds, unlike std's hasher.
+fn fnv1a(bytes: &[u8], mut hash: u64) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+pub fn hash(bytes: &[u8]) -> u64 {
+    fnv1a(bytes, FNV_OFFSET)
+}
+
+// Cached thumbnails are named after the source file's path, size and
+// modification time, so editing or replacing the file misses the cache.
+fn cache_file(source: &Path) -> Option<PathBuf> {
+    let meta = fs::metadata(source).ok()?;
+    let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    let mut key = hash(source.as_os_str().as_encoded_bytes());
+    key = fnv1a(&meta.len().to_le_bytes(), key);
+    key = fnv1a(&mtime.as_nanos().to_le_bytes(), key);
+
+    let dir = paths::cache_dir()?.join("covers");
+    fs::create_dir_all(&dir).ok()?;
+    Some(dir.join(format!("{:016x}.png", key)))
+}
+
+// The thumbnail for `source`, from the cache or else from `extract`, which
+// returns the full-size image. An empty cache file records that there was
+// no art, so files without any aren't probed again.
+pub fn cached(source: &Path, extract: impl FnOnce() -> Option<Vec<u8>>) -> Option<Thumbnail> {
+    if remote::is_remote(source) {
+        return Thumbnail::decode(&extract()?);
+    }
+    let Some(file) = cache_file(source) else {
+        return Thumbnail::decode(&extract()?);
+    };
+    if let Ok(bytes) = fs::read(&file) {
+        return Thumbnail::decode(&bytes);
+    }
+
+    let thumbnail = extract().and_then(|bytes| Thumbnail::decode(&bytes));
+    let result = match &thumbnail {
+        Some(thumbnail) => image::save_buffer(
+            &file,
+            &thumbnail.pixels.concat(),
+            thumbnail.width,
+            thumbnail.height,
+            ColorType::Rgb8,
+        )
+        .map_err(|e| e.to_string()),
+        None => fs::write(&file, []).map_err(|e| e.to_string()),
+    };
+    if let Err(e) = result {
+        log::warn(&format!(
+            "Could not cache cover for {}: {}",
+            source.display(),
+            e
+        ));
+    }
+    thumbnail
+}
+
+// Looks up track covers on a worker thread so the UI never waits on a
+// decode. Ask with `request`, collect with `poll`, then read with `get`.
+pub struct CoverService {
+    // Whether covers missing locally are looked up online.
+    pub fetch: bool,
+    requests: Sender<(PathBuf, bool)>,
+    results: Receiver<(PathBuf, Option<Thumbnail>)>,
+    pending: HashSet<PathBuf>,
+    covers: HashMap<PathBuf, Option<Thumbnail>>,
+}
+
+impl CoverService {
+    pub fn start() -> CoverService {
+        let (requests, request_rx) = mpsc::channel::<(PathBuf, bool)>();
+        let (result_tx, results) = mpsc::channel();
+        thread::spawn(move || {
+            for (path, fetch) in request_rx {
+                let cover = folder::track_cover(&path).or_else(|| fetch_cover(&path, fetch));
+                if result_tx.send((path, cover)).is_err() {
+                    return;
+                }
+            }
+        });
+        CoverService {
+            fetch: false,
+            requests,
+            results,
+            pending: HashSet::new(),
+            covers: HashMap::new(),
+        }
+    }
+
+    pub fn request(&mut self, path: &Path) {
+        if self.covers.contains_key(path) || self.pending.contains(path) {
+            return;
+        }
+        if self.requests.send((path.to_path_buf(), self.fetch)).is_ok() {
+            self.pending.insert(path.to_path_buf());
+        }
+    }
+
+    // Takes in finished lookups, returning true if any arrived.
+    pub fn poll(&mut self) -> bool {
+        let mut arrived = false;
+        while let Ok((path, cover)) = self.results.try_recv() {
+            if self.covers.len() >= MEMORY_LIMIT {
+                self.covers.clear();
+            }
+            self.pending.remove(&path);
+            self.covers.insert(path, cover);
+            arrived = true;
+        }
+        arrived
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&Thumbnail> {
+        self.covers.get(path)?.as_ref()
+    }
+}
+
+#[cfg(feature = "coverart")]
+fn fetch_cover(path: &Path, fetch: bool) -> Option<Thumbnail> {
+    if !fetch || remote::is_remote(path) {
+        return None;
+    }
+    Thumbnail::decode(&crate::coverart::fetch(path)?)
+}
+
+#[cfg(not(feature = "coverart"))]
+fn fetch_cover(_path: &Path, _fetch: bool) -> Option<Thumbnail> {
+    None
+}
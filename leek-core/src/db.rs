@@ -0,0 +1,650 @@
+use anyhow::Result;
+use rusqlite::{Connection, OptionalExtension, params};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::library::Track;
+use crate::paths;
+use crate::query::Stats;
+use crate::session::Session;
+use crate::tags::Tags;
+
+const SCHEMA_V1: &str = "
+CREATE TABLE tracks (
+    path TEXT PRIMARY KEY,
+    mtime INTEGER NOT NULL,
+    title TEXT,
+    artist TEXT,
+    album TEXT,
+    year INTEGER,
+    track_number INTEGER,
+    duration_ms INTEGER
+);
+CREATE TABLE history (
+    id INTEGER PRIMARY KEY,
+    path TEXT NOT NULL,
+    played_at INTEGER NOT NULL
+);
+CREATE INDEX history_path ON history (path);
+CREATE TABLE ratings (
+    path TEXT PRIMARY KEY,
+    rating INTEGER NOT NULL
+);
+CREATE TABLE positions (
+    path TEXT PRIMARY KEY,
+    position_ms INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+CREATE TABLE bookmarks (
+    id INTEGER PRIMARY KEY,
+    path TEXT NOT NULL,
+    position_ms INTEGER NOT NULL,
+    name TEXT NOT NULL
+);
+";
+
+// Existing rows are marked stale so the next scan re-reads their tags.
+const ALBUM_ARTIST_V2: &str = "
+ALTER TABLE tracks ADD COLUMN album_artist TEXT;
+ALTER TABLE tracks ADD COLUMN compilation INTEGER NOT NULL DEFAULT 0;
+UPDATE tracks SET mtime = 0;
+";
+
+const DISC_NUMBER_V3: &str = "
+ALTER TABLE tracks ADD COLUMN disc_number INTEGER;
+UPDATE tracks SET mtime = 0;
+";
+
+const INBOX_V4: &str = "
+CREATE TABLE inbox (
+    path TEXT PRIMARY KEY,
+    added_at INTEGER NOT NULL
+);
+";
+
+// Files already in the library count as added when they were last changed.
+const ADDED_AT_V5: &str = "
+ALTER TABLE tracks ADD COLUMN added_at INTEGER NOT NULL DEFAULT 0;
+UPDATE tracks SET added_at = mtime;
+";
+
+// The session as of the last checkpoint: one row for the player, plus the
+// queue in order.
+const SESSION_V6: &str = "
+CREATE TABLE session (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    queue_index INTEGER NOT NULL,
+    position_ms INTEGER NOT NULL,
+    volume INTEGER NOT NULL,
+    playing INTEGER NOT NULL
+);
+CREATE TABLE session_queue (
+    position INTEGER PRIMARY KEY,
+    path TEXT NOT NULL
+);
+";
+
+// The volume last used on each output device, saved with the session.
+const DEVICE_VOLUMES_V7: &str = "
+CREATE TABLE device_volumes (
+    device TEXT PRIMARY KEY,
+    volume INTEGER NOT NULL
+);
+";
+
+// A hand-set volume offset for a track, in decibels.
+const TRACK_GAIN_V8: &str = "
+CREATE TABLE track_gain (
+    path TEXT PRIMARY KEY,
+    gain_db REAL NOT NULL
+);
+";
+
+// Every table with a row per file, for `move_path`.
+const PATH_TABLES: &[&str] = &[
+    "tracks",
+    "history",
+    "ratings",
+    "positions",
+    "bookmarks",
+    "inbox",
+    "track_gain",
+    "session_queue",
+];
+
+const MIGRATIONS: &[&str] = &[
+    SCHEMA_V1,
+    ALBUM_ARTIST_V2,
+    DISC_NUMBER_V3,
+    INBOX_V4,
+    ADDED_AT_V5,
+    SESSION_V6,
+    DEVICE_VOLUMES_V7,
+    TRACK_GAIN_V8,
+];
+
+#[derive(Clone, Debug)]
+pub struct HistoryEntry {
+    pub path: PathBuf,
+    pub played_at: i64,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub title: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct PlayCount {
+    pub path: PathBuf,
+    pub plays: u64,
+    pub last_played: i64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Cue {
+    pub id: i64,
+    pub position: Duration,
+    pub name: String,
+}
+
+pub struct Database {
+    conn: Connection,
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn path_key(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+impl Database {
+    pub fn open() -> Result<Self> {
+        let dir = paths::data_dir().ok_or_else(|| anyhow::anyhow!("no data directory"))?;
+        Self::open_at(&dir.join("leek.db"))
+    }
+
+    pub fn open_at(path: &Path) -> Result<Self> {
+        let mut db = Self {
+            conn: Connection::open(path)?,
+        };
+        db.migrate()?;
+        Ok(db)
+    }
+
+    fn migrate(&mut self) -> Result<()> {
+        let version: usize = self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let tx = self.conn.transaction()?;
+            tx.execute_batch(migration)?;
+            tx.pragma_update(None, "user_version", i + 1)?;
+            tx.commit()?;
+        }
+        Ok(())
+    }
+
+    pub fn load_tracks(&self) -> Result<Vec<Track>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT path, mtime, title, artist, album, year, track_number, duration_ms,
+                    album_artist, compilation, disc_number, added_at
+             FROM tracks ORDER BY path",
+        )?;
+        let tracks = stmt
+            .query_map([], |row| {
+                let path: String = row.get(0)?;
+                let duration_ms: Option<u64> = row.get(7)?;
+                Ok(Track {
+                    path: PathBuf::from(path),
+                    mtime: row.get(1)?,
+                    added_at: row.get(11)?,
+                    tags: Tags {
+                        title: row.get(2)?,
+                        artist: row.get(3)?,
+                        album: row.get(4)?,
+                        album_artist: row.get(8)?,
+                        compilation: row.get(9)?,
+                        year: row.get(5)?,
+                        track_number: row.get(6)?,
+                        disc_number: row.get(10)?,
+                        duration: duration_ms.map(Duration::from_millis),
+                    },
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(tracks)
+    }
+
+    pub fn save_tracks(&mut self, tracks: &[Track]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM tracks", [])?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO tracks
+                 (path, mtime, title, artist, album, year, track_number, duration_ms,
+                  album_artist, compilation, disc_number, added_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            )?;
+            for track in tracks {
+                let tags = &track.tags;
+                stmt.execute(params![
+                    path_key(&track.path),
+                    track.mtime,
+                    tags.title,
+                    tags.artist,
+                    tags.album,
+                    tags.year,
+                    tags.track_number,
+                    tags.duration.map(|d| d.as_millis() as u64),
+                    tags.album_artist,
+                    tags.compilation,
+                    tags.disc_number,
+                    track.added_at,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn record_play(&self, path: &Path) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO history (path, played_at) VALUES (?1, ?2)",
+            params![path_key(path), now()],
+        )?;
+        Ok(())
+    }
+
+    // Most recently played first, each track once.
+    pub fn recently_played(&self, limit: usize) -> Result<Vec<PathBuf>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT path FROM history GROUP BY path
+             ORDER BY MAX(played_at) DESC, MAX(id) DESC LIMIT ?1",
+        )?;
+        let paths = stmt
+            .query_map([limit as i64], |row| row.get::<_, String>(0))?
+            .map(|path| path.map(PathBuf::from))
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(paths)
+    }
+
+    pub fn history(&self) -> Result<Vec<HistoryEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT h.path, h.played_at, t.artist, t.album, t.title
+             FROM history h LEFT JOIN tracks t ON t.path = h.path
+             ORDER BY h.played_at",
+        )?;
+        let entries = stmt
+            .query_map([], |row| {
+                let path: String = row.get(0)?;
+                Ok(HistoryEntry {
+                    path: PathBuf::from(path),
+                    played_at: row.get(1)?,
+                    artist: row.get(2)?,
+                    album: row.get(3)?,
+                    title: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(entries)
+    }
+
+    pub fn play_counts(&self) -> Result<Vec<PlayCount>> {
+        self.play_counts_since(0)
+    }
+
+    // Counts only plays at or after `since`, in seconds since the epoch.
+    pub fn play_counts_since(&self, since: i64) -> Result<Vec<PlayCount>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT path, COUNT(*), MAX(played_at) FROM history
+             WHERE played_at >= ?1
+             GROUP BY path ORDER BY COUNT(*) DESC, path",
+        )?;
+        let counts = stmt
+            .query_map([since], |row| {
+                let path: String = row.get(0)?;
+                Ok(PlayCount {
+                    path: PathBuf::from(path),
+                    plays: row.get(1)?,
+                    last_played: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(counts)
+    }
+
+    // Ratings and play history for every track that has either.
+    pub fn track_stats(&self) -> Result<HashMap<PathBuf, Stats>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT path, MAX(rating), SUM(plays), MAX(last_played) FROM (
+                 SELECT path, NULL AS rating, COUNT(*) AS plays,
+                        MAX(played_at) AS last_played
+                 FROM history GROUP BY path
+                 UNION ALL
+                 SELECT path, rating, 0, NULL FROM ratings
+             ) GROUP BY path",
+        )?;
+        let stats = stmt
+            .query_map([], |row| {
+                let path: String = row.get(0)?;
+                Ok((
+                    PathBuf::from(path),
+                    Stats {
+                        rating: row.get(1)?,
+                        plays: row.get(2)?,
+                        last_played: row.get(3)?,
+                    },
+                ))
+            })?
+            .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+        Ok(stats)
+    }
+
+    // 1 to 5 stars; 0 clears the rating.
+    pub fn set_rating(&self, path: &Path, stars: u32) -> Result<()> {
+        if stars == 0 {
+            self.conn
+                .execute("DELETE FROM ratings WHERE path = ?1", [path_key(path)])?;
+        } else {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO ratings (path, rating) VALUES (?1, ?2)",
+                params![path_key(path), stars.min(5)],
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn add_cue(&self, path: &Path, position: Duration, name: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO bookmarks (path, position_ms, name) VALUES (?1, ?2, ?3)",
+            params![path_key(path), position.as_millis() as u64, name],
+        )?;
+        Ok(())
+    }
+
+    pub fn cues(&self, path: &Path) -> Result<Vec<Cue>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, position_ms, name FROM bookmarks
+             WHERE path = ?1 ORDER BY position_ms",
+        )?;
+        let cues = stmt
+            .query_map([path_key(path)], |row| {
+                Ok(Cue {
+                    id: row.get(0)?,
+                    position: Duration::from_millis(row.get(1)?),
+                    name: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(cues)
+    }
+
+    pub fn delete_cue(&self, id: i64) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM bookmarks WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    pub fn track_gain(&self, path: &Path) -> Result<f32> {
+        let gain: Option<f64> = self
+            .conn
+            .query_row(
+                "SELECT gain_db FROM track_gain WHERE path = ?1",
+                [path_key(path)],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(gain.unwrap_or(0.0) as f32)
+    }
+
+    // 0 dB is the same as no offset, so it isn't kept.
+    pub fn set_track_gain(&self, path: &Path, gain_db: f32) -> Result<()> {
+        if gain_db == 0.0 {
+            self.conn
+                .execute("DELETE FROM track_gain WHERE path = ?1", [path_key(path)])?;
+        } else {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO track_gain (path, gain_db) VALUES (?1, ?2)",
+                params![path_key(path), gain_db as f64],
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn save_position(&self, path: &Path, position: Duration) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO positions (path, position_ms, updated_at)
+             VALUES (?1, ?2, ?3)",
+            params![path_key(path), position.as_millis() as u64, now()],
+        )?;
+        Ok(())
+    }
+
+    pub fn position(&self, path: &Path) -> Result<Option<Duration>> {
+        let position = self
+            .conn
+            .query_row(
+                "SELECT position_ms FROM positions WHERE path = ?1",
+                [path_key(path)],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(position.map(Duration::from_millis))
+    }
+
+    pub fn clear_position(&self, path: &Path) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM positions WHERE path = ?1", [path_key(path)])?;
+        Ok(())
+    }
+
+    // Returns how many of the paths were new to the inbox.
+    pub fn add_to_inbox(&mut self, paths: &[PathBuf]) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        let mut added = 0;
+        {
+            let mut stmt =
+                tx.prepare("INSERT OR IGNORE INTO inbox (path, added_at) VALUES (?1, ?2)")?;
+            let added_at = now();
+            for path in paths {
+                added += stmt.execute(params![path_key(path), added_at])?;
+            }
+        }
+        tx.commit()?;
+        Ok(added)
+    }
+
+    pub fn inbox(&self) -> Result<Vec<PathBuf>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT path FROM inbox ORDER BY added_at, rowid")?;
+        let paths = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .map(|path| path.map(PathBuf::from))
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(paths)
+    }
+
+    pub fn remove_from_inbox(&self, path: &Path) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM inbox WHERE path = ?1", [path_key(path)])?;
+        Ok(())
+    }
+
+    pub fn load_session(&self) -> Result<Option<Session>> {
+        let session = self
+            .conn
+            .query_row(
+                "SELECT queue_index, position_ms, volume, playing FROM session WHERE id = 1",
+                [],
+                |row| {
+                    Ok(Session {
+                        index: row.get::<_, i64>(0)? as usize,
+                        position_ms: row.get::<_, i64>(1)? as u64,
+                        volume: row.get(2)?,
+                        playing: row.get(3)?,
+                        ..Session::default()
+                    })
+                },
+            )
+            .optional()?;
+        let Some(mut session) = session else {
+            return Ok(None);
+        };
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT path FROM session_queue ORDER BY position")?;
+        session.queue = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+        let mut stmt = self
+            .conn
+            .prepare("SELECT device, volume FROM device_volumes")?;
+        session.device_volumes = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(Some(session))
+    }
+
+    // In one transaction, so a crash mid-write leaves the previous
+    // checkpoint intact.
+    pub fn save_session(&mut self, session: &Session) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT OR REPLACE INTO session (id, queue_index, position_ms, volume, playing)
+             VALUES (1, ?1, ?2, ?3, ?4)",
+            params![
+                session.index as i64,
+                session.position_ms as i64,
+                session.volume,
+                session.playing
+            ],
+        )?;
+        tx.execute("DELETE FROM session_queue", [])?;
+        for (i, path) in session.queue.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO session_queue (position, path) VALUES (?1, ?2)",
+                params![i as i64, path_key(path)],
+            )?;
+        }
+        tx.execute("DELETE FROM device_volumes", [])?;
+        for (device, volume) in &session.device_volumes {
+            tx.execute(
+                "INSERT INTO device_volumes (device, volume) VALUES (?1, ?2)",
+                params![device, volume],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // Carries a file's history, rating, cues and the rest over to where it
+    // was moved, all at once or not at all.
+    pub fn move_path(&mut self, from: &Path, to: &Path) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        for table in PATH_TABLES {
+            tx.execute(
+                &format!("UPDATE OR REPLACE {} SET path = ?2 WHERE path = ?1", table),
+                params![path_key(from), path_key(to)],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::Query;
+
+    #[test]
+    fn sessions_round_trip() {
+        let mut db = Database::open_at(Path::new(":memory:")).unwrap();
+        assert_eq!(db.load_session().unwrap(), None);
+
+        let mut session = Session {
+            queue: vec![
+                PathBuf::from("/music/b.flac"),
+                PathBuf::from("/music/a.flac"),
+            ],
+            index: 1,
+            position_ms: 61_500,
+            volume: 70,
+            playing: true,
+            ..Session::default()
+        };
+        session.device_volumes.insert("Headphones".into(), 40);
+        db.save_session(&session).unwrap();
+        assert_eq!(db.load_session().unwrap(), Some(session.clone()));
+
+        session.queue.truncate(1);
+        session.device_volumes.clear();
+        db.save_session(&session).unwrap();
+        assert_eq!(db.load_session().unwrap(), Some(session.clone()));
+
+        // A renamed file stays in the queue.
+        db.move_path(Path::new("/music/b.flac"), Path::new("/music/c.flac"))
+            .unwrap();
+        session.queue = vec![PathBuf::from("/music/c.flac")];
+        assert_eq!(db.load_session().unwrap(), Some(session));
+    }
+
+    #[test]
+    fn move_path_follows_every_table() {
+        let mut db = Database::open_at(Path::new(":memory:")).unwrap();
+        let (from, to) = (Path::new("/music/a.flac"), Path::new("/music/b.flac"));
+
+        db.record_play(from).unwrap();
+        db.add_cue(from, Duration::from_secs(3), "chorus").unwrap();
+        db.set_track_gain(from, 2.0).unwrap();
+        db.save_position(from, Duration::from_secs(9)).unwrap();
+        db.add_to_inbox(&[from.to_path_buf()]).unwrap();
+        db.move_path(from, to).unwrap();
+
+        assert_eq!(db.recently_played(10).unwrap(), vec![to.to_path_buf()]);
+        assert_eq!(db.cues(to).unwrap().len(), 1);
+        assert!(db.cues(from).unwrap().is_empty());
+        assert_eq!(db.track_gain(to).unwrap(), 2.0);
+        assert_eq!(db.position(to).unwrap(), Some(Duration::from_secs(9)));
+        assert_eq!(db.inbox().unwrap(), vec![to.to_path_buf()]);
+    }
+
+    #[test]
+    fn ratings_can_be_searched() {
+        let db = Database::open_at(Path::new(":memory:")).unwrap();
+        let track = |name: &str| Track {
+            path: PathBuf::from(name),
+            mtime: 0,
+            added_at: 0,
+            tags: Tags::default(),
+        };
+        let (loved, liked, unrated) = (track("loved.flac"), track("liked.flac"), track("new.flac"));
+        db.set_rating(&loved.path, 5).unwrap();
+        db.set_rating(&liked.path, 2).unwrap();
+        db.set_rating(&liked.path, 3).unwrap();
+
+        let stats = db.track_stats().unwrap();
+        let query = Query::parse("rating>=4");
+        let matches = |track: &Track| {
+            query.matches(track, &stats.get(&track.path).cloned().unwrap_or_default())
+        };
+        assert!(matches(&loved));
+        assert!(!matches(&liked));
+        assert!(!matches(&unrated));
+        assert_eq!(stats[&liked.path].rating, Some(3));
+
+        db.set_rating(&loved.path, 0).unwrap();
+        assert!(!db.track_stats().unwrap().contains_key(&loved.path));
+    }
+}
@@ -0,0 +1,399 @@
+use anyhow::{Result, anyhow};
+use rodio::buffer::SamplesBuffer;
+use rodio::{Decoder, Source};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{Decoder as CodecDecoder, DecoderOptions};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use crate::remote;
+use crate::sandbox;
+use crate::tags::{self, LoopPoints, Tags};
+
+pub type BoxedSource = Box<dyn Source<Item = i16> + Send>;
+
+const FFMPEG_SAMPLE_RATE: u32 = 44100;
+const FFMPEG_CHANNELS: u16 = 2;
+
+// A decoded file and what the player needs from its tags, read by
+// whichever process did the decoding.
+pub struct Opened {
+    pub source: BoxedSource,
+    pub tags: Tags,
+    pub loop_points: Option<LoopPoints>,
+}
+
+pub fn open(path: &Path) -> Result<Opened> {
+    open_with(path, None)
+}
+
+// With `sandbox`, the decoding happens in a child process running that
+// program, which reads the tags too, so nothing parses the file in this
+// one; see `sandbox::open`.
+pub fn open_with(path: &Path, sandbox: Option<&Path>) -> Result<Opened> {
+    let mut opened = match sandbox {
+        Some(program) => sandbox::open(program, path)?,
+        None => {
+            let source = decode(path)?;
+            let (tags, loop_points) = tags::read_for_playback(path);
+            Opened {
+                source,
+                tags,
+                loop_points,
+            }
+        }
+    };
+    if let Some(points) = opened.loop_points {
+        opened.source = Box::new(LoopSource::new(opened.source, points));
+    }
+    Ok(opened)
+}
+
+pub(crate) fn decode(path: &Path) -> Result<BoxedSource> {
+    if remote::is_remote(path) {
+        return open_remote(path);
+    }
+    let mut errors = Vec::new();
+
+    match open_rodio(path) {
+        Ok(source) => return Ok(source),
+        Err(e) => errors.push(format!("rodio: {}", e)),
+    }
+
+    match open_symphonia(path) {
+        Ok(source) => return Ok(source),
+        Err(e) => errors.push(format!("symphonia: {}", e)),
+    }
+
+    match open_ffmpeg(path) {
+        Ok(source) => return Ok(source),
+        Err(e) => errors.push(format!("ffmpeg: {}", e)),
+    }
+
+    Err(anyhow!(errors.join("; ")))
+}
+
+// Decodes a whole file as fast as possible, ignoring loop points, and
+// returns how much audio it held. For `leek bench`.
+pub fn decode_all(path: &Path) -> Result<Duration> {
+    let source = decode(path)?;
+    let rate = source.sample_rate() as f64 * source.channels() as f64;
+    let samples = source.count();
+    Ok(Duration::from_secs_f64(samples as f64 / rate.max(1.0)))
+}
+
+pub struct Preload {
+    pub path: PathBuf,
+    rx: mpsc::Receiver<Result<Opened>>,
+    ready: Option<Result<Opened>>,
+}
+
+impl Preload {
+    pub fn spawn(path: PathBuf, sandbox: Option<PathBuf>) -> Preload {
+        let (tx, rx) = mpsc::channel();
+        let thread_path = path.clone();
+        thread::spawn(move || {
+            let _ = tx.send(open_with(&thread_path, sandbox.as_deref()));
+        });
+        Preload {
+            path,
+            rx,
+            ready: None,
+        }
+    }
+
+    // The file's tags, once it has been opened.
+    pub fn tags(&mut self) -> Option<&Tags> {
+        if self.ready.is_none() {
+            self.ready = self.rx.try_recv().ok();
+        }
+        self.ready
+            .as_ref()?
+            .as_ref()
+            .ok()
+            .map(|opened| &opened.tags)
+    }
+
+    // The thread only hangs up without an answer if the decoder panicked,
+    // which trying again here would only repeat.
+    pub fn wait(self) -> Result<Opened> {
+        match self.ready {
+            Some(result) => result,
+            None => self
+                .rx
+                .recv()
+                .unwrap_or_else(|_| Err(anyhow!("decoder panicked"))),
+        }
+    }
+}
+
+fn open_rodio(path: &Path) -> Result<BoxedSource> {
+    let file = File::open(path)?;
+    let source = Decoder::new(BufReader::new(file))?;
+    Ok(Box::new(source))
+}
+
+// Remote files stream through rodio only; the fallbacks need a local file.
+fn open_remote(path: &Path) -> Result<BoxedSource> {
+    let source = Decoder::new(remote::open(path)?)?;
+    Ok(Box::new(source))
+}
+
+fn open_symphonia(path: &Path) -> Result<BoxedSource> {
+    Ok(Box::new(SymphoniaSource::new(path)?))
+}
+
+fn open_ffmpeg(path: &Path) -> Result<BoxedSource> {
+    let output = Command::new("ffmpeg")
+        .args(["-v", "error", "-i"])
+        .arg(path)
+        .args(["-f", "s16le", "-acodec", "pcm_s16le"])
+        .args(["-ac", &FFMPEG_CHANNELS.to_string()])
+        .args(["-ar", &FFMPEG_SAMPLE_RATE.to_string()])
+        .arg("-")
+        .output()?;
+
+    if !output.status.success() || output.stdout.is_empty() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("{}", stderr.trim()));
+    }
+
+    let samples: Vec<i16> = output
+        .stdout
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+        .collect();
+
+    Ok(Box::new(SamplesBuffer::new(
+        FFMPEG_CHANNELS,
+        FFMPEG_SAMPLE_RATE,
+        samples,
+    )))
+}
+
+// Decodes with symphonia directly, skipping packets that fail to decode
+// instead of ending the stream like the stock decoders do.
+struct SymphoniaSource {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn CodecDecoder>,
+    track_id: u32,
+    buffer: Vec<i16>,
+    position: usize,
+    channels: u16,
+    sample_rate: u32,
+    total_duration: Option<Duration>,
+}
+
+impl SymphoniaSource {
+    fn new(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+        let mut hint = Hint::new();
+        if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+            hint.with_extension(ext);
+        }
+
+        let probed = symphonia::default::get_probe().format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )?;
+        let format = probed.format;
+
+        let track = format
+            .default_track()
+            .ok_or_else(|| anyhow!("no audio track"))?;
+        let track_id = track.id;
+        let params = track.codec_params.clone();
+
+        let decoder = symphonia::default::get_codecs().make(&params, &DecoderOptions::default())?;
+
+        let total_duration = match (params.n_frames, params.sample_rate) {
+            (Some(frames), Some(rate)) if rate > 0 => {
+                Some(Duration::from_secs_f64(frames as f64 / rate as f64))
+            }
+            _ => None,
+        };
+
+        let mut source = Self {
+            format,
+            decoder,
+            track_id,
+            buffer: Vec::new(),
+            position: 0,
+            channels: params.channels.map(|c| c.count() as u16).unwrap_or(2),
+            sample_rate: params.sample_rate.unwrap_or(44100),
+            total_duration,
+        };
+
+        if !source.refill() {
+            return Err(anyhow!("no decodable audio"));
+        }
+
+        Ok(source)
+    }
+
+    fn refill(&mut self) -> bool {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(_) => return false,
+            };
+
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            match self.decoder.decode(&packet) {
+                Ok(decoded) => {
+                    let spec = *decoded.spec();
+                    let mut samples = SampleBuffer::<i16>::new(decoded.capacity() as u64, spec);
+                    samples.copy_interleaved_ref(decoded);
+
+                    if samples.samples().is_empty() {
+                        continue;
+                    }
+
+                    self.channels = spec.channels.count() as u16;
+                    self.sample_rate = spec.rate;
+                    self.buffer = samples.samples().to_vec();
+                    self.position = 0;
+                    return true;
+                }
+                Err(SymphoniaError::DecodeError(_)) => continue,
+                Err(_) => return false,
+            }
+        }
+    }
+}
+
+impl Iterator for SymphoniaSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.position >= self.buffer.len() && !self.refill() {
+            return None;
+        }
+
+        let sample = self.buffer[self.position];
+        self.position += 1;
+        Some(sample)
+    }
+}
+
+impl Source for SymphoniaSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.buffer.len() - self.position)
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.total_duration
+    }
+}
+
+// Plays through to the loop end once, keeping the looped section in memory,
+// then repeats that section forever without reopening the file.
+struct LoopSource {
+    inner: BoxedSource,
+    start: usize,
+    end: usize,
+    played: usize,
+    buffer: Vec<i16>,
+    replay: Option<usize>,
+}
+
+impl LoopSource {
+    fn new(inner: BoxedSource, points: LoopPoints) -> Self {
+        // The ffmpeg fallback resamples, so the frame offsets are rescaled.
+        let scale = inner.sample_rate() as f64 / points.sample_rate as f64;
+        let channels = inner.channels() as usize;
+        let to_samples = |frames: u64| (frames as f64 * scale) as usize * channels;
+
+        Self {
+            start: to_samples(points.start),
+            end: to_samples(points.end),
+            inner,
+            played: 0,
+            buffer: Vec::new(),
+            replay: None,
+        }
+    }
+
+    fn start_replay(&mut self) -> Option<i16> {
+        if self.buffer.is_empty() {
+            return None;
+        }
+        self.replay = Some(0);
+        self.next()
+    }
+}
+
+impl Iterator for LoopSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if let Some(i) = self.replay {
+            self.replay = Some((i + 1) % self.buffer.len());
+            return Some(self.buffer[i]);
+        }
+
+        let Some(sample) = self.inner.next() else {
+            return self.start_replay();
+        };
+        if self.played >= self.start {
+            self.buffer.push(sample);
+        }
+        self.played += 1;
+        if self.played >= self.end && !self.buffer.is_empty() {
+            self.replay = Some(0);
+        }
+        Some(sample)
+    }
+}
+
+impl Source for LoopSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        match self.replay {
+            Some(i) => Some(self.buffer.len() - i),
+            None => {
+                let left = self.end.saturating_sub(self.played);
+                match self.inner.current_frame_len() {
+                    Some(len) if left > 0 => Some(len.min(left)),
+                    len => len,
+                }
+            }
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+}
@@ -0,0 +1,298 @@
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, mpsc};
+use std::thread;
+
+const CHUNK_SIZE: usize = 256 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Codec {
+    Opus,
+    Mp3,
+}
+
+impl Codec {
+    fn extension(self) -> &'static str {
+        match self {
+            Codec::Opus => "opus",
+            Codec::Mp3 => "mp3",
+        }
+    }
+
+    fn ffmpeg_args(self) -> [&'static str; 4] {
+        match self {
+            Codec::Opus => ["-c:a", "libopus", "-f", "opus"],
+            Codec::Mp3 => ["-c:a", "libmp3lame", "-f", "mp3"],
+        }
+    }
+}
+
+// Lossless files are encoded with ffmpeg on the way out; lossy ones are
+// copied as they are, since re-encoding them only loses quality.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transcode {
+    pub codec: Codec,
+    pub bitrate: u32,
+}
+
+fn is_lossless(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| matches!(ext.to_lowercase().as_str(), "flac" | "wav"))
+        .unwrap_or(false)
+}
+
+// Copies tracks to a device in the background. Each file keeps its place
+// under the library root (files from elsewhere go into a folder named
+// after their own), and files already there with the same size are
+// skipped, so exporting the same folder twice is cheap.
+pub struct Export {
+    rx: mpsc::Receiver<(PathBuf, Result<bool>)>,
+    copied: Arc<AtomicU64>,
+    total_bytes: u64,
+    total_files: usize,
+    done: usize,
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl Export {
+    pub fn start(
+        paths: Vec<PathBuf>,
+        dest: PathBuf,
+        root: PathBuf,
+        transcode: Option<Transcode>,
+    ) -> Export {
+        let total_bytes = paths
+            .iter()
+            .filter_map(|path| fs::metadata(path).ok())
+            .map(|meta| meta.len())
+            .sum();
+        let total_files = paths.len();
+        let copied = Arc::new(AtomicU64::new(0));
+        let (tx, rx) = mpsc::channel();
+
+        let thread_copied = Arc::clone(&copied);
+        thread::spawn(move || {
+            let mut claimed = HashMap::new();
+            for path in paths {
+                let target = device_path(&path, &dest, Some(&root), transcode);
+                let result = claim(&mut claimed, target, &path)
+                    .and_then(|()| send(&path, &dest, Some(&root), transcode, &thread_copied));
+                if tx.send((path, result)).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Export {
+            rx,
+            copied,
+            total_bytes,
+            total_files,
+            done: 0,
+            failed: Vec::new(),
+        }
+    }
+
+    // Collects finished files and returns true once the copy thread is done.
+    pub fn poll(&mut self) -> bool {
+        loop {
+            match self.rx.try_recv() {
+                Ok((path, result)) => {
+                    self.done += 1;
+                    if let Err(e) = result {
+                        self.failed.push((path, e.to_string()));
+                    }
+                }
+                Err(mpsc::TryRecvError::Empty) => return false,
+                Err(mpsc::TryRecvError::Disconnected) => return true,
+            }
+        }
+    }
+
+    pub fn files(&self) -> (usize, usize) {
+        (self.done, self.total_files)
+    }
+
+    pub fn percent(&self) -> u64 {
+        if self.total_bytes == 0 {
+            return 100;
+        }
+        (self.copied.load(Ordering::Relaxed) * 100 / self.total_bytes).min(100)
+    }
+}
+
+// Where a track ends up under `dest`, taking the change of format into
+// account.
+pub(crate) fn device_path(
+    path: &Path,
+    dest: &Path,
+    root: Option<&Path>,
+    transcode: Option<Transcode>,
+) -> PathBuf {
+    match transcode {
+        Some(transcode) if is_lossless(path) => {
+            target_path(path, dest, root).with_extension(transcode.codec.extension())
+        }
+        _ => target_path(path, dest, root),
+    }
+}
+
+// Records that `target` is `path`'s, failing if another track already
+// has it, so one is never copied over the other.
+pub(crate) fn claim(
+    claimed: &mut HashMap<PathBuf, PathBuf>,
+    target: PathBuf,
+    path: &Path,
+) -> Result<()> {
+    match claimed.entry(target) {
+        Entry::Occupied(entry) if entry.get() != path => Err(anyhow!(
+            "{} goes to the same place on the device",
+            entry.get().display()
+        )),
+        Entry::Occupied(_) => Ok(()),
+        Entry::Vacant(entry) => {
+            entry.insert(path.to_path_buf());
+            Ok(())
+        }
+    }
+}
+
+// Returns whether the file was written, rather than already being there.
+pub(crate) fn send(
+    path: &Path,
+    dest: &Path,
+    root: Option<&Path>,
+    transcode: Option<Transcode>,
+    copied: &AtomicU64,
+) -> Result<bool> {
+    let target = target_path(path, dest, root);
+    match transcode {
+        Some(transcode) if is_lossless(path) => transcode_file(path, target, transcode, copied),
+        _ => export_file(path, &target, copied),
+    }
+}
+
+fn target_path(path: &Path, dest: &Path, root: Option<&Path>) -> PathBuf {
+    if let Some(relative) = root.and_then(|root| path.strip_prefix(root).ok())
+        && relative
+            .components()
+            .all(|part| matches!(part, Component::Normal(_)))
+    {
+        return dest.join(relative);
+    }
+    let mut target = dest.to_path_buf();
+    if let Some(album) = path.parent().and_then(Path::file_name) {
+        target.push(album);
+    }
+    target.push(path.file_name().unwrap_or_default());
+    target
+}
+
+fn export_file(path: &Path, target: &Path, copied: &AtomicU64) -> Result<bool> {
+    let size = fs::metadata(path)?.len();
+    if fs::metadata(target)
+        .map(|meta| meta.len() == size)
+        .unwrap_or(false)
+    {
+        copied.fetch_add(size, Ordering::Relaxed);
+        return Ok(false);
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // Copied in chunks rather than with fs::copy so progress can be shown
+    // while large files go to slow USB storage.
+    let mut input = File::open(path)?;
+    let mut output = File::create(target)?;
+    let mut buffer = vec![0; CHUNK_SIZE];
+    loop {
+        let read = input.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        output.write_all(&buffer[..read])?;
+        copied.fetch_add(read as u64, Ordering::Relaxed);
+    }
+    output.sync_all()?;
+    Ok(true)
+}
+
+fn transcode_file(
+    path: &Path,
+    target: PathBuf,
+    transcode: Transcode,
+    copied: &AtomicU64,
+) -> Result<bool> {
+    let size = fs::metadata(path)?.len();
+    let target = target.with_extension(transcode.codec.extension());
+    if target.exists() {
+        copied.fetch_add(size, Ordering::Relaxed);
+        return Ok(false);
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // Encoded to a temporary name first, so an interrupted export doesn't
+    // leave a truncated file that the next run would skip.
+    let partial = target.with_extension("part");
+    let output = Command::new("ffmpeg")
+        .args(["-v", "error", "-y", "-i"])
+        .arg(path)
+        .args(["-vn", "-map_metadata", "0"])
+        .args(transcode.codec.ffmpeg_args())
+        .args(["-b:a", &format!("{}k", transcode.bitrate)])
+        .arg(&partial)
+        .output()?;
+
+    if !output.status.success() {
+        let _ = fs::remove_file(&partial);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("ffmpeg: {}", stderr.trim()));
+    }
+    fs::rename(&partial, &target)?;
+    copied.fetch_add(size, Ordering::Relaxed);
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn albums_with_the_same_name_stay_apart() {
+        let root = tempfile::tempdir().unwrap();
+        let (music, device) = (root.path().join("music"), root.path().join("device"));
+        let elsewhere = root.path().join("elsewhere");
+        let paths = vec![
+            music.join("ABBA/Greatest Hits/01.flac"),
+            music.join("Queen/Greatest Hits/01.flac"),
+            elsewhere.join("Greatest Hits/01.flac"),
+            elsewhere.join("Other/Greatest Hits/01.flac"),
+        ];
+        for (i, path) in paths.iter().enumerate() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, i.to_string()).unwrap();
+        }
+
+        let mut export = Export::start(paths.clone(), device.clone(), music, None);
+        while !export.poll() {
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(export.files(), (4, 4));
+        let read = |path: &str| fs::read_to_string(device.join(path)).unwrap();
+        assert_eq!(read("ABBA/Greatest Hits/01.flac"), "0");
+        assert_eq!(read("Queen/Greatest Hits/01.flac"), "1");
+        assert_eq!(read("Greatest Hits/01.flac"), "2");
+        assert_eq!(export.failed.len(), 1);
+        assert_eq!(export.failed[0].0, paths[3]);
+    }
+}
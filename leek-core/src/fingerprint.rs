@@ -0,0 +1,142 @@
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use std::path::Path;
+use std::process::Command;
+
+// Two raw fingerprints whose bits differ less than this are the same
+// recording, give or take encoding and a little trimming.
+const DUPLICATE_BIT_ERROR: f64 = 0.15;
+
+// Chromaprint fingerprints from `fpcalc`, which ships with Chromaprint.
+// The compressed form is what AcoustID takes; the raw one is a list of
+// 32-bit sub-fingerprints that can be compared directly.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Fingerprint {
+    pub duration: f64,
+    pub fingerprint: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RawFingerprint {
+    pub duration: f64,
+    pub fingerprint: Vec<u32>,
+}
+
+fn fpcalc(path: &Path, raw: bool) -> Result<Vec<u8>> {
+    let mut command = Command::new("fpcalc");
+    command.arg("-json");
+    if raw {
+        command.arg("-raw");
+    }
+    let output = command
+        .arg(path)
+        .output()
+        .map_err(|e| anyhow!("fpcalc (Chromaprint) is not installed: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("fpcalc: {}", stderr.trim()));
+    }
+    Ok(output.stdout)
+}
+
+pub fn fingerprint(path: &Path) -> Result<Fingerprint> {
+    Ok(serde_json::from_slice(&fpcalc(path, false)?)?)
+}
+
+pub fn raw_fingerprint(path: &Path) -> Result<RawFingerprint> {
+    Ok(serde_json::from_slice(&fpcalc(path, true)?)?)
+}
+
+impl RawFingerprint {
+    // The share of differing bits over the stretch both fingerprints cover.
+    pub fn bit_error(&self, other: &RawFingerprint) -> f64 {
+        let len = self.fingerprint.len().min(other.fingerprint.len());
+        if len == 0 {
+            return 1.0;
+        }
+        let differing: u32 = self
+            .fingerprint
+            .iter()
+            .zip(&other.fingerprint)
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum();
+        differing as f64 / (len * 32) as f64
+    }
+
+    pub fn same_recording(&self, other: &RawFingerprint) -> bool {
+        (self.duration - other.duration).abs() < 5.0 && self.bit_error(other) < DUPLICATE_BIT_ERROR
+    }
+}
+
+// A recording AcoustID matched, with MusicBrainz ids to look further.
+#[cfg(feature = "acoustid")]
+#[derive(Clone, Debug)]
+pub struct Match {
+    pub score: f64,
+    pub recording_id: String,
+    pub title: Option<String>,
+    pub artists: Vec<String>,
+}
+
+// Asks AcoustID which MusicBrainz recordings sound like this. Needs a free
+// application key from acoustid.org.
+#[cfg(feature = "acoustid")]
+pub fn lookup(fingerprint: &Fingerprint, key: &str) -> Result<Vec<Match>> {
+    #[derive(Deserialize)]
+    struct Response {
+        status: String,
+        #[serde(default)]
+        results: Vec<LookupResult>,
+        error: Option<Error>,
+    }
+    #[derive(Deserialize)]
+    struct Error {
+        message: String,
+    }
+    #[derive(Deserialize)]
+    struct LookupResult {
+        score: f64,
+        #[serde(default)]
+        recordings: Vec<Recording>,
+    }
+    #[derive(Deserialize)]
+    struct Recording {
+        id: String,
+        title: Option<String>,
+        #[serde(default)]
+        artists: Vec<Artist>,
+    }
+    #[derive(Deserialize)]
+    struct Artist {
+        name: String,
+    }
+
+    let duration = (fingerprint.duration.round() as u64).to_string();
+    let text = ureq::post("https://api.acoustid.org/v2/lookup")
+        .send_form([
+            ("client", key),
+            ("meta", "recordings"),
+            ("duration", duration.as_str()),
+            ("fingerprint", fingerprint.fingerprint.as_str()),
+        ])?
+        .body_mut()
+        .read_to_string()?;
+    let response: Response = serde_json::from_str(&text)?;
+    if response.status != "ok" {
+        let message = response.error.map(|e| e.message).unwrap_or_default();
+        return Err(anyhow!("AcoustID: {}", message));
+    }
+
+    Ok(response
+        .results
+        .into_iter()
+        .flat_map(|result| {
+            result.recordings.into_iter().map(move |recording| Match {
+                score: result.score,
+                recording_id: recording.id,
+                title: recording.title,
+                artists: recording.artists.into_iter().map(|a| a.name).collect(),
+            })
+        })
+        .collect())
+}
@@ -0,0 +1,159 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::covers;
+use crate::library;
+use crate::tags;
+
+const COVER_NAMES: &[&str] = &["cover", "folder", "front", "album"];
+const THUMBNAIL_SIZE: u32 = 32;
+
+// A small RGB copy of the cover, enough for a terminal preview.
+#[derive(Clone, Debug)]
+pub struct Thumbnail {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[u8; 3]>,
+}
+
+impl Thumbnail {
+    pub(crate) fn decode(bytes: &[u8]) -> Option<Thumbnail> {
+        let image = image::load_from_memory(bytes).ok()?;
+        let image = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).to_rgb8();
+        Some(Thumbnail {
+            width: image.width(),
+            height: image.height(),
+            pixels: image.pixels().map(|pixel| pixel.0).collect(),
+        })
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 3] {
+        self.pixels[(y * self.width + x) as usize]
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct FolderSummary {
+    pub tracks: usize,
+    pub size: u64,
+    pub duration: Duration,
+    pub formats: Vec<String>,
+    pub cover: Option<Thumbnail>,
+    pub modified: Option<SystemTime>,
+}
+
+impl FolderSummary {
+    pub fn read(dir: &Path) -> FolderSummary {
+        let files = library::audio_files(dir);
+        let mut formats = BTreeSet::new();
+        let mut duration = Duration::ZERO;
+        let mut size = 0;
+
+        for path in &files {
+            size += fs::metadata(path).map(|meta| meta.len()).unwrap_or(0);
+            if let Some(ext) = path.extension() {
+                formats.insert(ext.to_string_lossy().to_uppercase());
+            }
+            if let Some(track_duration) = tags::read(path).ok().and_then(|t| t.duration) {
+                duration += track_duration;
+            }
+        }
+
+        let cover = match cover_file(dir) {
+            Some(file) => covers::cached(&file, || fs::read(&file).ok()),
+            None => files
+                .iter()
+                .find_map(|path| covers::cached(path, || tags::read_cover(path))),
+        };
+
+        FolderSummary {
+            tracks: files.len(),
+            size,
+            duration,
+            formats: formats.into_iter().collect(),
+            cover,
+            modified: modified(dir),
+        }
+    }
+
+    // Adding or removing files touches the folder, so a summary read since
+    // the last change can be reused. Edits deeper in the tree are missed.
+    pub fn is_current(&self, dir: &Path) -> bool {
+        self.modified.is_some() && self.modified == modified(dir)
+    }
+}
+
+// A track's embedded art, or else the cover image in its folder.
+pub fn track_cover(path: &Path) -> Option<Thumbnail> {
+    covers::cached(path, || {
+        tags::read_cover(path).or_else(|| {
+            let file = cover_file(path.parent()?)?;
+            fs::read(file).ok()
+        })
+    })
+}
+
+fn modified(dir: &Path) -> Option<SystemTime> {
+    fs::metadata(dir).ok()?.modified().ok()
+}
+
+// "512 KB", "1.4 GB".
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if size < 10.0 && unit > 0 {
+        format!("{:.1} {}", size, UNITS[unit])
+    } else {
+        format!("{:.0} {}", size, UNITS[unit])
+    }
+}
+
+// Finds cover.jpg, folder.png and the like, ignoring case.
+fn cover_file(dir: &Path) -> Option<PathBuf> {
+    fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| {
+            let stem = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or_default();
+            let ext = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or_default();
+            COVER_NAMES.contains(&stem.to_lowercase().as_str())
+                && matches!(ext.to_lowercase().as_str(), "jpg" | "jpeg" | "png")
+        })
+}
+
+// Reads a folder summary on a worker thread, since it probes every file.
+pub struct PendingSummary {
+    pub dir: PathBuf,
+    rx: mpsc::Receiver<FolderSummary>,
+}
+
+impl PendingSummary {
+    pub fn spawn(dir: PathBuf) -> PendingSummary {
+        let (tx, rx) = mpsc::channel();
+        let thread_dir = dir.clone();
+        thread::spawn(move || {
+            let _ = tx.send(FolderSummary::read(&thread_dir));
+        });
+        PendingSummary { dir, rx }
+    }
+
+    pub fn poll(&self) -> Option<FolderSummary> {
+        self.rx.try_recv().ok()
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::library::Track;
+
+#[derive(Clone, Debug)]
+pub struct Group {
+    pub album: String,
+    pub artist: Option<String>,
+    pub year: Option<u32>,
+    pub duration: Duration,
+    pub start: usize,
+    pub len: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Row {
+    Header(usize),
+    Item(usize),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct GroupedList {
+    pub cursor: usize,
+    pub collapsed: HashSet<String>,
+}
+
+fn album_of(path: &Path, track: Option<&Track>) -> String {
+    track.and_then(|t| t.tags.album.clone()).unwrap_or_else(|| {
+        path.parent()
+            .and_then(|p| p.file_name())
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    })
+}
+
+// Groups consecutive items that share an album, so a queue that jumps
+// between albums gets one header per run rather than one per album.
+pub fn build<'a>(items: impl Iterator<Item = (&'a Path, Option<&'a Track>)>) -> Vec<Group> {
+    let mut groups: Vec<Group> = Vec::new();
+
+    for (i, (path, track)) in items.enumerate() {
+        let album = album_of(path, track);
+        let artist = track.and_then(|t| t.tags.display_album_artist());
+        let duration = track.and_then(|t| t.tags.duration).unwrap_or_default();
+        let year = track.and_then(|t| t.tags.year);
+
+        match groups.last_mut() {
+            Some(group) if group.album == album && same_artist(&group.artist, &artist) => {
+                group.len += 1;
+                group.duration += duration;
+                group.year = group.year.or(year);
+                group.artist = group.artist.take().or(artist);
+            }
+            _ => groups.push(Group {
+                album,
+                artist,
+                year,
+                duration,
+                start: i,
+                len: 1,
+            }),
+        }
+    }
+    groups
+}
+
+// Untagged files shouldn't split an otherwise consistent album.
+fn same_artist(a: &Option<String>, b: &Option<String>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+        _ => true,
+    }
+}
+
+pub fn rows(groups: &[Group], collapsed: &HashSet<String>) -> Vec<Row> {
+    let mut rows = Vec::new();
+    for (g, group) in groups.iter().enumerate() {
+        rows.push(Row::Header(g));
+        if !collapsed.contains(&group.album) {
+            rows.extend((group.start..group.start + group.len).map(Row::Item));
+        }
+    }
+    rows
+}
+
+impl GroupedList {
+    pub fn next(&mut self, row_count: usize) {
+        if row_count > 0 {
+            self.cursor = (self.cursor + 1) % row_count;
+        }
+    }
+
+    pub fn prev(&mut self, row_count: usize) {
+        if row_count > 0 {
+            self.cursor = self.cursor.checked_sub(1).unwrap_or(row_count - 1);
+        }
+    }
+
+    pub fn selected(&self, rows: &[Row]) -> Option<Row> {
+        rows.get(self.cursor).copied()
+    }
+
+    pub fn toggle(&mut self, group: &Group) {
+        if !self.collapsed.remove(&group.album) {
+            self.collapsed.insert(group.album.clone());
+        }
+    }
+
+    pub fn clamp(&mut self, row_count: usize) {
+        self.cursor = self.cursor.min(row_count.saturating_sub(1));
+    }
+}
@@ -0,0 +1,147 @@
+use anyhow::{Result, anyhow};
+use plist::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub struct ImportedPlaylist {
+    pub name: String,
+    pub entries: Vec<PathBuf>,
+}
+
+// Rewrites the start of a path, e.g. `/Users/me/Music=/home/me/music`
+// for a library exported on another machine.
+pub struct PrefixMap {
+    pub from: String,
+    pub to: String,
+}
+
+impl PrefixMap {
+    pub fn parse(spec: &str) -> Option<PrefixMap> {
+        let (from, to) = spec.split_once('=')?;
+        Some(PrefixMap {
+            from: from.to_string(),
+            to: to.to_string(),
+        })
+    }
+}
+
+pub fn import(path: &Path, maps: &[PrefixMap]) -> Result<Vec<ImportedPlaylist>> {
+    let root = Value::from_file(path)?;
+    let root = root
+        .as_dictionary()
+        .ok_or_else(|| anyhow!("not an iTunes library file"))?;
+
+    let tracks: HashMap<i64, PathBuf> = root
+        .get("Tracks")
+        .and_then(Value::as_dictionary)
+        .map(|tracks| {
+            tracks
+                .iter()
+                .filter_map(|(id, track)| {
+                    let location = track.as_dictionary()?.get("Location")?.as_string()?;
+                    Some((id.parse().ok()?, remap(&location_to_path(location), maps)))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let playlists = root
+        .get("Playlists")
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("no playlists found"))?;
+
+    Ok(playlists
+        .iter()
+        .filter_map(Value::as_dictionary)
+        .filter(|playlist| {
+            // Skip the whole-library "Master" list and Apple's built-in smart lists.
+            !playlist.contains_key("Master") && !playlist.contains_key("Distinguished Kind")
+        })
+        .filter_map(|playlist| {
+            let name = playlist.get("Name")?.as_string()?.to_string();
+            let entries = playlist
+                .get("Playlist Items")
+                .and_then(Value::as_array)
+                .map(|items| {
+                    items
+                        .iter()
+                        .filter_map(|item| {
+                            let id = item.as_dictionary()?.get("Track ID")?.as_signed_integer()?;
+                            tracks.get(&id).cloned()
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            Some(ImportedPlaylist { name, entries })
+        })
+        .collect())
+}
+
+fn location_to_path(location: &str) -> String {
+    let path = location
+        .strip_prefix("file://localhost")
+        .or_else(|| location.strip_prefix("file://"))
+        .unwrap_or(location);
+    percent_decode(path)
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        // Digits are read from the bytes, since a `%` can be followed by a
+        // multibyte character that a `str` slice would split.
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && let Some(high) = (bytes[i + 1] as char).to_digit(16)
+            && let Some(low) = (bytes[i + 2] as char).to_digit(16)
+        {
+            out.push((high * 16 + low) as u8);
+            i += 3;
+            continue;
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn remap(path: &str, maps: &[PrefixMap]) -> PathBuf {
+    for map in maps {
+        if let Some(rest) = path.strip_prefix(&map.from) {
+            return PathBuf::from(format!("{}{}", map.to, rest));
+        }
+    }
+    PathBuf::from(path)
+}
+
+pub fn file_name_for(name: &str) -> String {
+    let clean: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect();
+    format!("{}.m3u", clean.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_escapes_and_leaves_the_rest() {
+        assert_eq!(percent_decode("My%20Music/caf%C3%A9"), "My Music/café");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+    }
+
+    #[test]
+    fn keeps_a_percent_before_a_multibyte_character() {
+        assert_eq!(percent_decode("%é1"), "%é1");
+        assert_eq!(percent_decode("a%1é"), "a%1é");
+    }
+}
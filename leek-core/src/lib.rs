@@ -0,0 +1,33 @@
+pub mod arrivals;
+pub mod backend;
+pub mod charts;
+pub mod clock;
+#[cfg(feature = "coverart")]
+pub mod coverart;
+pub mod covers;
+pub mod db;
+pub mod decode;
+pub mod export;
+pub mod fingerprint;
+pub mod folder;
+pub mod groups;
+pub mod itunes;
+pub mod library;
+pub mod log;
+pub mod paths;
+pub mod player;
+pub mod playlist;
+pub mod query;
+pub mod remote;
+pub mod rename;
+pub mod sandbox;
+pub mod session;
+pub mod settings;
+#[cfg(feature = "sftp")]
+pub mod sftp;
+pub mod snapcast;
+pub mod surround;
+pub mod sync;
+pub mod tags;
+#[cfg(feature = "webdav")]
+pub mod webdav;
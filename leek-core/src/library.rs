@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::tags::{self, Tags};
+
+#[derive(Clone, Debug)]
+pub struct Track {
+    pub path: PathBuf,
+    pub mtime: u64,
+    // When a scan first found the file, in seconds since the epoch.
+    pub added_at: u64,
+    pub tags: Tags,
+}
+
+#[derive(Default)]
+pub struct Library {
+    pub root: PathBuf,
+    pub tracks: Vec<Track>,
+    index: HashMap<PathBuf, usize>,
+}
+
+impl Library {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_tracks(&mut self, tracks: Vec<Track>) {
+        self.index = tracks
+            .iter()
+            .enumerate()
+            .map(|(i, track)| (track.path.clone(), i))
+            .collect();
+        self.tracks = tracks;
+    }
+
+    pub fn find(&self, path: &Path) -> Option<&Track> {
+        self.index.get(path).map(|&i| &self.tracks[i])
+    }
+
+    // Orders files album by album, then by disc and track number, so
+    // "Disc 1"/"Disc 2" subfolders and DISCNUMBER tags play in sequence.
+    // Unindexed files are only opened for their tags with `probe`, which
+    // a sandboxed player leaves off; they go by folder and name instead.
+    pub fn sort_album_order(&self, paths: &mut [PathBuf], probe: bool) {
+        paths.sort_by_cached_key(|path| {
+            let tags = match self.find(path) {
+                Some(track) => track.tags.clone(),
+                None if probe => tags::read(path).unwrap_or_default(),
+                None => Tags::default(),
+            };
+            let folder_disc = path.parent().and_then(disc_folder_number);
+            let album_dir = match folder_disc {
+                Some(_) => path.parent().and_then(Path::parent),
+                None => path.parent(),
+            };
+            (
+                album_dir.map(Path::to_path_buf),
+                tags.disc_number.or(folder_disc).unwrap_or(0),
+                tags.track_number.unwrap_or(u32::MAX),
+                path.clone(),
+            )
+        });
+    }
+
+    // Tracks found since `since`, newest first. Tracks from the same scan
+    // stay in path order so albums hold together.
+    pub fn added_since(&self, since: u64) -> Vec<&Track> {
+        let mut tracks: Vec<&Track> = self
+            .tracks
+            .iter()
+            .filter(|track| track.added_at >= since)
+            .collect();
+        tracks.sort_by(|a, b| {
+            b.added_at
+                .cmp(&a.added_at)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        tracks
+    }
+}
+
+// Recognizes folder names like "Disc 2", "CD1" or "disk_3".
+fn disc_folder_number(dir: &Path) -> Option<u32> {
+    let name = dir.file_name()?.to_string_lossy().to_lowercase();
+    let rest = ["disc", "disk", "cd"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))?;
+    let digits = rest.trim_start_matches([' ', '-', '_', '.']);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+pub fn audio_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    walk(dir, &mut |path| {
+        files.push(path);
+        true
+    });
+    files
+}
+
+pub fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| matches!(ext.to_lowercase().as_str(), "mp3" | "wav" | "flac" | "ogg"))
+        .unwrap_or(false)
+}
+
+fn mtime(path: &Path) -> u64 {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub struct Scan {
+    rx: mpsc::Receiver<Track>,
+    found: Arc<AtomicUsize>,
+    reused: Arc<AtomicUsize>,
+    tracks: Vec<Track>,
+}
+
+impl Scan {
+    pub fn start(root: PathBuf, previous: &[Track]) -> Scan {
+        let workers = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(4);
+        Scan::with_workers(root, previous, workers)
+    }
+
+    // Tags are read on `workers` threads; `start` uses one per core.
+    pub fn with_workers(root: PathBuf, previous: &[Track], workers: usize) -> Scan {
+        let previous: Arc<HashMap<PathBuf, Track>> = Arc::new(
+            previous
+                .iter()
+                .map(|track| (track.path.clone(), track.clone()))
+                .collect(),
+        );
+        let (path_tx, path_rx) = mpsc::channel::<PathBuf>();
+        let (track_tx, track_rx) = mpsc::channel();
+        let found = Arc::new(AtomicUsize::new(0));
+        let reused = Arc::new(AtomicUsize::new(0));
+        let scanned_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let walker_found = Arc::clone(&found);
+        thread::spawn(move || {
+            walk(&root, &mut |path| {
+                walker_found.fetch_add(1, Ordering::Relaxed);
+                path_tx.send(path).is_ok()
+            });
+        });
+
+        let path_rx = Arc::new(Mutex::new(path_rx));
+        for _ in 0..workers.max(1) {
+            let path_rx = Arc::clone(&path_rx);
+            let track_tx = track_tx.clone();
+            let previous = Arc::clone(&previous);
+            let reused = Arc::clone(&reused);
+            thread::spawn(move || {
+                loop {
+                    let next = path_rx.lock().ok().and_then(|rx| rx.recv().ok());
+                    let Some(path) = next else {
+                        return;
+                    };
+
+                    let mtime = mtime(&path);
+                    let known = previous.get(&path);
+                    let track = match known {
+                        Some(known) if known.mtime == mtime => {
+                            reused.fetch_add(1, Ordering::Relaxed);
+                            known.clone()
+                        }
+                        _ => {
+                            let tags = tags::read(&path).unwrap_or_default();
+                            let added_at = known.map_or(scanned_at, |k| k.added_at);
+                            Track {
+                                path,
+                                mtime,
+                                added_at,
+                                tags,
+                            }
+                        }
+                    };
+
+                    if track_tx.send(track).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+
+        Scan {
+            rx: track_rx,
+            found,
+            reused,
+            tracks: Vec::new(),
+        }
+    }
+
+    // Drains finished tracks and returns true once every worker has exited.
+    pub fn poll(&mut self) -> bool {
+        loop {
+            match self.rx.try_recv() {
+                Ok(track) => self.tracks.push(track),
+                Err(mpsc::TryRecvError::Empty) => return false,
+                Err(mpsc::TryRecvError::Disconnected) => return true,
+            }
+        }
+    }
+
+    pub fn progress(&self) -> (usize, usize) {
+        (self.tracks.len(), self.found.load(Ordering::Relaxed))
+    }
+
+    pub fn reused(&self) -> usize {
+        self.reused.load(Ordering::Relaxed)
+    }
+
+    pub fn finish(mut self) -> Vec<Track> {
+        self.tracks.sort_by(|a, b| a.path.cmp(&b.path));
+        self.tracks
+    }
+}
+
+fn walk(dir: &Path, on_file: &mut dyn FnMut(PathBuf) -> bool) -> bool {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return true;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        if is_dir {
+            if !walk(&path, on_file) {
+                return false;
+            }
+        } else if is_audio_file(&path) && !on_file(path) {
+            return false;
+        }
+    }
+    true
+}
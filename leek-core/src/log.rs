@@ -0,0 +1,25 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::paths;
+
+fn log_path() -> Option<PathBuf> {
+    Some(paths::state_dir()?.join("leek.log"))
+}
+
+pub fn warn(message: &str) {
+    let Some(path) = log_path() else {
+        return;
+    };
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+        let _ = writeln!(file, "[{}] WARN {}", timestamp, message);
+    }
+}
@@ -0,0 +1,43 @@
+use directories::ProjectDirs;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Each directory can be overridden with an environment variable, otherwise
+// it follows the platform convention (XDG base directories on Linux).
+fn resolve(var: &str, default: impl FnOnce(&ProjectDirs) -> &Path) -> Option<PathBuf> {
+    let dir = match env::var_os(var).filter(|value| !value.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => default(&ProjectDirs::from("", "", "leek")?).to_path_buf(),
+    };
+    fs::create_dir_all(&dir).ok()?;
+    Some(dir)
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    resolve("LEEK_CONFIG_DIR", ProjectDirs::config_dir)
+}
+
+pub fn data_dir() -> Option<PathBuf> {
+    resolve("LEEK_DATA_DIR", ProjectDirs::data_local_dir)
+}
+
+pub fn state_dir() -> Option<PathBuf> {
+    resolve("LEEK_STATE_DIR", |dirs| {
+        dirs.state_dir().unwrap_or_else(|| dirs.data_local_dir())
+    })
+}
+
+pub fn cache_dir() -> Option<PathBuf> {
+    resolve("LEEK_CACHE_DIR", ProjectDirs::cache_dir)
+}
+
+pub fn playlists_dir() -> Option<PathBuf> {
+    let dir = data_dir()?.join("playlists");
+    fs::create_dir_all(&dir).ok()?;
+    Some(dir)
+}
+
+pub fn config_file() -> Option<PathBuf> {
+    Some(config_dir()?.join("config.toml"))
+}
@@ -0,0 +1,740 @@
+use anyhow::{Result, anyhow};
+use rodio::Source;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::backend::{AudioBackend, RodioBackend};
+use crate::decode::{self, BoxedSource, Preload};
+use crate::log;
+use crate::remote;
+use crate::sandbox;
+use crate::settings::FolderSettings;
+use crate::surround;
+use crate::tags::{self, LoopPoints, Tags};
+
+const PRELOAD_AHEAD: Duration = Duration::from_secs(5);
+// The volume slider spans this many decibels, from just above silence at 1%
+// to unity at 100%.
+const VOLUME_RANGE_DB: f32 = 60.0;
+// How fast ducking fades.
+const DUCK_DB_PER_SECOND: f32 = 20.0;
+// The most a track's own offset can raise or lower it.
+pub const TRACK_GAIN_DB: f32 = 12.0;
+
+// What happened when the player tried to start a queue entry. Unplayable
+// files are skipped, so one call can report several of them, each with the
+// reason it wouldn't open, as can a track whose sandboxed decoder crashed
+// partway through. `finished` is the track that played to its end, when
+// that is why the player moved on.
+// `lost` is the track whose file went away mid-play, e.g. with a dropped
+// network share; the player stops there instead of skipping the queue.
+#[derive(Debug, Default)]
+pub struct Playback {
+    pub started: Option<PathBuf>,
+    pub skipped: Vec<(PathBuf, String)>,
+    pub finished: Option<PathBuf>,
+    pub lost: Option<PathBuf>,
+}
+
+pub struct Player {
+    pub queue: Vec<PathBuf>,
+    pub queue_index: usize,
+    pub volume: u8,
+    // Above 100 the signal is amplified, for quiet recordings.
+    pub max_volume: u8,
+    // Decibels taken off the volume while something talks over the music.
+    ducking: f32,
+    duck_target: f32,
+    // A per-track offset in decibels, for the odd quiet or loud recording.
+    track_gain: f32,
+    fade_rate: f32,
+    // Silence between one track running out and the next starting.
+    pub gap: Duration,
+    gap_remaining: Duration,
+    advancing: bool,
+    // Overlap between tracks from different albums, or from any two tracks
+    // with `crossfade_albums`, so live and gapless albums stay seamless.
+    pub crossfade: Duration,
+    pub crossfade_albums: bool,
+    crossfading: bool,
+    // Whether the coming transition has been looked at yet this track.
+    crossfade_checked: bool,
+    pub is_playing: bool,
+
+    pub elapsed: Duration,
+    pub duration: Option<Duration>,
+    pub loop_points: Option<LoopPoints>,
+    // The playing track's tags, as read when it was opened.
+    pub tags: Tags,
+    pub settings: FolderSettings,
+    // A program to decode in, as `<program> decode-child <file>`, so a
+    // crashing decoder can't take the player with it.
+    pub sandbox: Option<PathBuf>,
+    // Surround files are virtualised for headphones rather than cut down
+    // to their front pair.
+    pub headphones: bool,
+
+    preload: Option<Preload>,
+    backend: Box<dyn AudioBackend>,
+}
+
+impl Player {
+    pub fn new() -> Result<Self> {
+        Ok(Self::with_backend(Box::new(RodioBackend::new()?)))
+    }
+
+    pub fn with_backend(backend: Box<dyn AudioBackend>) -> Self {
+        let mut player = Self {
+            queue: Vec::new(),
+            queue_index: 0,
+            volume: 50,
+            max_volume: 100,
+            ducking: 0.0,
+            track_gain: 0.0,
+            duck_target: 0.0,
+            fade_rate: DUCK_DB_PER_SECOND,
+            gap: Duration::ZERO,
+            gap_remaining: Duration::ZERO,
+            advancing: false,
+            crossfade: Duration::ZERO,
+            crossfade_albums: false,
+            crossfading: false,
+            crossfade_checked: false,
+            is_playing: false,
+            elapsed: Duration::ZERO,
+            duration: None,
+            loop_points: None,
+            tags: Tags::default(),
+            settings: FolderSettings::default(),
+            sandbox: None,
+            headphones: false,
+            preload: None,
+            backend,
+        };
+        player.set_volume(player.volume);
+        player
+    }
+
+    pub fn current(&self) -> Option<&Path> {
+        self.queue.get(self.queue_index).map(PathBuf::as_path)
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.backend.is_empty()
+    }
+
+    pub fn set_queue(&mut self, queue: Vec<PathBuf>, index: usize) -> Playback {
+        self.queue = queue;
+        self.queue_index = index;
+        self.play_current()
+    }
+
+    // Appends to the queue, starting playback if the queue was empty.
+    pub fn enqueue(&mut self, paths: Vec<PathBuf>) -> Playback {
+        let was_empty = self.queue.is_empty();
+        self.queue.extend(paths);
+        if was_empty {
+            self.queue_index = 0;
+            return self.play_current();
+        }
+        Playback::default()
+    }
+
+    // Slots a track in after the current one and skips to it, keeping the
+    // rest of the queue.
+    pub fn play_now(&mut self, path: PathBuf) -> Playback {
+        let index = if self.queue.is_empty() {
+            0
+        } else {
+            self.queue_index + 1
+        };
+        self.queue.insert(index, path);
+        self.play_index(index)
+    }
+
+    pub fn clear(&mut self) {
+        self.backend.stop();
+        self.queue.clear();
+        self.queue_index = 0;
+        self.is_playing = false;
+        self.elapsed = Duration::ZERO;
+        self.duration = None;
+        self.loop_points = None;
+        self.tags = Tags::default();
+        self.preload = None;
+    }
+
+    // Drops everything but the current track, which keeps playing.
+    pub fn crop(&mut self) {
+        let Some(current) = self.queue.get(self.queue_index).cloned() else {
+            return;
+        };
+        self.queue = vec![current];
+        self.queue_index = 0;
+        self.preload = None;
+    }
+
+    // Puts back a queue saved before `clear` or `crop`, without touching
+    // what is playing.
+    pub fn restore_queue(&mut self, queue: Vec<PathBuf>, index: usize) {
+        self.queue = queue;
+        self.queue_index = index.min(self.queue.len().saturating_sub(1));
+        self.preload = None;
+    }
+
+    // Takes the same tracks in a new order. The playing track keeps playing
+    // wherever it ended up.
+    pub fn reorder(&mut self, queue: Vec<PathBuf>) {
+        let current = self.current().map(Path::to_path_buf);
+        self.queue = queue;
+        self.queue_index = current
+            .and_then(|current| self.queue.iter().position(|path| *path == current))
+            .unwrap_or(0);
+        self.preload = None;
+    }
+
+    pub fn play_index(&mut self, index: usize) -> Playback {
+        self.queue_index = index;
+        self.play_current()
+    }
+
+    pub fn play_current(&mut self) -> Playback {
+        let mut playback = Playback::default();
+
+        while playback.skipped.len() < self.queue.len() {
+            let Some(path) = self.queue.get(self.queue_index).cloned() else {
+                break;
+            };
+
+            match self.start(&path) {
+                Ok(()) => {
+                    playback.started = Some(path);
+                    break;
+                }
+                Err(e) => {
+                    log::warn(&format!("Skipping {}: {}", path.display(), e));
+                    playback.skipped.push((path, e.to_string()));
+                    self.queue_index = (self.queue_index + 1) % self.queue.len();
+                }
+            }
+        }
+        playback
+    }
+
+    fn start(&mut self, path: &Path) -> Result<()> {
+        let crossfading = std::mem::take(&mut self.crossfading);
+        if !crossfading {
+            self.backend.stop();
+        }
+        self.is_playing = false;
+        self.crossfade_checked = false;
+        self.duration = None;
+        self.loop_points = None;
+        self.tags = Tags::default();
+        self.elapsed = Duration::ZERO;
+        self.gap_remaining = Duration::ZERO;
+        // Only a track running out earns the gap, not skipping ahead.
+        let gap = if std::mem::take(&mut self.advancing) {
+            self.gap
+        } else {
+            Duration::ZERO
+        };
+
+        self.settings = FolderSettings::for_track(path);
+        let opened = match self.preload.take() {
+            Some(preload) if preload.path == path => preload.wait()?,
+            _ => decode::open_with(path, self.sandbox.as_deref())?,
+        };
+
+        let source = opened.source;
+        self.duration = source.total_duration();
+        self.loop_points = opened.loop_points;
+        self.tags = opened.tags;
+        let skip = self.settings.skip_intro();
+        let source = if skip.is_zero() {
+            source
+        } else {
+            self.elapsed = skip;
+            Box::new(Source::skip_duration(source, skip))
+        };
+        let source = self.with_speed(self.for_output(source));
+        if crossfading {
+            self.backend.crossfade(source, self.crossfade);
+        } else if gap.is_zero() {
+            self.backend.play(source);
+        } else {
+            self.backend.play(Box::new(Source::delay(source, gap)));
+            self.gap_remaining = gap;
+        }
+        self.is_playing = true;
+        Ok(())
+    }
+
+    fn for_output(&self, source: BoxedSource) -> BoxedSource {
+        if self.headphones {
+            surround::headphones(source)
+        } else {
+            source
+        }
+    }
+
+    fn with_speed(&self, source: BoxedSource) -> BoxedSource {
+        if self.settings.speed == 1.0 {
+            return source;
+        }
+        Box::new(Source::speed(source, self.settings.speed))
+    }
+
+    // rodio 0.17 sinks can't seek, so seeking reopens the file and skips
+    // ahead to the requested position.
+    pub fn seek_to(&mut self, position: Duration) -> Result<()> {
+        let path = self
+            .current()
+            .ok_or_else(|| anyhow!("nothing playing"))?
+            .to_path_buf();
+        let position = match self.duration {
+            Some(duration) => position.min(duration),
+            None => position,
+        };
+
+        let source = decode::open_with(&path, self.sandbox.as_deref())?.source;
+
+        self.preload = None;
+        self.gap_remaining = Duration::ZERO;
+        let skipped = Box::new(Source::skip_duration(source, position));
+        self.backend.play(self.with_speed(self.for_output(skipped)));
+        if !self.is_playing {
+            self.backend.pause();
+        }
+        self.elapsed = position;
+        Ok(())
+    }
+
+    // Picks a lost track up again where it stopped.
+    pub fn reconnect(&mut self) -> Result<()> {
+        self.is_playing = true;
+        let result = self.seek_to(self.elapsed);
+        if result.is_err() {
+            self.is_playing = false;
+        }
+        result
+    }
+
+    pub fn toggle_play(&mut self) -> Playback {
+        if self.backend.is_empty() && !self.queue.is_empty() {
+            return self.play_current();
+        }
+        if self.backend.is_paused() {
+            self.backend.resume();
+            self.is_playing = true;
+        } else {
+            self.backend.pause();
+            self.is_playing = false;
+        }
+        Playback::default()
+    }
+
+    pub fn next_track(&mut self) -> Playback {
+        if self.queue.is_empty() {
+            return Playback::default();
+        }
+        self.queue_index = (self.queue_index + 1) % self.queue.len();
+        self.play_current()
+    }
+
+    pub fn prev_track(&mut self) -> Playback {
+        if self.queue.is_empty() {
+            return Playback::default();
+        }
+        self.queue_index = if self.queue_index > 0 {
+            self.queue_index - 1
+        } else {
+            self.queue.len() - 1
+        };
+        self.play_current()
+    }
+
+    pub fn set_volume(&mut self, volume: u8) {
+        self.volume = volume.min(self.max_volume);
+        let offset = 10f32.powf((self.track_gain - self.ducking) / 20.0);
+        self.backend.set_volume(gain(self.volume) * offset);
+    }
+
+    pub fn track_gain(&self) -> f32 {
+        self.track_gain
+    }
+
+    // Clamped to ±TRACK_GAIN_DB, and heard straight away.
+    pub fn set_track_gain(&mut self, gain_db: f32) {
+        self.track_gain = gain_db.clamp(-TRACK_GAIN_DB, TRACK_GAIN_DB);
+        self.set_volume(self.volume);
+    }
+
+    // Fades down by `db` over the next few ticks, or back up with 0.
+    pub fn duck(&mut self, db: f32) {
+        self.duck_target = db.max(0.0);
+        self.fade_rate = DUCK_DB_PER_SECOND;
+    }
+
+    // Starts from silence and rises to the set volume over `duration`.
+    pub fn fade_in(&mut self, duration: Duration) {
+        self.ducking = VOLUME_RANGE_DB;
+        self.duck_target = 0.0;
+        self.fade_rate = VOLUME_RANGE_DB / duration.as_secs_f32().max(1.0);
+        self.set_volume(self.volume);
+    }
+
+    fn fade_duck(&mut self, delta: Duration) {
+        if self.ducking == self.duck_target {
+            return;
+        }
+        let step = self.fade_rate * delta.as_secs_f32();
+        self.ducking = if self.ducking < self.duck_target {
+            (self.ducking + step).min(self.duck_target)
+        } else {
+            (self.ducking - step).max(self.duck_target)
+        };
+        self.set_volume(self.volume);
+    }
+
+    pub fn set_max_volume(&mut self, max_volume: u8) {
+        self.max_volume = max_volume.max(100);
+        self.set_volume(self.volume);
+    }
+
+    // Advances the position clock and moves on when the track runs out.
+    pub fn tick(&mut self, delta: Duration) -> Playback {
+        self.fade_duck(delta);
+        if !self.is_playing {
+            return Playback::default();
+        }
+        // The decoder is waiting on the network, so the audio is too.
+        if self.buffering().is_some_and(|progress| progress.stalled) {
+            return Playback::default();
+        }
+        if !self.gap_remaining.is_zero() {
+            self.gap_remaining = self.gap_remaining.saturating_sub(delta);
+            return Playback::default();
+        }
+
+        self.elapsed += delta.mul_f32(self.settings.speed);
+        if let Some(points) = self.loop_points
+            && self.elapsed >= points.end_time()
+        {
+            self.elapsed -= points.end_time() - points.start_time();
+        }
+
+        if self.crossfade_due() {
+            let finished = self.current().map(Path::to_path_buf);
+            self.crossfading = true;
+            let mut playback = self.next_track();
+            self.crossfading = false;
+            playback.finished = finished;
+            return playback;
+        }
+
+        if self.backend.is_empty() && !self.queue.is_empty() && self.duration.is_some() {
+            if let Some(path) = self.current().map(Path::to_path_buf)
+                && !remote::is_remote(&path)
+                && !path.exists()
+            {
+                self.is_playing = false;
+                return Playback {
+                    lost: Some(path),
+                    ..Playback::default()
+                };
+            }
+            let current = self.current().map(Path::to_path_buf);
+            let crash = current.as_deref().and_then(sandbox::take_crash);
+            self.advancing = true;
+            let mut playback = self.next_track();
+            self.advancing = false;
+            match (current, crash) {
+                (Some(path), Some(error)) => playback.skipped.insert(0, (path, error)),
+                (current, _) => playback.finished = current,
+            }
+            return playback;
+        }
+        self.preload_next();
+        Playback::default()
+    }
+
+    // True once the track is within a crossfade of its end and the next one
+    // should come in over it. Tags are only read once per track.
+    fn crossfade_due(&mut self) -> bool {
+        let Some(duration) = self.duration else {
+            return false;
+        };
+        if self.crossfade.is_zero()
+            || self.crossfade_checked
+            || self.queue.len() < 2
+            || self.loop_points.is_some()
+        {
+            return false;
+        }
+        let remaining = duration
+            .saturating_sub(self.elapsed)
+            .div_f32(self.settings.speed);
+        if remaining > self.crossfade {
+            return false;
+        }
+        self.crossfade_checked = true;
+
+        let next = self.queue[(self.queue_index + 1) % self.queue.len()].clone();
+        self.crossfade_albums || !self.same_album(&next)
+    }
+
+    // Whether `next` is from the playing track's album by the same album
+    // artist, as far as local tags tell. Remote tracks can't be checked
+    // cheaply, so they never match. A sandboxed player only has the tags
+    // its decoder sent, so until the preload has them it goes by folder.
+    fn same_album(&mut self, next: &Path) -> bool {
+        let Some(current) = self.current().map(Path::to_path_buf) else {
+            return false;
+        };
+        if remote::is_remote(&current) || remote::is_remote(next) {
+            return false;
+        }
+        let next_tags = match &mut self.preload {
+            Some(preload) if preload.path == next => preload.tags().cloned(),
+            _ if self.sandbox.is_none() => tags::read(next).ok(),
+            _ => None,
+        };
+        let Some(next_tags) = next_tags else {
+            return current.parent() == next.parent();
+        };
+        let album = |tags: &Tags| Some((tags.album.clone()?, tags.display_album_artist()));
+        match (album(&self.tags), album(&next_tags)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    // The download of a remote track that has not fully arrived yet.
+    pub fn buffering(&self) -> Option<remote::Progress> {
+        let path = self.current().filter(|path| remote::is_remote(path))?;
+        remote::progress(path).filter(|progress| !progress.done)
+    }
+
+    fn preload_next(&mut self) {
+        let Some(duration) = self.duration else {
+            return;
+        };
+        if self.preload.is_some() || self.queue.is_empty() || !self.settings.gapless {
+            return;
+        }
+        if duration.saturating_sub(self.elapsed) > PRELOAD_AHEAD + self.crossfade {
+            return;
+        }
+
+        let next_index = (self.queue_index + 1) % self.queue.len();
+        self.preload = Some(Preload::spawn(
+            self.queue[next_index].clone(),
+            self.sandbox.clone(),
+        ));
+    }
+
+    // Drops a run of entries. If the playing track goes with them, whatever
+    // takes its place starts instead.
+    pub fn remove_range(&mut self, range: RangeInclusive<usize>) -> Playback {
+        let (start, end) = (*range.start(), *range.end());
+        if start > end || end >= self.queue.len() {
+            return Playback::default();
+        }
+        let removed_current = range.contains(&self.queue_index);
+        self.queue.drain(range);
+        self.preload = None;
+        if self.queue.is_empty() {
+            self.clear();
+            return Playback::default();
+        }
+
+        if removed_current {
+            self.queue_index = start % self.queue.len();
+            if self.is_playing {
+                return self.play_current();
+            }
+            self.backend.stop();
+            self.elapsed = Duration::ZERO;
+            self.duration = None;
+            self.loop_points = None;
+            self.tags = Tags::default();
+        } else if self.queue_index > end {
+            self.queue_index -= end - start + 1;
+        }
+        Playback::default()
+    }
+
+    // Shifts a run of entries one place up or down, as far as the ends.
+    pub fn move_range(&mut self, range: RangeInclusive<usize>, down: bool) -> bool {
+        let (start, end) = (*range.start(), *range.end());
+        if start > end || end >= self.queue.len() {
+            return false;
+        }
+        if down && end + 1 < self.queue.len() {
+            self.move_entry(end + 1, start);
+        } else if !down && start > 0 {
+            self.move_entry(start - 1, end);
+        } else {
+            return false;
+        }
+        self.preload = None;
+        true
+    }
+
+    pub fn move_entry(&mut self, from: usize, to: usize) {
+        if from >= self.queue.len() || to >= self.queue.len() || from == to {
+            return;
+        }
+
+        let entry = self.queue.remove(from);
+        self.queue.insert(to, entry);
+
+        self.queue_index = if self.queue_index == from {
+            to
+        } else if from < self.queue_index && to >= self.queue_index {
+            self.queue_index - 1
+        } else if from > self.queue_index && to <= self.queue_index {
+            self.queue_index + 1
+        } else {
+            self.queue_index
+        };
+    }
+}
+
+// Loudness is heard on a log scale, so each step of the slider is the same
+// number of decibels. Boost past 100% stays linear, where a few percent more
+// is already plenty.
+fn gain(volume: u8) -> f32 {
+    match volume {
+        0 => 0.0,
+        1..=100 => 10f32.powf((volume as f32 / 100.0 - 1.0) * VOLUME_RANGE_DB / 20.0),
+        _ => volume as f32 / 100.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MockBackend;
+    use std::fs;
+    use tempfile::TempDir;
+
+    // A second of silence under each name, in a folder that goes away with
+    // the returned guard.
+    fn tracks(names: &[&str]) -> (TempDir, Vec<PathBuf>) {
+        const RATE: u32 = 8000;
+        let dir = tempfile::tempdir().unwrap();
+
+        let data = RATE * 2;
+        let mut wav = Vec::new();
+        wav.extend(b"RIFF");
+        wav.extend((36 + data).to_le_bytes());
+        wav.extend(b"WAVEfmt ");
+        wav.extend(16u32.to_le_bytes());
+        wav.extend(1u16.to_le_bytes());
+        wav.extend(1u16.to_le_bytes());
+        wav.extend(RATE.to_le_bytes());
+        wav.extend((RATE * 2).to_le_bytes());
+        wav.extend(2u16.to_le_bytes());
+        wav.extend(16u16.to_le_bytes());
+        wav.extend(b"data");
+        wav.extend(data.to_le_bytes());
+        wav.resize(wav.len() + data as usize, 0);
+
+        let paths = names
+            .iter()
+            .map(|name| {
+                let path = dir.path().join(name);
+                fs::write(&path, &wav).unwrap();
+                path
+            })
+            .collect();
+        (dir, paths)
+    }
+
+    fn player() -> (Player, MockBackend) {
+        let backend = MockBackend::new();
+        (Player::with_backend(Box::new(backend.clone())), backend)
+    }
+
+    #[test]
+    fn enqueue_starts_an_empty_queue_only() {
+        let (_dir, paths) = tracks(&["a.wav", "b.wav", "c.wav"]);
+        let (mut player, backend) = player();
+
+        let playback = player.enqueue(paths[..1].to_vec());
+        assert_eq!(playback.started.as_ref(), Some(&paths[0]));
+        assert_eq!(backend.state().plays, 1);
+
+        let playback = player.enqueue(paths[1..].to_vec());
+        assert!(playback.started.is_none());
+        assert_eq!(player.queue, paths);
+        assert_eq!(backend.state().plays, 1);
+    }
+
+    #[test]
+    fn next_skips_unplayable_entries_and_wraps() {
+        let (_dir, paths) = tracks(&["a.wav", "b.wav"]);
+        let missing = paths[0].with_file_name("missing.wav");
+        let (mut player, _) = player();
+        player.set_queue(vec![paths[0].clone(), missing.clone(), paths[1].clone()], 0);
+
+        let playback = player.next_track();
+        assert_eq!(playback.skipped.len(), 1);
+        assert_eq!(playback.skipped[0].0, missing);
+        assert_eq!(playback.started.as_ref(), Some(&paths[1]));
+        assert_eq!(player.queue_index, 2);
+
+        player.next_track();
+        assert_eq!(player.queue_index, 0);
+    }
+
+    #[test]
+    fn running_out_moves_on() {
+        let (_dir, paths) = tracks(&["a.wav", "b.wav"]);
+        let (mut player, backend) = player();
+        player.set_queue(paths.clone(), 0);
+
+        backend.finish();
+        let playback = player.tick(Duration::from_millis(100));
+        assert_eq!(playback.finished.as_ref(), Some(&paths[0]));
+        assert_eq!(playback.started.as_ref(), Some(&paths[1]));
+    }
+
+    #[test]
+    fn move_entry_keeps_the_playing_track() {
+        let (_dir, paths) = tracks(&["a.wav", "b.wav", "c.wav", "d.wav"]);
+        let (mut player, _) = player();
+        player.set_queue(paths.clone(), 1);
+
+        player.move_entry(1, 3);
+        assert_eq!(player.queue_index, 3);
+        player.move_entry(0, 3);
+        assert_eq!(player.queue_index, 2);
+        player.move_entry(3, 0);
+        assert_eq!(player.queue_index, 3);
+        assert_eq!(player.current(), Some(paths[1].as_path()));
+
+        player.move_entry(0, 9);
+        assert_eq!(player.queue_index, 3);
+    }
+
+    #[test]
+    fn seek_clamps_and_stays_paused() {
+        let (_dir, paths) = tracks(&["a.wav"]);
+        let (mut player, backend) = player();
+        player.set_queue(paths, 0);
+        assert_eq!(player.duration, Some(Duration::from_secs(1)));
+
+        player.seek_to(Duration::from_millis(400)).unwrap();
+        assert_eq!(player.elapsed, Duration::from_millis(400));
+        assert_eq!(backend.state().plays, 2);
+
+        player.toggle_play();
+        player.seek_to(Duration::from_secs(5)).unwrap();
+        assert_eq!(player.elapsed, Duration::from_secs(1));
+        assert!(backend.state().paused);
+    }
+}
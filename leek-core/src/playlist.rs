@@ -0,0 +1,281 @@
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub fn is_playlist_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| matches!(ext.to_lowercase().as_str(), "m3u" | "m3u8"))
+        .unwrap_or(false)
+}
+
+// A track in a playlist file, with the lines just before it there
+// (#EXTINF and comments), so rewriting the file keeps them with it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub extras: Vec<String>,
+}
+
+impl From<PathBuf> for Entry {
+    fn from(path: PathBuf) -> Entry {
+        Entry {
+            path,
+            extras: Vec::new(),
+        }
+    }
+}
+
+// A playlist file as written, down to the lines after its last track.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Playlist {
+    pub entries: Vec<Entry>,
+    pub trailing: Vec<String>,
+}
+
+// URLs are kept as they are; anything else is relative to the playlist.
+fn resolve(base: &Path, line: &str) -> PathBuf {
+    if line.contains("://") {
+        PathBuf::from(line)
+    } else {
+        base.join(line)
+    }
+}
+
+pub fn read(path: &Path) -> Result<Playlist> {
+    let base = path.parent().unwrap_or(Path::new("."));
+    let contents = fs::read_to_string(path)?;
+
+    let mut playlist = Playlist::default();
+    let mut extras = Vec::new();
+    for (i, line) in contents.lines().map(str::trim).enumerate() {
+        if line.is_empty() || (i == 0 && line == "#EXTM3U") {
+            continue;
+        }
+        if line.starts_with('#') {
+            extras.push(line.to_string());
+            continue;
+        }
+        playlist.entries.push(Entry {
+            path: resolve(base, line),
+            extras: std::mem::take(&mut extras),
+        });
+    }
+    playlist.trailing = extras;
+    Ok(playlist)
+}
+
+pub fn write(path: &Path, playlist: &Playlist) -> Result<()> {
+    let base = path.parent().unwrap_or(Path::new("."));
+    let mut contents = String::from("#EXTM3U\n");
+
+    for entry in &playlist.entries {
+        for extra in &entry.extras {
+            contents.push_str(extra);
+            contents.push('\n');
+        }
+        let relative = entry.path.strip_prefix(base).unwrap_or(&entry.path);
+        contents.push_str(&relative.to_string_lossy());
+        contents.push('\n');
+    }
+    for extra in &playlist.trailing {
+        contents.push_str(extra);
+        contents.push('\n');
+    }
+
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+pub fn load(path: &Path) -> Result<Vec<PathBuf>> {
+    Ok(read(path)?
+        .entries
+        .into_iter()
+        .map(|entry| entry.path)
+        .collect())
+}
+
+pub fn save(path: &Path, entries: &[PathBuf]) -> Result<()> {
+    let playlist = Playlist {
+        entries: entries.iter().cloned().map(Entry::from).collect(),
+        trailing: Vec::new(),
+    };
+    write(path, &playlist)
+}
+
+// Adds one entry to the end of a playlist, creating the file if needed.
+// Returns false when the entry was already there.
+pub fn append(path: &Path, entry: &Path) -> Result<bool> {
+    let mut playlist = if path.exists() {
+        read(path)?
+    } else {
+        Playlist::default()
+    };
+    if playlist
+        .entries
+        .iter()
+        .any(|existing| existing.path == entry)
+    {
+        return Ok(false);
+    }
+    playlist.entries.push(Entry::from(entry.to_path_buf()));
+    write(path, &playlist)?;
+    Ok(true)
+}
+
+pub struct PlaylistEditor {
+    pub path: PathBuf,
+    pub entries: Vec<Entry>,
+    pub index: usize,
+    pub dirty: bool,
+    trailing: Vec<String>,
+}
+
+impl PlaylistEditor {
+    pub fn open(path: &Path) -> Result<Self> {
+        let playlist = read(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries: playlist.entries,
+            index: 0,
+            dirty: false,
+            trailing: playlist.trailing,
+        })
+    }
+
+    pub fn save(&mut self) -> Result<()> {
+        let playlist = Playlist {
+            entries: self.entries.clone(),
+            trailing: self.trailing.clone(),
+        };
+        write(&self.path, &playlist)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn next(&mut self) {
+        if !self.entries.is_empty() {
+            self.index = (self.index + 1) % self.entries.len();
+        }
+    }
+
+    pub fn prev(&mut self) {
+        if !self.entries.is_empty() {
+            self.index = self.index.checked_sub(1).unwrap_or(self.entries.len() - 1);
+        }
+    }
+
+    pub fn move_up(&mut self) {
+        if self.index > 0 && self.index < self.entries.len() {
+            self.entries.swap(self.index, self.index - 1);
+            self.index -= 1;
+            self.dirty = true;
+        }
+    }
+
+    pub fn move_down(&mut self) {
+        if self.index + 1 < self.entries.len() {
+            self.entries.swap(self.index, self.index + 1);
+            self.index += 1;
+            self.dirty = true;
+        }
+    }
+
+    pub fn remove(&mut self) {
+        if self.index < self.entries.len() {
+            self.entries.remove(self.index);
+            self.index = self.index.min(self.entries.len().saturating_sub(1));
+            self.dirty = true;
+        }
+    }
+
+    pub fn insert(&mut self, paths: Vec<PathBuf>) {
+        let at = if self.entries.is_empty() {
+            0
+        } else {
+            self.index + 1
+        };
+        let count = paths.len();
+        self.entries
+            .splice(at..at, paths.into_iter().map(Entry::from));
+        if count > 0 {
+            self.index = at + count - 1;
+            self.dirty = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXTENDED: &str = "#EXTM3U
+#PLAYLIST:Evening
+#EXTINF:545,Miles Davis - So What
+Kind of Blue/01 So What.flac
+# a note about the next one
+#EXTINF:-1,Radio
+https://example.com/stream.mp3
+sftp://nas/music/Blue in Green.flac
+# the end
+";
+
+    #[test]
+    fn extended_playlists_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("evening.m3u8");
+        fs::write(&path, EXTENDED).unwrap();
+
+        let playlist = read(&path).unwrap();
+        assert_eq!(
+            playlist.entries[0],
+            Entry {
+                path: dir.path().join("Kind of Blue/01 So What.flac"),
+                extras: vec![
+                    "#PLAYLIST:Evening".into(),
+                    "#EXTINF:545,Miles Davis - So What".into(),
+                ],
+            }
+        );
+        assert_eq!(
+            playlist.entries[1].path,
+            Path::new("https://example.com/stream.mp3")
+        );
+        assert_eq!(
+            playlist.entries[2].path,
+            Path::new("sftp://nas/music/Blue in Green.flac")
+        );
+        assert_eq!(playlist.trailing, vec!["# the end".to_string()]);
+
+        write(&path, &playlist).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), EXTENDED);
+    }
+
+    #[test]
+    fn edits_keep_extinf_with_its_track() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("evening.m3u8");
+        fs::write(&path, EXTENDED).unwrap();
+
+        let mut editor = PlaylistEditor::open(&path).unwrap();
+        editor.move_down();
+        editor.save().unwrap();
+        assert!(append(&path, &dir.path().join("02 Freddie.flac")).unwrap());
+
+        let saved = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            saved,
+            "#EXTM3U
+# a note about the next one
+#EXTINF:-1,Radio
+https://example.com/stream.mp3
+#PLAYLIST:Evening
+#EXTINF:545,Miles Davis - So What
+Kind of Blue/01 So What.flac
+sftp://nas/music/Blue in Green.flac
+02 Freddie.flac
+# the end
+"
+        );
+    }
+}
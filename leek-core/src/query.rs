@@ -0,0 +1,360 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::library::Track;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Field {
+    Artist,
+    Album,
+    AlbumArtist,
+    Title,
+    Year,
+    Path,
+    Rating,
+    Plays,
+    LastPlayed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Contains,
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Term {
+    Filter(Field, Op, String),
+    Fuzzy(String),
+}
+
+// What the database knows about a track beyond its tags.
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    pub rating: Option<u32>,
+    pub plays: u64,
+    pub last_played: Option<i64>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Query {
+    pub terms: Vec<Term>,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Field> {
+        match name.to_lowercase().as_str() {
+            "artist" => Some(Field::Artist),
+            "album" => Some(Field::Album),
+            "albumartist" => Some(Field::AlbumArtist),
+            "title" => Some(Field::Title),
+            "year" | "date" => Some(Field::Year),
+            "path" | "file" => Some(Field::Path),
+            "rating" | "stars" => Some(Field::Rating),
+            "plays" | "playcount" => Some(Field::Plays),
+            "lastplayed" => Some(Field::LastPlayed),
+            _ => None,
+        }
+    }
+}
+
+fn split_op(value: &str) -> (Op, &str) {
+    for (prefix, op) in [
+        ("<=", Op::Le),
+        (">=", Op::Ge),
+        ("<", Op::Lt),
+        (">", Op::Gt),
+        ("=", Op::Eq),
+    ] {
+        if let Some(rest) = value.strip_prefix(prefix) {
+            return (op, rest);
+        }
+    }
+    (Op::Contains, value)
+}
+
+// Splits on whitespace while keeping double-quoted sections together,
+// so `album:"kind of blue"` stays a single token.
+fn tokenize(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in input.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Query {
+        let terms = tokenize(input)
+            .into_iter()
+            .map(|token| {
+                if let Some((name, value)) = token.split_once(':')
+                    && let Some(field) = Field::parse(name)
+                {
+                    let (op, value) = split_op(value);
+                    return Term::Filter(field, op, value.to_lowercase());
+                }
+                // `rating>=4` works as well as `rating:>=4`.
+                if let Some(i) = token.find(['<', '>', '='])
+                    && let Some(field) = Field::parse(&token[..i])
+                {
+                    let (op, value) = split_op(&token[i..]);
+                    return Term::Filter(field, op, value.to_lowercase());
+                }
+                Term::Fuzzy(token.to_lowercase())
+            })
+            .collect();
+        Query { terms }
+    }
+
+    pub fn matches(&self, track: &Track, stats: &Stats) -> bool {
+        self.terms.iter().all(|term| term.matches(track, stats))
+    }
+
+    // Whether matching needs `Stats`, which cost a database query.
+    pub fn needs_stats(&self) -> bool {
+        self.terms.iter().any(|term| {
+            matches!(
+                term,
+                Term::Filter(Field::Rating | Field::Plays | Field::LastPlayed, _, _)
+            )
+        })
+    }
+}
+
+impl Term {
+    fn matches(&self, track: &Track, stats: &Stats) -> bool {
+        match self {
+            Term::Fuzzy(needle) => {
+                let haystack = format!(
+                    "{} {} {} {}",
+                    track.tags.artist.as_deref().unwrap_or(""),
+                    track.tags.album.as_deref().unwrap_or(""),
+                    track.tags.title.as_deref().unwrap_or(""),
+                    track.path.file_name().unwrap_or_default().to_string_lossy()
+                );
+                fuzzy_match(&haystack.to_lowercase(), needle)
+            }
+            Term::Filter(Field::Year, op, value) => {
+                let (Some(year), Ok(wanted)) = (track.tags.year, value.parse::<u32>()) else {
+                    return false;
+                };
+                number_matches(year as u64, *op, wanted as u64)
+            }
+            Term::Filter(Field::Rating, op, value) => {
+                let (Some(rating), Ok(wanted)) = (stats.rating, value.parse::<u32>()) else {
+                    return false;
+                };
+                number_matches(rating as u64, *op, wanted as u64)
+            }
+            Term::Filter(Field::Plays, op, value) => {
+                let Ok(wanted) = value.parse::<u64>() else {
+                    return false;
+                };
+                number_matches(stats.plays, *op, wanted)
+            }
+            // `lastplayed>30d` is anything not played in the last 30 days,
+            // which includes tracks that were never played.
+            Term::Filter(Field::LastPlayed, op, value) => {
+                let Ok(wanted) = value.trim_end_matches('d').parse::<u64>() else {
+                    return false;
+                };
+                match stats.last_played {
+                    Some(played_at) => {
+                        let days = (now() - played_at).max(0) / SECONDS_PER_DAY;
+                        number_matches(days as u64, *op, wanted)
+                    }
+                    None => matches!(op, Op::Gt | Op::Ge),
+                }
+            }
+            Term::Filter(Field::Artist, op, value) => {
+                let artist = Term::Filter(Field::AlbumArtist, *op, value.clone());
+                text_matches(track.tags.artist.as_deref(), *op, value)
+                    || artist.matches(track, stats)
+            }
+            Term::Filter(field, op, value) => {
+                let text = match field {
+                    Field::AlbumArtist => track.tags.display_album_artist(),
+                    Field::Album => track.tags.album.clone(),
+                    Field::Title => track.tags.title.clone(),
+                    Field::Path => Some(track.path.to_string_lossy().into_owned()),
+                    Field::Artist
+                    | Field::Year
+                    | Field::Rating
+                    | Field::Plays
+                    | Field::LastPlayed => None,
+                };
+                text_matches(text.as_deref(), *op, value)
+            }
+        }
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn number_matches(number: u64, op: Op, wanted: u64) -> bool {
+    match op {
+        Op::Contains | Op::Eq => number == wanted,
+        Op::Lt => number < wanted,
+        Op::Le => number <= wanted,
+        Op::Gt => number > wanted,
+        Op::Ge => number >= wanted,
+    }
+}
+
+fn text_matches(text: Option<&str>, op: Op, value: &str) -> bool {
+    let Some(text) = text.map(|t| t.to_lowercase()) else {
+        return false;
+    };
+    match op {
+        Op::Eq => text == value,
+        Op::Lt => text.as_str() < value,
+        Op::Le => text.as_str() <= value,
+        Op::Gt => text.as_str() > value,
+        Op::Ge => text.as_str() >= value,
+        Op::Contains => text.contains(value),
+    }
+}
+
+fn fuzzy_match(haystack: &str, needle: &str) -> bool {
+    let mut chars = haystack.chars();
+    needle.chars().all(|n| chars.any(|h| h == n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tags::Tags;
+    use std::path::PathBuf;
+
+    fn track() -> Track {
+        Track {
+            path: PathBuf::from("/music/Miles Davis/Kind of Blue/01 So What.flac"),
+            mtime: 0,
+            added_at: 0,
+            tags: Tags {
+                title: Some("So What".into()),
+                artist: Some("Miles Davis".into()),
+                album: Some("Kind of Blue".into()),
+                year: Some(1959),
+                ..Tags::default()
+            },
+        }
+    }
+
+    fn finds(query: &str, stats: &Stats) -> bool {
+        Query::parse(query).matches(&track(), stats)
+    }
+
+    #[test]
+    fn quotes_keep_values_together() {
+        assert_eq!(
+            Query::parse(r#"album:"Kind of Blue" so"#).terms,
+            vec![
+                Term::Filter(Field::Album, Op::Contains, "kind of blue".into()),
+                Term::Fuzzy("so".into()),
+            ]
+        );
+        assert!(finds(r#"album:"kind of blue""#, &Stats::default()));
+        assert!(!finds(r#"album:"kind of red""#, &Stats::default()));
+    }
+
+    #[test]
+    fn years_compare_as_numbers() {
+        assert_eq!(
+            Query::parse("year:<1970").terms,
+            vec![Term::Filter(Field::Year, Op::Lt, "1970".into())]
+        );
+        assert!(finds("year:<1970", &Stats::default()));
+        assert!(!finds("year:>=1970", &Stats::default()));
+        assert!(finds("date=1959", &Stats::default()));
+    }
+
+    #[test]
+    fn ratings_and_plays_come_from_stats() {
+        let rated = Stats {
+            rating: Some(4),
+            plays: 12,
+            last_played: None,
+        };
+        assert_eq!(
+            Query::parse("rating>=4").terms,
+            vec![Term::Filter(Field::Rating, Op::Ge, "4".into())]
+        );
+        assert!(Query::parse("rating>=4").needs_stats());
+        assert!(finds("rating>=4", &rated));
+        assert!(finds("stars:4 plays>10", &rated));
+        assert!(!finds("rating>=4", &Stats::default()));
+        assert!(!Query::parse("artist:miles").needs_stats());
+    }
+
+    #[test]
+    fn last_played_counts_days() {
+        let played = |days: i64| Stats {
+            last_played: Some(now() - days * SECONDS_PER_DAY),
+            ..Stats::default()
+        };
+        assert!(finds("lastplayed>30d", &played(45)));
+        assert!(!finds("lastplayed>30d", &played(3)));
+        assert!(finds("lastplayed<7", &played(3)));
+        // Never played is longer ago than any number of days.
+        assert!(finds("lastplayed>30d", &Stats::default()));
+        assert!(!finds("lastplayed<30d", &Stats::default()));
+    }
+
+    #[test]
+    fn unknown_fields_are_searched_for() {
+        assert_eq!(
+            Query::parse("genre:jazz").terms,
+            vec![Term::Fuzzy("genre:jazz".into())]
+        );
+        assert!(!finds("genre:jazz", &Stats::default()));
+    }
+
+    #[test]
+    fn malformed_input_is_harmless() {
+        // An unclosed quote runs to the end.
+        assert_eq!(
+            Query::parse(r#"album:"kind of"#).terms,
+            vec![Term::Filter(Field::Album, Op::Contains, "kind of".into())]
+        );
+        assert!(!finds("year:abc", &Stats::default()));
+        assert!(!finds("rating>=", &Stats::default()));
+        assert!(!finds("lastplayed>soon", &Stats::default()));
+        assert!(Query::parse("   ").terms.is_empty());
+        assert!(finds("", &Stats::default()));
+    }
+
+    #[test]
+    fn fuzzy_terms_match_in_order() {
+        assert!(finds("mdkob", &Stats::default()));
+        assert!(finds("sowhat", &Stats::default()));
+        assert!(!finds("bokm", &Stats::default()));
+        assert!(fuzzy_match("kind of blue", "kob"));
+        assert!(!fuzzy_match("kind of blue", "kobb"));
+    }
+}
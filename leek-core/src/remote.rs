@@ -0,0 +1,347 @@
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::thread;
+
+use crate::library;
+use crate::log;
+
+const SCHEMES: &[&str] = &["sftp", "dav", "davs"];
+const CHUNK_SIZE: usize = 64 * 1024;
+// Streams kept around after they are opened, so seeking and the preloaded
+// next track don't download the file again.
+const CACHED_STREAMS: usize = 2;
+
+// Music on another machine, addressed by URL (`sftp://user@host/music`,
+// `davs://user@host/remote.php/dav/files/user/Music`).
+// Remote paths travel through the player as ordinary `PathBuf`s holding
+// the URL, so the queue, history and playlists keep working unchanged.
+pub trait Remote: Send + Sync {
+    fn list(&self, path: &str) -> Result<Vec<Entry>>;
+    fn open(&self, path: &str) -> Result<RemoteFile>;
+}
+
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub name: String,
+    pub is_dir: bool,
+}
+
+// An open remote file, with its size when the server gives one.
+pub struct RemoteFile {
+    pub reader: Box<dyn Read + Send>,
+    pub size: Option<u64>,
+}
+
+// How far along a remote track's download is. `stalled` is set while the
+// decoder is waiting on data that has not arrived yet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    pub received: u64,
+    pub size: Option<u64>,
+    pub done: bool,
+    pub stalled: bool,
+}
+
+impl Progress {
+    pub fn ratio(&self) -> Option<f64> {
+        if self.done {
+            return Some(1.0);
+        }
+        let size = self.size.filter(|&size| size > 0)?;
+        Some((self.received as f64 / size as f64).clamp(0.0, 1.0))
+    }
+}
+
+// The parts of a remote URL. `path` is what the server sees: absolute, or
+// relative to the login directory when the URL starts with `/~/`.
+#[derive(Clone, Debug)]
+pub struct Location {
+    pub scheme: String,
+    pub user: Option<String>,
+    pub host: String,
+    pub port: Option<u16>,
+    pub path: String,
+}
+
+impl Location {
+    pub fn parse(url: &str) -> Option<Location> {
+        let (scheme, rest) = url.split_once("://")?;
+        if !SCHEMES.contains(&scheme) {
+            return None;
+        }
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], rest[i..].replace('\\', "/")),
+            None => (rest, String::from("/")),
+        };
+        let (user, host) = match authority.rsplit_once('@') {
+            Some((user, host)) => (Some(user.to_string()), host),
+            None => (None, authority),
+        };
+        let (host, port) = match host.rsplit_once(':') {
+            Some((name, port)) if port.parse::<u16>().is_ok() => (name, port.parse().ok()),
+            _ => (host, None),
+        };
+        if host.is_empty() {
+            return None;
+        }
+        let path = match path.strip_prefix("/~") {
+            Some(relative) => relative.trim_start_matches('/').to_string(),
+            None => path,
+        };
+
+        Some(Location {
+            scheme: scheme.to_string(),
+            user,
+            host: host.to_string(),
+            port,
+            path,
+        })
+    }
+
+    fn server(&self) -> String {
+        format!(
+            "{}://{}@{}:{}",
+            self.scheme,
+            self.user.as_deref().unwrap_or_default(),
+            self.host,
+            self.port.unwrap_or_default()
+        )
+    }
+}
+
+pub fn is_remote(path: &Path) -> bool {
+    path.to_str()
+        .and_then(|url| url.split_once("://"))
+        .is_some_and(|(scheme, _)| SCHEMES.contains(&scheme))
+}
+
+fn locate(path: &Path) -> Result<Location> {
+    path.to_str()
+        .and_then(Location::parse)
+        .ok_or_else(|| anyhow!("not a remote path: {}", path.display()))
+}
+
+fn connect(location: &Location) -> Result<Arc<dyn Remote>> {
+    match location.scheme.as_str() {
+        #[cfg(feature = "sftp")]
+        "sftp" => Ok(Arc::new(crate::sftp::Sftp::connect(location)?)),
+        #[cfg(not(feature = "sftp"))]
+        "sftp" => Err(anyhow!("built without SFTP support")),
+        #[cfg(feature = "webdav")]
+        "dav" | "davs" => Ok(Arc::new(crate::webdav::WebDav::connect(location)?)),
+        #[cfg(not(feature = "webdav"))]
+        "dav" | "davs" => Err(anyhow!("built without WebDAV support")),
+        scheme => Err(anyhow!("unsupported scheme: {}", scheme)),
+    }
+}
+
+fn connections() -> &'static Mutex<HashMap<String, Arc<dyn Remote>>> {
+    static CONNECTIONS: OnceLock<Mutex<HashMap<String, Arc<dyn Remote>>>> = OnceLock::new();
+    CONNECTIONS.get_or_init(Mutex::default)
+}
+
+// Runs `f` on a cached connection to the path's server. A failure drops the
+// connection and tries once more on a fresh one, in case the server went
+// away since it was opened.
+fn with_remote<T>(path: &Path, f: impl Fn(&dyn Remote, &str) -> Result<T>) -> Result<T> {
+    let location = locate(path)?;
+    let key = location.server();
+
+    let cached = connections().lock().unwrap().get(&key).cloned();
+    if let Some(remote) = cached {
+        match f(remote.as_ref(), &location.path) {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                log::warn(&format!("Reconnecting to {}: {}", location.host, e));
+                connections().lock().unwrap().remove(&key);
+            }
+        }
+    }
+
+    let remote = connect(&location)?;
+    connections()
+        .lock()
+        .unwrap()
+        .insert(key, Arc::clone(&remote));
+    f(remote.as_ref(), &location.path)
+}
+
+// Directories first, then files, each sorted by name.
+pub fn list(path: &Path) -> Result<Vec<Entry>> {
+    let mut entries = with_remote(path, |remote, path| remote.list(path))?;
+    entries.sort_by(|a, b| {
+        b.is_dir
+            .cmp(&a.is_dir)
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+    Ok(entries)
+}
+
+// The audio files under `dir` and its subfolders, like `library::audio_files`.
+pub fn audio_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in list(dir)? {
+        let path = dir.join(&entry.name);
+        if entry.is_dir {
+            files.extend(audio_files(&path)?);
+        } else if library::is_audio_file(&path) {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+pub fn open(path: &Path) -> Result<Stream> {
+    {
+        let mut cache = streams().lock().unwrap();
+        if let Some(i) = cache.iter().position(|(cached, _)| cached == path) {
+            let (_, buffer) = &cache[i];
+            let failed = buffer.0.lock().unwrap().error.is_some();
+            if !failed {
+                return Ok(Stream::new(Arc::clone(buffer)));
+            }
+            cache.remove(i);
+        }
+    }
+
+    // Not under the cache lock, so a slow server only holds up this track
+    // and not the buffering display of the others.
+    let file = with_remote(path, |remote, path| remote.open(path))?;
+    let buffer = Arc::new((
+        Mutex::new(Buffer {
+            size: file.size,
+            ..Buffer::default()
+        }),
+        Condvar::new(),
+    ));
+    download(file.reader, Arc::clone(&buffer));
+
+    let mut cache = streams().lock().unwrap();
+    cache.push((path.to_path_buf(), Arc::clone(&buffer)));
+    if cache.len() > CACHED_STREAMS {
+        cache.remove(0);
+    }
+    Ok(Stream::new(buffer))
+}
+
+// The download state of a remote file that is open for playback.
+pub fn progress(path: &Path) -> Option<Progress> {
+    let cache = streams().lock().unwrap();
+    let (_, buffer) = cache.iter().find(|(cached, _)| cached == path)?;
+    let state = buffer.0.lock().unwrap();
+    Some(Progress {
+        received: state.data.len() as u64,
+        size: state.size,
+        done: state.done,
+        stalled: state.stalled,
+    })
+}
+
+type SharedBuffer = Arc<(Mutex<Buffer>, Condvar)>;
+
+fn streams() -> &'static Mutex<Vec<(PathBuf, SharedBuffer)>> {
+    static STREAMS: OnceLock<Mutex<Vec<(PathBuf, SharedBuffer)>>> = OnceLock::new();
+    STREAMS.get_or_init(Mutex::default)
+}
+
+#[derive(Default)]
+struct Buffer {
+    data: Vec<u8>,
+    size: Option<u64>,
+    done: bool,
+    stalled: bool,
+    error: Option<String>,
+}
+
+// Reads the whole file into memory on a background thread, ahead of the
+// decoder. The download stops early once nothing holds the buffer anymore.
+fn download(mut reader: Box<dyn Read + Send>, buffer: SharedBuffer) {
+    thread::spawn(move || {
+        let mut chunk = vec![0; CHUNK_SIZE];
+        loop {
+            if Arc::strong_count(&buffer) == 1 {
+                return;
+            }
+            let result = reader.read(&mut chunk);
+            let (lock, ready) = &*buffer;
+            let mut state = lock.lock().unwrap();
+            match result {
+                Ok(0) => state.done = true,
+                Ok(n) => state.data.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    state.error = Some(e.to_string());
+                    state.done = true;
+                }
+            }
+            ready.notify_all();
+            if state.done {
+                return;
+            }
+        }
+    });
+}
+
+// A seekable view of a file that is still downloading. Reads past what has
+// arrived so far wait for the download to catch up.
+pub struct Stream {
+    buffer: SharedBuffer,
+    position: u64,
+}
+
+impl Stream {
+    fn new(buffer: SharedBuffer) -> Stream {
+        Stream {
+            buffer,
+            position: 0,
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let (lock, ready) = &*self.buffer;
+        let mut state = lock.lock().unwrap();
+        if state.data.len() as u64 <= self.position && !state.done {
+            state.stalled = true;
+            while state.data.len() as u64 <= self.position && !state.done {
+                state = ready.wait(state).unwrap();
+            }
+            state.stalled = false;
+        }
+        if let Some(error) = &state.error
+            && state.data.len() as u64 <= self.position
+        {
+            return Err(io::Error::other(error.clone()));
+        }
+
+        let start = (self.position as usize).min(state.data.len());
+        let n = out.len().min(state.data.len() - start);
+        out[..n].copy_from_slice(&state.data[start..start + n]);
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for Stream {
+    fn seek(&mut self, from: SeekFrom) -> io::Result<u64> {
+        let position = match from {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(offset) => {
+                let (lock, ready) = &*self.buffer;
+                let mut state = lock.lock().unwrap();
+                while !state.done {
+                    state = ready.wait(state).unwrap();
+                }
+                (state.data.len() as u64).checked_add_signed(offset)
+            }
+        };
+        self.position = position
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek before start"))?;
+        Ok(self.position)
+    }
+}
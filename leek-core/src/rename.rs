@@ -0,0 +1,282 @@
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::library;
+use crate::tags::{self, Tags};
+
+// Characters that aren't safe in file names on one system or another.
+const UNSAFE_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Rename,
+    Unchanged,
+    // The pattern used a tag the file doesn't have.
+    MissingTags,
+    // Another file has, or would get, the same name.
+    Conflict,
+}
+
+#[derive(Clone, Debug)]
+pub struct Rename {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub outcome: Outcome,
+}
+
+// Works out new names for the audio files under `dir` from a pattern like
+// `%artist% - %album%/%track% - %title%.%ext%`, relative to `dir`. Nothing
+// is moved until `apply`. Tags already in `known` aren't read again.
+pub fn plan(dir: &Path, pattern: &str, known: &HashMap<PathBuf, Tags>) -> Vec<Rename> {
+    plan_into(dir, dir, pattern, known)
+}
+
+// Like `plan`, with the pattern relative to `dest`, e.g. to move a download
+// into the library. Everything else in a folder whose audio all lands in one
+// place, such as its cover, cue sheet or rip log, goes along with it.
+pub fn plan_into(
+    dir: &Path,
+    dest: &Path,
+    pattern: &str,
+    known: &HashMap<PathBuf, Tags>,
+) -> Vec<Rename> {
+    let mut renames: Vec<Rename> = library::audio_files(dir)
+        .into_iter()
+        .map(|from| {
+            let tags = match known.get(&from) {
+                Some(tags) => tags.clone(),
+                None => tags::read(&from).unwrap_or_default(),
+            };
+            match expand(pattern, &tags, &from) {
+                Some(relative) => {
+                    let to = dest.join(relative);
+                    let outcome = if to == from {
+                        Outcome::Unchanged
+                    } else {
+                        Outcome::Rename
+                    };
+                    Rename { from, to, outcome }
+                }
+                None => Rename {
+                    to: from.clone(),
+                    from,
+                    outcome: Outcome::MissingTags,
+                },
+            }
+        })
+        .collect();
+    if dest != dir {
+        let companions = companions(&renames);
+        renames.extend(companions);
+    }
+
+    for i in 0..renames.len() {
+        if renames[i].outcome != Outcome::Rename {
+            continue;
+        }
+        let to = &renames[i].to;
+        let taken = renames
+            .iter()
+            .enumerate()
+            .any(|(j, other)| j != i && (other.to == *to || other.from == *to))
+            || to.exists();
+        if taken {
+            renames[i].outcome = Outcome::Conflict;
+        }
+    }
+    renames.sort_by(|a, b| a.from.cmp(&b.from));
+    renames
+}
+
+fn companions(renames: &[Rename]) -> Vec<Rename> {
+    let mut folders: HashMap<&Path, Option<&Path>> = HashMap::new();
+    for rename in renames {
+        let (Some(from), Some(to)) = (rename.from.parent(), rename.to.parent()) else {
+            continue;
+        };
+        let to = (rename.outcome == Outcome::Rename).then_some(to);
+        folders
+            .entry(from)
+            .and_modify(|dest| {
+                if *dest != to {
+                    *dest = None;
+                }
+            })
+            .or_insert(to);
+    }
+
+    let mut companions = Vec::new();
+    for (folder, dest) in folders {
+        let (Some(dest), Ok(entries)) = (dest, fs::read_dir(folder)) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let from = entry.path();
+            let is_file = entry.file_type().map(|t| t.is_file()).unwrap_or(false);
+            if is_file && !library::is_audio_file(&from) {
+                let to = dest.join(entry.file_name());
+                companions.push(Rename {
+                    from,
+                    to,
+                    outcome: Outcome::Rename,
+                });
+            }
+        }
+    }
+    companions
+}
+
+fn expand(pattern: &str, tags: &Tags, path: &Path) -> Option<PathBuf> {
+    let mut out = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find('%') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = after.find('%')?;
+        out.push_str(&clean(&field(&after[..end], tags, path)?));
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+
+    // Each `/` in the pattern starts a folder; empty parts would escape it.
+    let parts: Vec<&str> = out.split('/').map(str::trim).collect();
+    if parts
+        .iter()
+        .any(|part| part.is_empty() || *part == "." || *part == "..")
+    {
+        return None;
+    }
+    Some(parts.iter().collect())
+}
+
+fn field(name: &str, tags: &Tags, path: &Path) -> Option<String> {
+    match name.to_lowercase().as_str() {
+        "artist" => tags.artist.clone(),
+        "albumartist" => tags.display_album_artist().or_else(|| tags.artist.clone()),
+        "album" => tags.album.clone(),
+        "title" => tags.title.clone(),
+        "track" => tags.track_number.map(|n| format!("{:02}", n)),
+        "disc" => tags.disc_number.map(|n| n.to_string()),
+        "year" => tags.year.map(|n| n.to_string()),
+        "ext" => Some(path.extension()?.to_string_lossy().to_lowercase()),
+        _ => None,
+    }
+}
+
+fn clean(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if UNSAFE_CHARS.contains(&c) { '_' } else { c })
+        .collect::<String>()
+        .trim()
+        .trim_end_matches('.')
+        .to_string()
+}
+
+// What `apply` got done: each file it moved, from and to, and the error
+// that stopped it partway, if one did.
+#[derive(Debug, Default)]
+pub struct Applied {
+    pub moved: Vec<(PathBuf, PathBuf)>,
+    pub error: Option<anyhow::Error>,
+}
+
+// Moves the files marked for renaming, making folders as needed and
+// removing ones left empty. Stops at the first failure, keeping what was
+// already moved.
+pub fn apply(renames: &[Rename]) -> Applied {
+    let mut applied = Applied::default();
+    for rename in renames.iter().filter(|r| r.outcome == Outcome::Rename) {
+        if let Err(e) = move_file(&rename.from, &rename.to) {
+            applied.error = Some(e);
+            break;
+        }
+        applied.moved.push((rename.from.clone(), rename.to.clone()));
+        if let Some(parent) = rename.from.parent() {
+            // Fails harmlessly while the folder still holds anything.
+            let _ = fs::remove_dir(parent);
+        }
+    }
+    applied
+}
+
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if to.exists() {
+        return Err(anyhow!("{} already exists", to.display()));
+    }
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match fs::rename(from, to) {
+        // A rename can't cross filesystems, such as from a downloads disk
+        // to the library's, so the file is copied over instead.
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            if let Err(e) = fs::copy(from, to) {
+                let _ = fs::remove_file(to);
+                return Err(e.into());
+            }
+            fs::remove_file(from)?;
+        }
+        result => result?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moving_a_folder_takes_its_other_files_along() {
+        let root = tempfile::tempdir().unwrap();
+        let (dir, dest) = (root.path().join("downloads/Rip"), root.path().join("music"));
+        fs::create_dir_all(&dir).unwrap();
+        fs::create_dir_all(&dest).unwrap();
+        let mut known = HashMap::new();
+        for (name, title) in [("01.flac", "One"), ("02.flac", "Two")] {
+            fs::write(dir.join(name), "").unwrap();
+            let tags = Tags {
+                album: Some("Album".to_string()),
+                title: Some(title.to_string()),
+                ..Tags::default()
+            };
+            known.insert(dir.join(name), tags);
+        }
+        fs::write(dir.join("cover.jpg"), "").unwrap();
+        fs::write(dir.join("rip.log"), "").unwrap();
+
+        let renames = plan_into(&dir, &dest, "%album%/%title%.%ext%", &known);
+        assert_eq!(renames.len(), 4);
+        let applied = apply(&renames);
+        assert!(applied.error.is_none());
+        assert_eq!(applied.moved.len(), 4);
+        for name in ["One.flac", "Two.flac", "cover.jpg", "rip.log"] {
+            assert!(dest.join("Album").join(name).exists(), "{}", name);
+        }
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn leaves_other_files_when_the_audio_is_split_up() {
+        let root = tempfile::tempdir().unwrap();
+        let (dir, dest) = (root.path().join("downloads/Mix"), root.path().join("music"));
+        fs::create_dir_all(&dir).unwrap();
+        let mut known = HashMap::new();
+        for (name, album) in [("01.flac", "One"), ("02.flac", "Two")] {
+            fs::write(dir.join(name), "").unwrap();
+            let tags = Tags {
+                album: Some(album.to_string()),
+                title: Some(name.to_string()),
+                ..Tags::default()
+            };
+            known.insert(dir.join(name), tags);
+        }
+        fs::write(dir.join("cover.jpg"), "").unwrap();
+
+        let renames = plan_into(&dir, &dest, "%album%/%title%", &known);
+        assert_eq!(renames.len(), 2);
+    }
+}
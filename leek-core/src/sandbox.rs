@@ -0,0 +1,314 @@
+use anyhow::{Result, anyhow};
+use rodio::Source;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStderr, ChildStdout, Command, ExitStatus, Stdio};
+use std::sync::{Mutex, OnceLock, mpsc};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::decode::{self, Opened};
+use crate::log;
+use crate::tags::{self, LoopPoints, Tags};
+
+// The ffmpeg fallback reads the whole file before the first sample, so a
+// long one can take a while; a decoder stuck for longer than this is given
+// up on.
+const START_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Written before the samples: channels, sample rate, the duration in
+// milliseconds (0 when unknown) and the length of the `Details` that
+// follow, little-endian.
+const HEADER_LEN: usize = 2 + 4 + 8 + 4;
+// Far more than any file's tags need; a child claiming more is broken.
+const DETAILS_LIMIT: usize = 1024 * 1024;
+
+// How much of the child's stderr is kept for the error message; the rest is
+// read and thrown away.
+const STDERR_LIMIT: u64 = 64 * 1024;
+
+struct Header {
+    channels: u16,
+    sample_rate: u32,
+    total_duration: Option<Duration>,
+    details_len: usize,
+}
+
+impl Header {
+    fn parse(bytes: &[u8; HEADER_LEN]) -> Header {
+        let millis = u64::from_le_bytes(bytes[6..14].try_into().unwrap());
+        Header {
+            channels: u16::from_le_bytes([bytes[0], bytes[1]]),
+            sample_rate: u32::from_le_bytes(bytes[2..6].try_into().unwrap()),
+            total_duration: (millis > 0).then(|| Duration::from_millis(millis)),
+            details_len: u32::from_le_bytes(bytes[14..18].try_into().unwrap()) as usize,
+        }
+    }
+}
+
+// What the player needs from the file's tags, read by the child as JSON
+// after the header so the parent never parses the file itself.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+struct Details {
+    tags: Tags,
+    loop_points: Option<LoopPoints>,
+}
+
+fn read_header(reader: &mut impl Read) -> io::Result<(Header, Details)> {
+    let mut bytes = [0; HEADER_LEN];
+    reader.read_exact(&mut bytes)?;
+    let header = Header::parse(&bytes);
+    if header.details_len > DETAILS_LIMIT {
+        return Err(io::Error::new(ErrorKind::InvalidData, "oversized header"));
+    }
+    let mut details = vec![0; header.details_len];
+    reader.read_exact(&mut details)?;
+    Ok((header, serde_json::from_slice(&details)?))
+}
+
+// The child's side: decodes `path` and writes the header and 16-bit
+// samples to stdout until the track ends or the parent hangs up.
+pub fn serve(path: &Path) -> Result<()> {
+    let source = decode::decode(path)?;
+    let (tags, loop_points) = tags::read_for_playback(path);
+    let details = serde_json::to_vec(&Details { tags, loop_points })?;
+    let mut out = BufWriter::new(io::stdout().lock());
+    let millis = source.total_duration().map_or(0, |d| d.as_millis() as u64);
+
+    let mut header = Vec::with_capacity(HEADER_LEN + details.len());
+    header.extend(source.channels().to_le_bytes());
+    header.extend(source.sample_rate().to_le_bytes());
+    header.extend(millis.to_le_bytes());
+    header.extend((details.len() as u32).to_le_bytes());
+    header.extend(details);
+    let written = out.write_all(&header).and_then(|()| {
+        for sample in source {
+            out.write_all(&sample.to_le_bytes())?;
+        }
+        out.flush()
+    });
+    match written {
+        // Seeking or skipping drops the stream.
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+// Decodes `path` in a child process running `program decode-child`, so a
+// decoder that crashes or hangs on a bad file takes down only the child.
+pub fn open(program: &Path, path: &Path) -> Result<Opened> {
+    let mut child = Command::new(program)
+        .arg("decode-child")
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| anyhow!("no pipe from the decoder"))?;
+    let stderr = child.stderr.take().map(drain);
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut reader = BufReader::new(stdout);
+        let result = read_header(&mut reader);
+        let _ = tx.send((reader, result));
+    });
+
+    let (reader, (header, details)) = match rx.recv_timeout(START_TIMEOUT) {
+        Ok((reader, Ok(header))) => (reader, header),
+        Ok((_, Err(_))) => {
+            // Usually gone already; otherwise its header made no sense.
+            let _ = child.kill();
+            let status = child.wait();
+            return Err(failure(stderr, status));
+        }
+        Err(_) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(anyhow!("decoder stopped responding"));
+        }
+    };
+    // rodio divides by both, so a broken child mustn't get them through.
+    if header.channels == 0 || header.sample_rate == 0 {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(anyhow!(
+            "decoder reported {} channels at {} Hz",
+            header.channels,
+            header.sample_rate
+        ));
+    }
+
+    Ok(Opened {
+        source: Box::new(ChildSource {
+            child,
+            reader,
+            stderr,
+            header,
+            path: path.to_path_buf(),
+        }),
+        tags: details.tags,
+        loop_points: details.loop_points,
+    })
+}
+
+// Reads the child's stderr as it comes, so a chatty decoder can't fill the
+// pipe and stall, keeping the start of it for an error message.
+fn drain(mut pipe: ChildStderr) -> JoinHandle<String> {
+    thread::spawn(move || {
+        let mut message = Vec::new();
+        let _ = (&mut pipe).take(STDERR_LIMIT).read_to_end(&mut message);
+        let _ = io::copy(&mut pipe, &mut io::sink());
+        String::from_utf8_lossy(&message).into_owned()
+    })
+}
+
+// Why the child gave up: its own error message when it had one, or how it
+// died.
+fn failure(stderr: Option<JoinHandle<String>>, status: io::Result<ExitStatus>) -> anyhow::Error {
+    let stderr = stderr
+        .and_then(|thread| thread.join().ok())
+        .unwrap_or_default();
+    match (stderr.trim(), status) {
+        // anyhow's report from the child's main.
+        (message, _) if !message.is_empty() => {
+            anyhow!("{}", message.trim_start_matches("Error: "))
+        }
+        (_, Ok(status)) => anyhow!("decoder crashed ({})", status),
+        (_, Err(e)) => anyhow!("decoder crashed ({})", e),
+    }
+}
+
+// Tracks whose decoder died partway through, with why, until the player
+// takes them.
+fn crashes() -> &'static Mutex<Vec<(PathBuf, String)>> {
+    static CRASHES: OnceLock<Mutex<Vec<(PathBuf, String)>>> = OnceLock::new();
+    CRASHES.get_or_init(Mutex::default)
+}
+
+// Why `path`'s decoder crashed, if it did, rather than the track ending.
+pub fn take_crash(path: &Path) -> Option<String> {
+    let mut crashes = crashes().lock().unwrap();
+    let found = crashes.iter().position(|(crashed, _)| crashed == path)?;
+    Some(crashes.remove(found).1)
+}
+
+struct ChildSource {
+    child: Child,
+    reader: BufReader<ChildStdout>,
+    // Taken once the track ends, to tell a crash from the end of the file.
+    stderr: Option<JoinHandle<String>>,
+    header: Header,
+    path: PathBuf,
+}
+
+impl Iterator for ChildSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        let mut bytes = [0; 2];
+        if self.reader.read_exact(&mut bytes).is_ok() {
+            return Some(i16::from_le_bytes(bytes));
+        }
+        // The track ends here either way, but a crash is reported as a
+        // skip rather than letting it pass for the end of the file.
+        let stderr = self.stderr.take()?;
+        let status = self.child.wait();
+        if status.as_ref().is_ok_and(ExitStatus::success) {
+            return None;
+        }
+        let error = failure(Some(stderr), status).to_string();
+        log::warn(&format!(
+            "Decoder for {} crashed: {}",
+            self.path.display(),
+            error
+        ));
+        crashes().lock().unwrap().push((self.path.clone(), error));
+        None
+    }
+}
+
+impl Source for ChildSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.header.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.header.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.header.total_duration
+    }
+}
+
+impl Drop for ChildSource {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn decoder(dir: &Path, script: &str) -> PathBuf {
+        let program = dir.join("decoder");
+        fs::write(&program, format!("#!/bin/sh\n{script}")).unwrap();
+        fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+        program
+    }
+
+    #[test]
+    fn a_crash_partway_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        // Stereo at 44100 Hz with a title, one frame, then a crash.
+        let program = decoder(
+            dir.path(),
+            "printf '\\002\\000\\104\\254\\000\\000\\000\\000\\000\\000\\000\\000\\000\\000'\n\
+             printf '\\031\\000\\000\\000{\"tags\":{\"title\":\"Song\"}}'\n\
+             printf '\\001\\000\\002\\000'\n\
+             echo 'Error: bad frame' >&2\n\
+             exit 3\n",
+        );
+        let track = dir.path().join("track.flac");
+
+        let opened = open(&program, &track).unwrap();
+        assert_eq!(opened.tags.title.as_deref(), Some("Song"));
+        assert!(opened.loop_points.is_none());
+        let source = opened.source;
+        assert_eq!((source.channels(), source.sample_rate()), (2, 44100));
+        assert_eq!(source.collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(take_crash(&track).as_deref(), Some("bad frame"));
+        assert_eq!(take_crash(&track), None);
+    }
+
+    #[test]
+    fn an_empty_format_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        // No channels at 44100 Hz, which rodio can't play.
+        let program = decoder(
+            dir.path(),
+            "printf '\\000\\000\\104\\254\\000\\000\\000\\000\\000\\000\\000\\000\\000\\000'\n\
+             printf '\\002\\000\\000\\000{}'\n\
+             sleep 5\n",
+        );
+
+        let error = open(&program, &dir.path().join("track.flac"))
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "decoder reported 0 channels at 44100 Hz");
+    }
+}
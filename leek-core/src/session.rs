@@ -0,0 +1,36 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::player::Player;
+
+// The queue and position as of the last checkpoint, kept in the database.
+// `playing` is cleared on a clean exit, so finding it set means leek was
+// killed mid-track.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Session {
+    pub queue: Vec<PathBuf>,
+    pub index: usize,
+    pub position_ms: u64,
+    pub volume: u8,
+    pub playing: bool,
+    // The last volume on each output device, by name.
+    pub device_volumes: BTreeMap<String, u8>,
+}
+
+impl Session {
+    pub fn capture(player: &Player) -> Session {
+        Session {
+            queue: player.queue.clone(),
+            index: player.queue_index,
+            position_ms: player.elapsed.as_millis() as u64,
+            volume: player.volume,
+            playing: player.is_playing,
+            device_volumes: BTreeMap::new(),
+        }
+    }
+
+    pub fn position(&self) -> Duration {
+        Duration::from_millis(self.position_ms)
+    }
+}
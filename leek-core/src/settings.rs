@@ -0,0 +1,76 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::log;
+use crate::remote;
+
+const SETTINGS_FILE: &str = ".leek.toml";
+
+// Playback settings from a `.leek.toml` in a track's folder or one of its
+// parents. The nearest file wins, and missing keys keep their defaults.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct FolderSettings {
+    // Remember where each track was left and carry on from there.
+    pub audiobook: bool,
+    pub speed: f32,
+    pub gapless: bool,
+    pub skip_intro: u64,
+}
+
+impl Default for FolderSettings {
+    fn default() -> Self {
+        Self {
+            audiobook: false,
+            speed: 1.0,
+            gapless: true,
+            skip_intro: 0,
+        }
+    }
+}
+
+impl FolderSettings {
+    pub fn for_track(path: &Path) -> FolderSettings {
+        if remote::is_remote(path) {
+            return FolderSettings::default();
+        }
+        for dir in path.ancestors().skip(1) {
+            match Self::load(&dir.join(SETTINGS_FILE)) {
+                Ok(Some(settings)) => return settings,
+                Ok(None) => {}
+                Err(e) => {
+                    log::warn(&format!(
+                        "Ignoring {}: {}",
+                        dir.join(SETTINGS_FILE).display(),
+                        e
+                    ));
+                    break;
+                }
+            }
+        }
+        FolderSettings::default()
+    }
+
+    fn load(path: &Path) -> Result<Option<FolderSettings>> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut settings: FolderSettings = toml::from_str(&text)?;
+        // NaN gets through a clamp, and Duration panics when scaled by it.
+        if !settings.speed.is_finite() {
+            settings.speed = 1.0;
+        }
+        settings.speed = settings.speed.clamp(0.25, 4.0);
+        Ok(Some(settings))
+    }
+
+    pub fn skip_intro(&self) -> Duration {
+        Duration::from_secs(self.skip_intro)
+    }
+}
@@ -0,0 +1,134 @@
+use anyhow::{Result, anyhow};
+use directories::BaseDirs;
+use ssh2::{CheckResult, KnownHostFileKind, Session};
+use std::env;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::remote::{Entry, Location, Remote, RemoteFile};
+
+const DEFAULT_PORT: u16 = 22;
+const TIMEOUT_MS: u32 = 15_000;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const KEY_FILES: &[&str] = &["id_ed25519", "id_ecdsa", "id_rsa"];
+
+// Logs in with ssh-agent or an unencrypted key from ~/.ssh, and only talks
+// to hosts already in ~/.ssh/known_hosts.
+pub struct Sftp {
+    _session: Session,
+    sftp: ssh2::Sftp,
+}
+
+impl Sftp {
+    pub fn connect(location: &Location) -> Result<Sftp> {
+        let port = location.port.unwrap_or(DEFAULT_PORT);
+        let tcp = connect_tcp(&location.host, port)?;
+        let mut session = Session::new()?;
+        session.set_tcp_stream(tcp);
+        session.set_timeout(TIMEOUT_MS);
+        session.handshake()?;
+
+        let ssh_dir = ssh_dir().ok_or_else(|| anyhow!("no home directory"))?;
+        check_host_key(&session, &ssh_dir, &location.host, port)?;
+
+        let user = match &location.user {
+            Some(user) => user.clone(),
+            None => env::var("USER")
+                .or_else(|_| env::var("USERNAME"))
+                .map_err(|_| anyhow!("no user name in the URL"))?,
+        };
+        authenticate(&session, &ssh_dir, &user)?;
+
+        let sftp = session.sftp()?;
+        Ok(Sftp {
+            _session: session,
+            sftp,
+        })
+    }
+}
+
+impl Remote for Sftp {
+    fn list(&self, path: &str) -> Result<Vec<Entry>> {
+        let entries = self.sftp.readdir(Path::new(path))?;
+        Ok(entries
+            .into_iter()
+            .filter_map(|(path, stat)| {
+                let name = path.file_name()?.to_string_lossy().into_owned();
+                Some(Entry {
+                    name,
+                    is_dir: stat.is_dir(),
+                })
+            })
+            .collect())
+    }
+
+    fn open(&self, path: &str) -> Result<RemoteFile> {
+        let mut file = self.sftp.open(Path::new(path))?;
+        let size = file.stat().ok().and_then(|stat| stat.size);
+        Ok(RemoteFile {
+            reader: Box::new(file),
+            size,
+        })
+    }
+}
+
+// Tries each address the host resolves to, giving up on each after
+// CONNECT_TIMEOUT rather than the system's minutes-long default.
+fn connect_tcp(host: &str, port: u16) -> Result<TcpStream> {
+    let mut last_error = None;
+    for address in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) {
+            Ok(tcp) => return Ok(tcp),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(match last_error {
+        Some(e) => e.into(),
+        None => anyhow!("{} has no addresses", host),
+    })
+}
+
+fn ssh_dir() -> Option<PathBuf> {
+    Some(BaseDirs::new()?.home_dir().join(".ssh"))
+}
+
+fn check_host_key(session: &Session, ssh_dir: &Path, host: &str, port: u16) -> Result<()> {
+    let mut known_hosts = session.known_hosts()?;
+    let file = ssh_dir.join("known_hosts");
+    if file.exists() {
+        known_hosts.read_file(&file, KnownHostFileKind::OpenSSH)?;
+    }
+
+    let (key, _) = session
+        .host_key()
+        .ok_or_else(|| anyhow!("{} sent no host key", host))?;
+    match known_hosts.check_port(host, port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::NotFound => Err(anyhow!(
+            "{} is not in ~/.ssh/known_hosts, connect once with ssh to trust it",
+            host
+        )),
+        CheckResult::Mismatch => Err(anyhow!(
+            "the host key for {} does not match ~/.ssh/known_hosts",
+            host
+        )),
+        CheckResult::Failure => Err(anyhow!("could not check the host key for {}", host)),
+    }
+}
+
+fn authenticate(session: &Session, ssh_dir: &Path, user: &str) -> Result<()> {
+    if session.userauth_agent(user).is_ok() && session.authenticated() {
+        return Ok(());
+    }
+    for name in KEY_FILES {
+        let key = ssh_dir.join(name);
+        if key.exists() && session.userauth_pubkey_file(user, None, &key, None).is_ok() {
+            return Ok(());
+        }
+    }
+    Err(anyhow!(
+        "could not log in as {} with ssh-agent or a key in ~/.ssh",
+        user
+    ))
+}
@@ -0,0 +1,97 @@
+use anyhow::{Result, anyhow};
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use crate::log;
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+// Short, since reconnecting happens on the mixer's thread.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+type Output = Box<dyn Write + Send>;
+
+// A pipe source (`/tmp/snapfifo`) or a TCP source in server mode
+// (`tcp://host:4953`). `None` means the server isn't there to take it yet.
+fn open(target: &str) -> Result<Option<Output>> {
+    match target.strip_prefix("tcp://") {
+        Some(address) => connect(address),
+        None => open_pipe(target),
+    }
+}
+
+fn connect(address: &str) -> Result<Option<Output>> {
+    let mut last_error = None;
+    for address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) {
+            Ok(stream) => return Ok(Some(Box::new(stream))),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    match last_error {
+        Some(e) if e.kind() == ErrorKind::ConnectionRefused => Ok(None),
+        Some(e) => Err(e.into()),
+        None => Err(anyhow!("{} has no addresses", address)),
+    }
+}
+
+// Opening a pipe for writing waits for a reader, so it is opened without
+// blocking, which fails with ENXIO while snapserver isn't running. Writes
+// block again once it is open, like any pipe's.
+#[cfg(unix)]
+fn open_pipe(path: &str) -> Result<Option<Output>> {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let file = match OpenOptions::new()
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)
+    {
+        Ok(file) => file,
+        Err(e) if e.raw_os_error() == Some(libc::ENXIO) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let fd = file.as_raw_fd();
+    // SAFETY: `fd` is open for as long as `file` is.
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK);
+    }
+    Ok(Some(Box::new(file)))
+}
+
+#[cfg(not(unix))]
+fn open_pipe(path: &str) -> Result<Option<Output>> {
+    Ok(Some(Box::new(OpenOptions::new().write(true).open(path)?)))
+}
+
+// Writes PCM to a Snapcast source, which shares it with every client in
+// sync. The mixer's 48kHz 16-bit stereo is snapserver's default format.
+// While the server is away the audio is dropped, so tracks still move on,
+// and the source is reopened now and then.
+pub fn writer(target: &str) -> Result<impl FnMut(&[u8]) + Send + 'static> {
+    let mut out = open(target).map_err(|e| anyhow!("Snapcast source {}: {}", target, e))?;
+    if out.is_none() {
+        log::warn(&format!(
+            "Snapcast source {}: waiting for snapserver",
+            target
+        ));
+    }
+    let target = target.to_string();
+    let mut retry_at = Instant::now();
+    Ok(move |chunk: &[u8]| {
+        if out.is_none() && Instant::now() >= retry_at {
+            out = open(&target).ok().flatten();
+            retry_at = Instant::now() + RECONNECT_DELAY;
+        }
+        if let Some(writer) = &mut out
+            && let Err(e) = writer.write_all(chunk)
+        {
+            log::warn(&format!("Snapcast source {}: {}", target, e));
+            out = None;
+            retry_at = Instant::now() + RECONNECT_DELAY;
+        }
+    })
+}
@@ -0,0 +1,271 @@
+use rodio::Source;
+use std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2, PI};
+use std::time::Duration;
+
+use crate::decode::BoxedSource;
+
+// A spherical head (Brown & Duda, 1998) stands in for measured HRTFs:
+// each virtual speaker reaches the far ear later and with its highs
+// shadowed by the head, which is what places it outside the head instead
+// of in the middle of it.
+const HEAD_RADIUS: f32 = 0.0875;
+const SPEED_OF_SOUND: f32 = 343.0;
+// The least the head shadow lets through, and the angle it is darkest at.
+const SHADOW_MIN: f32 = 0.1;
+const SHADOW_ANGLE: f32 = 150.0;
+// Longer than the furthest ear is from any speaker, in samples at 192kHz.
+const DELAY_LINE: usize = 256;
+
+const LFE: Option<f32> = None;
+
+// Speaker angles in degrees, clockwise from straight ahead, in WAVE channel
+// order; the LFE goes to both ears alike. Gains are the usual downmix ones.
+fn layout(channels: u16) -> Option<&'static [(Option<f32>, f32)]> {
+    Some(match channels {
+        // Quad: FL FR BL BR.
+        4 => &[
+            (Some(-30.0), 1.0),
+            (Some(30.0), 1.0),
+            (Some(-110.0), FRAC_1_SQRT_2),
+            (Some(110.0), FRAC_1_SQRT_2),
+        ],
+        // 5.0: FL FR FC BL BR.
+        5 => &[
+            (Some(-30.0), 1.0),
+            (Some(30.0), 1.0),
+            (Some(0.0), FRAC_1_SQRT_2),
+            (Some(-110.0), FRAC_1_SQRT_2),
+            (Some(110.0), FRAC_1_SQRT_2),
+        ],
+        // 5.1: FL FR FC LFE BL BR.
+        6 => &[
+            (Some(-30.0), 1.0),
+            (Some(30.0), 1.0),
+            (Some(0.0), FRAC_1_SQRT_2),
+            (LFE, 0.5),
+            (Some(-110.0), FRAC_1_SQRT_2),
+            (Some(110.0), FRAC_1_SQRT_2),
+        ],
+        // 7.1: FL FR FC LFE BL BR SL SR.
+        8 => &[
+            (Some(-30.0), 1.0),
+            (Some(30.0), 1.0),
+            (Some(0.0), FRAC_1_SQRT_2),
+            (LFE, 0.5),
+            (Some(-150.0), FRAC_1_SQRT_2),
+            (Some(150.0), FRAC_1_SQRT_2),
+            (Some(-90.0), FRAC_1_SQRT_2),
+            (Some(90.0), FRAC_1_SQRT_2),
+        ],
+        _ => return None,
+    })
+}
+
+// One speaker as heard by one ear: a fractional delay, then the head
+// shadow as a one-pole, one-zero filter.
+struct EarPath {
+    delay: f32,
+    b0: f32,
+    b1: f32,
+    a1: f32,
+    line: [f32; DELAY_LINE],
+    write: usize,
+    x1: f32,
+    y1: f32,
+}
+
+impl EarPath {
+    // `incidence` is the angle between the speaker and the ear, in radians.
+    fn new(incidence: f32, sample_rate: u32) -> EarPath {
+        let rate = sample_rate as f32;
+        let omega = SPEED_OF_SOUND / HEAD_RADIUS;
+        let alpha = (1.0 + SHADOW_MIN / 2.0)
+            + (1.0 - SHADOW_MIN / 2.0) * (incidence.to_degrees() / SHADOW_ANGLE * PI).cos();
+        // H(s) = (1 + alpha s / 2w) / (1 + s / 2w), by the bilinear transform.
+        let k = rate / omega;
+        let b0 = (1.0 + alpha * k) / (1.0 + k);
+        let b1 = (1.0 - alpha * k) / (1.0 + k);
+        let a1 = (1.0 - k) / (1.0 + k);
+
+        // Sound wraps around the head once the ear faces away from it.
+        let seconds = if incidence < FRAC_PI_2 {
+            HEAD_RADIUS / SPEED_OF_SOUND * (1.0 - incidence.cos())
+        } else {
+            HEAD_RADIUS / SPEED_OF_SOUND * (1.0 + incidence - FRAC_PI_2)
+        };
+        EarPath {
+            delay: (seconds * rate).min(DELAY_LINE as f32 - 2.0),
+            b0,
+            b1,
+            a1,
+            line: [0.0; DELAY_LINE],
+            write: 0,
+            x1: 0.0,
+            y1: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        self.line[self.write] = input;
+        let read = self.write as f32 + DELAY_LINE as f32 - self.delay;
+        let whole = read.floor();
+        let fraction = read - whole;
+        let a = self.line[whole as usize % DELAY_LINE];
+        let b = self.line[(whole as usize + 1) % DELAY_LINE];
+        self.write = (self.write + 1) % DELAY_LINE;
+        let delayed = a + (b - a) * fraction;
+
+        let output = self.b0 * delayed + self.b1 * self.x1 - self.a1 * self.y1;
+        self.x1 = delayed;
+        self.y1 = output;
+        output
+    }
+}
+
+struct Speaker {
+    gain: f32,
+    // Left and right ears, or nothing for the LFE.
+    ears: Option<(EarPath, EarPath)>,
+}
+
+// Folds a multichannel source down to two channels for headphones.
+struct Headphones {
+    inner: BoxedSource,
+    speakers: Vec<Speaker>,
+    scale: f32,
+    sample_rate: u32,
+    right: Option<i16>,
+}
+
+impl Headphones {
+    fn frame(&mut self) -> Option<(i16, i16)> {
+        let (mut left, mut right) = (0.0, 0.0);
+        for speaker in &mut self.speakers {
+            let sample = self.inner.next()? as f32 * speaker.gain;
+            match &mut speaker.ears {
+                Some((left_ear, right_ear)) => {
+                    left += left_ear.process(sample);
+                    right += right_ear.process(sample);
+                }
+                None => {
+                    left += sample;
+                    right += sample;
+                }
+            }
+        }
+        let clip =
+            |sample: f32| (sample * self.scale).clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        Some((clip(left), clip(right)))
+    }
+}
+
+impl Iterator for Headphones {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if let Some(right) = self.right.take() {
+            return Some(right);
+        }
+        let (left, right) = self.frame()?;
+        self.right = Some(right);
+        Some(left)
+    }
+}
+
+impl Source for Headphones {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+}
+
+// Surround files for headphones, instead of keeping just the front pair.
+// Stereo and unknown layouts are passed through as they are.
+pub fn headphones(source: BoxedSource) -> BoxedSource {
+    let Some(layout) = layout(source.channels()) else {
+        return source;
+    };
+    let sample_rate = source.sample_rate();
+    let speakers = layout
+        .iter()
+        .map(|&(azimuth, gain)| Speaker {
+            gain,
+            ears: azimuth.map(|azimuth| {
+                let incidence = |ear: f32| {
+                    let angle = (azimuth - ear).abs() % 360.0;
+                    angle.min(360.0 - angle).to_radians()
+                };
+                (
+                    EarPath::new(incidence(-90.0), sample_rate),
+                    EarPath::new(incidence(90.0), sample_rate),
+                )
+            }),
+        })
+        .collect::<Vec<_>>();
+    // Each ear hears every speaker, so the sum is brought back down to
+    // about the level of the front pair.
+    let total: f32 = speakers.iter().map(|speaker| speaker.gain).sum();
+    Box::new(Headphones {
+        inner: source,
+        speakers,
+        scale: 2.0 / total,
+        sample_rate,
+        right: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    fn source(channels: u16, samples: Vec<i16>) -> BoxedSource {
+        Box::new(SamplesBuffer::new(channels, 48000, samples))
+    }
+
+    #[test]
+    fn stereo_and_unknown_layouts_pass_through() {
+        for channels in [1, 2, 3] {
+            let samples: Vec<i16> = (0..channels as i16 * 4).collect();
+            let out = headphones(source(channels, samples.clone()));
+            assert_eq!(out.channels(), channels);
+            assert_eq!(out.collect::<Vec<_>>(), samples);
+        }
+    }
+
+    #[test]
+    fn folds_surround_down_to_two_channels() {
+        let frames = 100;
+        let out = headphones(source(6, vec![1000; 6 * frames]));
+        assert_eq!(out.channels(), 2);
+        assert_eq!(out.sample_rate(), 48000);
+        assert_eq!(out.count(), 2 * frames);
+    }
+
+    #[test]
+    fn front_left_reaches_the_left_ear_first() {
+        let frames = 64;
+        let mut samples = vec![0; 6 * frames];
+        samples[0] = i16::MAX;
+        let out: Vec<i16> = headphones(source(6, samples)).collect();
+        let first = |ear: usize| {
+            out.iter()
+                .skip(ear)
+                .step_by(2)
+                .position(|&sample| sample.abs() > 100)
+                .unwrap()
+        };
+        assert!(first(0) < first(1));
+    }
+}
@@ -0,0 +1,192 @@
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::AtomicU64;
+
+use crate::export::{self, Transcode};
+use crate::playlist;
+
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub copied: usize,
+    pub kept: usize,
+    pub pruned: usize,
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+// Lists what sync put on the device, relative to it, so that pruning never
+// touches files that were there already or were added by hand.
+const MANIFEST: &str = ".leek-sync";
+
+// Makes `dest` hold exactly the tracks of `playlists`: missing ones are
+// copied (or transcoded) the same way as an export, tracks no longer on
+// any of the playlists are deleted, and each playlist is written next to
+// them with the device's paths. Only tracks an earlier sync copied are
+// ever deleted. Tracks keep their place under `root`, and one that would
+// land on another's file fails and is left off the device's playlists.
+// `progress` gets each track as it's looked at, with its number and the
+// total.
+pub fn sync(
+    playlists: &[PathBuf],
+    dest: &Path,
+    root: Option<&Path>,
+    transcode: Option<Transcode>,
+    mut progress: impl FnMut(usize, usize, &Path),
+) -> Result<Summary> {
+    let mut lists = Vec::new();
+    for path in playlists {
+        lists.push((path, playlist::load(path)?));
+    }
+
+    let mut seen = HashSet::new();
+    let tracks: Vec<&PathBuf> = lists
+        .iter()
+        .flat_map(|(_, entries)| entries)
+        .filter(|track| seen.insert(*track))
+        .collect();
+
+    let mut summary = Summary::default();
+    let mut wanted = HashSet::new();
+    let mut claimed = HashMap::new();
+    let mut collided = HashSet::new();
+    let copied = AtomicU64::new(0);
+    for (i, track) in tracks.iter().enumerate() {
+        progress(i + 1, tracks.len(), track);
+        let target = export::device_path(track, dest, root, transcode);
+        if let Err(e) = export::claim(&mut claimed, target.clone(), track) {
+            summary.failed.push((track.to_path_buf(), e.to_string()));
+            collided.insert(*track);
+            continue;
+        }
+        match export::send(track, dest, root, transcode, &copied) {
+            Ok(true) => summary.copied += 1,
+            Ok(false) => summary.kept += 1,
+            Err(e) => summary.failed.push((track.to_path_buf(), e.to_string())),
+        }
+        wanted.insert(target);
+    }
+
+    fs::create_dir_all(dest)?;
+    for (path, entries) in &lists {
+        let entries: Vec<PathBuf> = entries
+            .iter()
+            .filter(|track| !collided.contains(track))
+            .map(|track| export::device_path(track, dest, root, transcode))
+            .collect();
+        playlist::save(&dest.join(path.file_name().unwrap_or_default()), &entries)?;
+    }
+
+    let left = prune(dest, &wanted, &mut summary);
+    write_manifest(dest, wanted.iter().chain(&left))?;
+    Ok(summary)
+}
+
+// Deletes files from the last sync's manifest that aren't wanted any more,
+// then any folders that leaves empty. Returns the ones it couldn't delete,
+// to try again next time.
+fn prune(dest: &Path, wanted: &HashSet<PathBuf>, summary: &mut Summary) -> Vec<PathBuf> {
+    let mut left = Vec::new();
+    let Ok(manifest) = fs::read_to_string(dest.join(MANIFEST)) else {
+        return left;
+    };
+    for line in manifest.lines() {
+        let relative = Path::new(line);
+        // Never anything outside the device, whatever the manifest says.
+        if !relative
+            .components()
+            .all(|part| matches!(part, Component::Normal(_)))
+        {
+            continue;
+        }
+        let path = dest.join(relative);
+        if wanted.contains(&path) || !path.is_file() {
+            continue;
+        }
+        if let Err(e) = fs::remove_file(&path) {
+            summary.failed.push((path.clone(), e.to_string()));
+            left.push(path);
+            continue;
+        }
+        summary.pruned += 1;
+        // Only succeeds once a folder is empty.
+        for dir in path.ancestors().skip(1).take_while(|dir| *dir != dest) {
+            if fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+    }
+    left
+}
+
+fn write_manifest<'a>(dest: &Path, synced: impl Iterator<Item = &'a PathBuf>) -> Result<()> {
+    let mut lines: Vec<String> = synced
+        .filter(|path| path.is_file())
+        .filter_map(|path| path.strip_prefix(dest).ok())
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    lines.sort();
+    let mut manifest = lines.join("\n");
+    manifest.push('\n');
+    fs::write(dest.join(MANIFEST), manifest)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prunes_only_what_it_synced() {
+        let root = tempfile::tempdir().unwrap();
+        let (library, device) = (root.path().join("music"), root.path().join("device"));
+        let music = library.join("Album");
+        let run = |list: &PathBuf| {
+            sync(
+                std::slice::from_ref(list),
+                &device,
+                Some(&library),
+                None,
+                |_, _, _| {},
+            )
+            .unwrap()
+        };
+        fs::create_dir_all(&music).unwrap();
+        fs::create_dir_all(root.path().join("other/Album")).unwrap();
+        fs::create_dir_all(device.join("Mine")).unwrap();
+        fs::write(music.join("a.mp3"), "a").unwrap();
+        fs::write(music.join("b.mp3"), "b").unwrap();
+        fs::write(device.join("Mine/own.mp3"), "mine").unwrap();
+        let stray = root.path().join("other/Album/a.mp3");
+        fs::write(&stray, "stray").unwrap();
+        let list = library.join("list.m3u");
+
+        // The track from outside the library would land on Album/a.mp3.
+        let entries = format!("Album/a.mp3\nAlbum/b.mp3\n{}\n", stray.display());
+        fs::write(&list, entries).unwrap();
+        let summary = run(&list);
+        assert_eq!((summary.copied, summary.kept, summary.pruned), (2, 0, 0));
+        assert_eq!(summary.failed.len(), 1);
+        assert_eq!(summary.failed[0].0, stray);
+        assert_eq!(fs::read_to_string(device.join("Album/a.mp3")).unwrap(), "a");
+        let saved = fs::read_to_string(device.join("list.m3u")).unwrap();
+        assert_eq!(
+            saved.lines().filter(|line| line.ends_with("a.mp3")).count(),
+            1
+        );
+
+        // A changed file is copied again, not counted as already there.
+        fs::write(music.join("a.mp3"), "a, remastered").unwrap();
+        fs::write(&list, "Album/a.mp3\n").unwrap();
+        let summary = run(&list);
+        assert_eq!((summary.copied, summary.kept, summary.pruned), (1, 0, 1));
+        assert!(!device.join("Album/b.mp3").exists());
+        assert!(device.join("Mine/own.mp3").exists());
+
+        fs::write(&list, "").unwrap();
+        let summary = run(&list);
+        assert_eq!(summary.pruned, 1);
+        assert!(!device.join("Album").exists());
+        assert!(device.join("Mine/own.mp3").exists());
+    }
+}
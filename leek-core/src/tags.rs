@@ -0,0 +1,470 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, StandardTagKey, StandardVisualKey, Tag, Visual};
+use symphonia::core::probe::{Hint, ProbeResult};
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Tags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub compilation: bool,
+    pub year: Option<u32>,
+    pub track_number: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub duration: Option<Duration>,
+}
+
+impl Tags {
+    pub fn display_album_artist(&self) -> Option<String> {
+        if let Some(album_artist) = &self.album_artist {
+            return Some(album_artist.clone());
+        }
+        if self.compilation {
+            return Some(String::from("Various Artists"));
+        }
+        self.artist.clone()
+    }
+
+    fn apply(&mut self, tag: &Tag) {
+        let value = tag.value.to_string();
+        let value = value.trim();
+        if value.is_empty() {
+            return;
+        }
+
+        match tag.std_key {
+            Some(StandardTagKey::TrackTitle) => self.title = Some(value.to_string()),
+            Some(StandardTagKey::Artist) => self.artist = Some(value.to_string()),
+            Some(StandardTagKey::Album) => self.album = Some(value.to_string()),
+            Some(StandardTagKey::AlbumArtist) => self.album_artist = Some(value.to_string()),
+            Some(StandardTagKey::Compilation) => {
+                self.compilation = matches!(value.to_lowercase().as_str(), "1" | "true" | "yes");
+            }
+            Some(StandardTagKey::Date) | Some(StandardTagKey::ReleaseDate) => {
+                self.year = self.year.or_else(|| leading_number(value));
+            }
+            Some(StandardTagKey::TrackNumber) => self.track_number = leading_number(value),
+            Some(StandardTagKey::DiscNumber) => self.disc_number = leading_number(value),
+            _ => {}
+        }
+    }
+}
+
+// Parses "3/12" as 3 and "1959-08-17" as 1959.
+fn leading_number(value: &str) -> Option<u32> {
+    let digits: String = value.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+fn probe(path: &Path) -> Result<ProbeResult> {
+    let file = File::open(path)?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    Ok(symphonia::default::get_probe().format(
+        &hint,
+        stream,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?)
+}
+
+fn all_tags(probed: &mut ProbeResult) -> Vec<Tag> {
+    let mut tags = Vec::new();
+
+    if let Some(metadata) = probed.metadata.get()
+        && let Some(revision) = metadata.current()
+    {
+        tags.extend_from_slice(revision.tags());
+    }
+
+    if let Some(revision) = probed.format.metadata().current() {
+        tags.extend_from_slice(revision.tags());
+    }
+    tags
+}
+
+fn all_visuals(probed: &mut ProbeResult) -> Vec<Visual> {
+    let mut visuals = Vec::new();
+
+    if let Some(metadata) = probed.metadata.get()
+        && let Some(revision) = metadata.current()
+    {
+        visuals.extend_from_slice(revision.visuals());
+    }
+
+    if let Some(revision) = probed.format.metadata().current() {
+        visuals.extend_from_slice(revision.visuals());
+    }
+    visuals
+}
+
+// Embedded cover art, preferring the front cover over other pictures.
+pub fn read_cover(path: &Path) -> Option<Vec<u8>> {
+    let mut probed = probe(path).ok()?;
+    let visuals = all_visuals(&mut probed);
+    visuals
+        .iter()
+        .find(|visual| visual.usage == Some(StandardVisualKey::FrontCover))
+        .or_else(|| visuals.first())
+        .map(|visual| visual.data.to_vec())
+}
+
+pub fn read(path: &Path) -> Result<Tags> {
+    let mut probed = probe(path)?;
+    Ok(tags_of(&mut probed))
+}
+
+// The tags and loop points the player wants when it opens a file, from
+// one look at it.
+pub fn read_for_playback(path: &Path) -> (Tags, Option<LoopPoints>) {
+    match probe(path) {
+        Ok(mut probed) => (tags_of(&mut probed), loop_points(&mut probed)),
+        Err(_) => (Tags::default(), None),
+    }
+}
+
+fn tags_of(probed: &mut ProbeResult) -> Tags {
+    let mut tags = Tags::default();
+
+    for tag in all_tags(probed) {
+        tags.apply(&tag);
+    }
+
+    if let Some(track) = probed.format.default_track() {
+        let params = &track.codec_params;
+        if let (Some(frames), Some(rate)) = (params.n_frames, params.sample_rate)
+            && rate > 0
+        {
+            tags.duration = Some(Duration::from_secs_f64(frames as f64 / rate as f64));
+        }
+    }
+    tags
+}
+
+// Everything a file says about itself, for inspecting bad metadata.
+#[derive(Clone, Debug, Default)]
+pub struct FileInfo {
+    pub codec: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<usize>,
+    pub bits_per_sample: Option<u32>,
+    pub duration: Option<Duration>,
+    pub pictures: usize,
+    // Every tag under the name the file stores it as.
+    pub tags: Vec<(String, String)>,
+}
+
+pub fn read_info(path: &Path) -> Result<FileInfo> {
+    let mut probed = probe(path)?;
+    let mut info = FileInfo {
+        tags: all_tags(&mut probed)
+            .iter()
+            .map(|tag| (tag.key.clone(), tag.value.to_string()))
+            .collect(),
+        pictures: all_visuals(&mut probed).len(),
+        ..FileInfo::default()
+    };
+
+    if let Some(track) = probed.format.default_track() {
+        let params = &track.codec_params;
+        info.codec = symphonia::default::get_codecs()
+            .get_codec(params.codec)
+            .map(|codec| codec.short_name.to_string());
+        info.sample_rate = params.sample_rate;
+        info.channels = params.channels.map(|channels| channels.count());
+        info.bits_per_sample = params.bits_per_sample;
+        if let (Some(frames), Some(rate)) = (params.n_frames, params.sample_rate)
+            && rate > 0
+        {
+            info.duration = Some(Duration::from_secs_f64(frames as f64 / rate as f64));
+        }
+    }
+    Ok(info)
+}
+
+// The MusicBrainz release a track belongs to, as taggers like Picard
+// record it.
+pub fn release_id(path: &Path) -> Option<String> {
+    let mut probed = probe(path).ok()?;
+    all_tags(&mut probed)
+        .iter()
+        .find(|tag| tag.std_key == Some(StandardTagKey::MusicBrainzAlbumId))
+        .map(|tag| tag.value.to_string().trim().to_string())
+        .filter(|id| !id.is_empty())
+}
+
+// Loop points are given in sample frames at the file's own rate.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct LoopPoints {
+    pub start: u64,
+    pub end: u64,
+    pub sample_rate: u32,
+}
+
+impl LoopPoints {
+    pub fn start_time(&self) -> Duration {
+        Duration::from_secs_f64(self.start as f64 / self.sample_rate as f64)
+    }
+
+    pub fn end_time(&self) -> Duration {
+        Duration::from_secs_f64(self.end as f64 / self.sample_rate as f64)
+    }
+}
+
+// Reads the LOOPSTART/LOOPLENGTH (or LOOPEND) tags used by game music rips.
+fn loop_points(probed: &mut ProbeResult) -> Option<LoopPoints> {
+    let (mut start, mut length, mut end) = (None, None, None);
+    for tag in all_tags(probed) {
+        let key = tag.key.to_uppercase().replace(['_', ' '], "");
+        let value = tag.value.to_string().trim().parse::<u64>().ok();
+        match key.as_str() {
+            "LOOPSTART" => start = value,
+            "LOOPLENGTH" => length = value,
+            "LOOPEND" => end = value,
+            _ => {}
+        }
+    }
+
+    let params = &probed.format.default_track()?.codec_params;
+    let sample_rate = params.sample_rate.filter(|&rate| rate > 0)?;
+    let start = start?;
+    let end = match (length, end) {
+        (Some(length), _) => start + length,
+        (None, Some(end)) => end,
+        (None, None) => params.n_frames?,
+    };
+
+    (end > start).then_some(LoopPoints {
+        start,
+        end,
+        sample_rate,
+    })
+}
+
+// Stores a play count and a 1-5 star rating in the file as FMPS_Playcount
+// and FMPS_Rating (a fraction of five stars), which other players read,
+// and for MP3s in a POPM frame as well. ffmpeg copies the streams into a
+// new file with the extra tags, which then replaces the original.
+pub fn write_stats(path: &Path, plays: u64, rating: Option<u32>) -> Result<()> {
+    let ext = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let partial = path.with_extension(format!("leek-part.{}", ext));
+
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-v", "error", "-y", "-i"])
+        .arg(path)
+        .args(["-map", "0", "-c", "copy", "-map_metadata", "0"])
+        .args(["-metadata", &format!("FMPS_Playcount={}", plays)]);
+    if let Some(rating) = rating {
+        let fraction = rating.min(5) as f32 / 5.0;
+        command.args(["-metadata", &format!("FMPS_Rating={}", fraction)]);
+    }
+    let output = command.arg(&partial).output()?;
+
+    if !output.status.success() {
+        let _ = fs::remove_file(&partial);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("ffmpeg: {}", stderr.trim()));
+    }
+    // ffmpeg has no way to write a POPM frame, so it's added afterwards.
+    if ext.eq_ignore_ascii_case("mp3")
+        && let Err(e) = write_popm(&partial, plays, rating)
+    {
+        let _ = fs::remove_file(&partial);
+        return Err(e);
+    }
+    fs::rename(&partial, path)?;
+    Ok(())
+}
+
+// The play count and star rating `write_stats` stored, from FMPS tags or,
+// for the rating, a POPM frame.
+pub fn read_stats(path: &Path) -> Result<(Option<u64>, Option<u32>)> {
+    let mut probed = probe(path)?;
+    let (mut plays, mut rating) = (None, None);
+    for tag in all_tags(&mut probed) {
+        let key = tag.key.to_uppercase();
+        let value = tag.value.to_string();
+        match key.trim_start_matches("TXXX:") {
+            "FMPS_PLAYCOUNT" => plays = value.trim().parse().ok(),
+            "FMPS_RATING" => {
+                rating = value
+                    .trim()
+                    .parse::<f32>()
+                    .ok()
+                    .map(|fraction| (fraction.clamp(0.0, 1.0) * 5.0).round() as u32)
+                    .filter(|&stars| stars > 0);
+            }
+            key if key.starts_with("POPM:") && rating.is_none() => {
+                rating = value.trim().parse::<u8>().ok().and_then(popm_stars);
+            }
+            _ => {}
+        }
+    }
+    Ok((plays, rating))
+}
+
+// Whose POPM frame this is; each player keeps its own.
+const POPM_EMAIL: &str = "leek";
+// Stars on the 0-255 POPM scale, as most players map them. 0 is unrated.
+const POPM_RATINGS: [u8; 6] = [0, 1, 64, 128, 196, 255];
+
+fn popm_stars(value: u8) -> Option<u32> {
+    match value {
+        0 => None,
+        1..=31 => Some(1),
+        32..=95 => Some(2),
+        96..=159 => Some(3),
+        160..=223 => Some(4),
+        _ => Some(5),
+    }
+}
+
+// Replaces leek's POPM frame in an MP3's ID3v2 tag, or adds one, with the
+// rating and a play counter, adding a tag if the file has none.
+fn write_popm(path: &Path, plays: u64, rating: Option<u32>) -> Result<()> {
+    let data = fs::read(path)?;
+    let (major, body, audio) = if data.starts_with(b"ID3") && data.len() >= 10 {
+        let (major, flags) = (data[3], data[5]);
+        // Unsynchronised tags, extended headers and footers aren't handled.
+        if !(3..=4).contains(&major) || flags & 0xd0 != 0 {
+            return Err(anyhow!("can't add a POPM frame to this ID3v2 tag"));
+        }
+        let end = 10 + syncsafe(&data[6..10]);
+        let body = data
+            .get(10..end)
+            .ok_or_else(|| anyhow!("truncated ID3v2 tag"))?;
+        (major, body, &data[end..])
+    } else {
+        (4, &[][..], &data[..])
+    };
+    let frame_size = |size: usize| match major {
+        4 => to_syncsafe(size),
+        _ => (size as u32).to_be_bytes(),
+    };
+
+    let mut frames = Vec::with_capacity(body.len() + 64);
+    let mut rest = body;
+    // Padding, if any, starts with a zero byte where a frame ID would be.
+    while rest.len() >= 10 && rest[0] != 0 {
+        let size = match major {
+            4 => syncsafe(&rest[4..8]),
+            _ => u32::from_be_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize,
+        };
+        let frame = rest
+            .get(..10 + size)
+            .ok_or_else(|| anyhow!("truncated ID3v2 frame"))?;
+        let ours = frame.starts_with(b"POPM") && frame[10..].starts_with(POPM_EMAIL.as_bytes());
+        if !ours {
+            frames.extend_from_slice(frame);
+        }
+        rest = &rest[10 + size..];
+    }
+
+    let mut popm = POPM_EMAIL.as_bytes().to_vec();
+    popm.push(0);
+    popm.push(POPM_RATINGS[rating.unwrap_or(0).min(5) as usize]);
+    popm.extend((plays.min(u32::MAX as u64) as u32).to_be_bytes());
+    frames.extend_from_slice(b"POPM");
+    frames.extend(frame_size(popm.len()));
+    frames.extend([0, 0]);
+    frames.extend(popm);
+
+    let mut out = Vec::with_capacity(10 + frames.len() + audio.len());
+    out.extend_from_slice(b"ID3");
+    out.extend([major, 0, 0]);
+    out.extend(to_syncsafe(frames.len()));
+    out.extend(frames);
+    out.extend_from_slice(audio);
+    fs::write(path, out)?;
+    Ok(())
+}
+
+// ID3v2 sizes keep the top bit of each byte clear.
+fn syncsafe(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .take(4)
+        .fold(0, |size, &byte| size << 7 | (byte & 0x7f) as usize)
+}
+
+fn to_syncsafe(size: usize) -> [u8; 4] {
+    [21, 14, 7, 0].map(|shift| (size >> shift) as u8 & 0x7f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A few silent MPEG-1 layer III frames at 128 kbps.
+    fn silence() -> Vec<u8> {
+        let mut data = Vec::new();
+        for _ in 0..20 {
+            let start = data.len();
+            data.extend([0xff, 0xfb, 0x90, 0x00]);
+            data.resize(start + 417, 0);
+        }
+        data
+    }
+
+    // Silence behind an ID3v2.4 tag with just a title.
+    fn mp3(title: &str) -> Vec<u8> {
+        let mut frame = b"TIT2".to_vec();
+        frame.extend(to_syncsafe(title.len() + 1));
+        frame.extend([0, 0, 3]);
+        frame.extend(title.as_bytes());
+
+        let mut data = b"ID3\x04\x00\x00".to_vec();
+        data.extend(to_syncsafe(frame.len()));
+        data.extend(frame);
+        data.extend(silence());
+        data
+    }
+
+    #[test]
+    fn popm_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        fs::write(&path, mp3("Song")).unwrap();
+
+        write_popm(&path, 7, Some(4)).unwrap();
+        assert_eq!(read(&path).unwrap().title.as_deref(), Some("Song"));
+        assert_eq!(read_stats(&path).unwrap(), (None, Some(4)));
+
+        // Written again, the old frame is replaced rather than added to.
+        write_popm(&path, 8, Some(2)).unwrap();
+        assert_eq!(read_stats(&path).unwrap(), (None, Some(2)));
+        let data = fs::read(&path).unwrap();
+        assert_eq!(data.windows(4).filter(|w| w == b"POPM").count(), 1);
+        assert!(data.windows(9).any(|w| w == b"leek\x00\x40\x00\x00\x00"));
+        assert_eq!(read(&path).unwrap().title.as_deref(), Some("Song"));
+    }
+
+    #[test]
+    fn popm_adds_a_tag_to_a_bare_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bare.mp3");
+        fs::write(&path, silence()).unwrap();
+
+        write_popm(&path, 1, Some(5)).unwrap();
+        assert_eq!(read_stats(&path).unwrap(), (None, Some(5)));
+        assert!(fs::read(&path).unwrap().ends_with(&silence()));
+    }
+}
@@ -0,0 +1,175 @@
+use anyhow::Result;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use directories::BaseDirs;
+use percent_encoding::{AsciiSet, CONTROLS, percent_decode_str, utf8_percent_encode};
+use std::fs;
+use std::time::Duration;
+use ureq::Agent;
+use ureq::http::Request;
+
+use crate::remote::{Entry, Location, Remote, RemoteFile};
+
+const TIMEOUT: Duration = Duration::from_secs(15);
+const PROPFIND: &str = r#"<?xml version="1.0" encoding="utf-8"?><d:propfind xmlns:d="DAV:"><d:prop><d:resourcetype/></d:prop></d:propfind>"#;
+// Everything but unreserved characters and `/` is escaped in request paths.
+const PATH: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b']')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}')
+    .add(b'^')
+    .add(b'|')
+    .add(b'\\');
+
+// `dav://` is plain HTTP and `davs://` HTTPS. The password for the URL's
+// host and user comes from ~/.netrc, as with curl.
+pub struct WebDav {
+    agent: Agent,
+    base: String,
+    authorization: Option<String>,
+}
+
+impl WebDav {
+    pub fn connect(location: &Location) -> Result<WebDav> {
+        let scheme = if location.scheme == "davs" {
+            "https"
+        } else {
+            "http"
+        };
+        let base = match location.port {
+            Some(port) => format!("{}://{}:{}", scheme, location.host, port),
+            None => format!("{}://{}", scheme, location.host),
+        };
+        let agent = Agent::config_builder()
+            .allow_non_standard_methods(true)
+            .timeout_connect(Some(TIMEOUT))
+            .timeout_recv_response(Some(TIMEOUT))
+            .build()
+            .into();
+        let authorization =
+            netrc_credentials(&location.host, location.user.as_deref()).map(|(user, password)| {
+                format!("Basic {}", BASE64.encode(format!("{}:{}", user, password)))
+            });
+
+        Ok(WebDav {
+            agent,
+            base,
+            authorization,
+        })
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::http::request::Builder {
+        let url = format!("{}{}", self.base, utf8_percent_encode(path, PATH));
+        let request = Request::builder().method(method).uri(url);
+        match &self.authorization {
+            Some(authorization) => request.header("Authorization", authorization),
+            None => request,
+        }
+    }
+}
+
+impl Remote for WebDav {
+    fn list(&self, path: &str) -> Result<Vec<Entry>> {
+        let request = self
+            .request("PROPFIND", path)
+            .header("Depth", "1")
+            .header("Content-Type", "application/xml")
+            .body(PROPFIND)?;
+        let text = self.agent.run(request)?.body_mut().read_to_string()?;
+        parse_listing(&text, path)
+    }
+
+    fn open(&self, path: &str) -> Result<RemoteFile> {
+        let response = self.agent.run(self.request("GET", path).body(())?)?;
+        let body = response.into_body();
+        let size = body.content_length();
+        Ok(RemoteFile {
+            reader: Box::new(body.into_reader()),
+            size,
+        })
+    }
+}
+
+// A PROPFIND multistatus lists the folder itself first, then its children.
+fn parse_listing(text: &str, dir: &str) -> Result<Vec<Entry>> {
+    let document = roxmltree::Document::parse(text)?;
+    let dir = dir.trim_end_matches('/');
+
+    let mut entries = Vec::new();
+    for response in document
+        .descendants()
+        .filter(|node| node.has_tag_name(("DAV:", "response")))
+    {
+        let Some(href) = response
+            .descendants()
+            .find(|node| node.has_tag_name(("DAV:", "href")))
+            .and_then(|node| node.text())
+        else {
+            continue;
+        };
+        let href = percent_decode_str(href).decode_utf8_lossy();
+        // Some servers answer with full URLs rather than paths.
+        let href = match href.split_once("://") {
+            Some((_, rest)) => rest.find('/').map_or("/", |i| &rest[i..]).to_string(),
+            None => href.into_owned(),
+        };
+        let href = href.trim_end_matches('/');
+        if href == dir {
+            continue;
+        }
+        let Some(name) = href.rsplit('/').next().filter(|name| !name.is_empty()) else {
+            continue;
+        };
+
+        let is_dir = response
+            .descendants()
+            .any(|node| node.has_tag_name(("DAV:", "collection")));
+        entries.push(Entry {
+            name: name.to_string(),
+            is_dir,
+        });
+    }
+    Ok(entries)
+}
+
+// Reads `machine <host> login <user> password <secret>` from ~/.netrc,
+// falling back to a `default` entry.
+fn netrc_credentials(host: &str, user: Option<&str>) -> Option<(String, String)> {
+    let path = BaseDirs::new()?.home_dir().join(".netrc");
+    let text = fs::read_to_string(path).ok()?;
+
+    let mut entries: Vec<(Option<String>, Option<String>, Option<String>)> = Vec::new();
+    let mut tokens = text.split_whitespace();
+    while let Some(token) = tokens.next() {
+        match token {
+            "machine" => entries.push((tokens.next().map(String::from), None, None)),
+            "default" => entries.push((None, None, None)),
+            "login" => {
+                if let Some(entry) = entries.last_mut() {
+                    entry.1 = tokens.next().map(String::from);
+                }
+            }
+            "password" => {
+                if let Some(entry) = entries.last_mut() {
+                    entry.2 = tokens.next().map(String::from);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    entries
+        .into_iter()
+        .filter(|(machine, _, _)| machine.as_deref().is_none_or(|machine| machine == host))
+        .filter(|(_, login, _)| user.is_none() || login.as_deref() == user)
+        .find_map(|(_, login, password)| Some((login.or(user.map(String::from))?, password?)))
+}
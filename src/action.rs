@@ -0,0 +1,351 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::app::{App, Focus, View};
+use crate::modal::{Modal, ModalKind};
+
+// Everything the user can ask leek to do, whichever way they asked. Keys are
+// mapped to actions here and `App::dispatch` carries them out, so other
+// controls (media keys, remotes, scripts) only need to produce an action.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Quit,
+
+    TogglePlay,
+    NextTrack,
+    PrevTrack,
+    VolumeUp,
+    VolumeDown,
+    SeekBack,
+    SeekForward,
+    Replay,
+    Duck,
+    Unduck,
+
+    NextItem,
+    PrevItem,
+    EnterSelected,
+    PlayFolder,
+    GoUp,
+    EnqueueSelected,
+    AddToPlaylist,
+    AddToInbox,
+    ExportSelected,
+    LikeCurrent,
+    ScanLibrary,
+
+    CycleFocus,
+    ShowBrowser,
+    ToggleQueueView,
+    ShowPlaylistEditor,
+    ShowInbox,
+    ShowRecentlyAdded,
+    ShowRecentlyPlayed,
+    ShowCharts,
+    ShowArrivals,
+    TrackGainUp,
+    TrackGainDown,
+    Rate(u32),
+    RenameFolder,
+    OpenWith,
+    Shell,
+    OpenCommandLine,
+    OpenPalette,
+    ShowTrackInfo,
+    ShowPlaybackErrors,
+    CyclePalette,
+    ToggleVisual,
+    CancelVisual,
+    ClearFilter,
+
+    OpenSearch,
+    CloseSearch,
+    SearchNext,
+    SearchPrev,
+    SearchEnter,
+    SearchPush(char),
+    SearchPop,
+
+    QueueNext,
+    QueuePrev,
+    QueueEnter,
+    CropQueue,
+    ClearQueue,
+    UndoQueue,
+    OpenQueueSort,
+    RemoveFromQueue,
+    MoveQueueUp,
+    MoveQueueDown,
+    JumpDigit(char),
+    JumpPop,
+    JumpToEntry,
+    CancelJump,
+
+    PlaylistNext,
+    PlaylistPrev,
+    PlaylistMoveUp,
+    PlaylistMoveDown,
+    PlaylistRemove,
+    SavePlaylist,
+    ClosePlaylist,
+    DiscardPlaylist,
+
+    InboxNext,
+    InboxPrev,
+    InboxEnter,
+    InboxRemove,
+
+    ChartsNext,
+    ChartsPrev,
+    ChartsEnter,
+    ChartsNextKind,
+    ChartsNextWindow,
+
+    TracksNext,
+    TracksPrev,
+    TracksEnter,
+
+    ArrivalsNext,
+    ArrivalsPrev,
+    ArrivalsEnter,
+    MoveArrival,
+
+    RenameNext,
+    RenamePrev,
+    ApplyRename,
+
+    StartCuePrompt,
+    OpenCuePicker,
+
+    SubmitModal,
+    EnqueueFromModal,
+    CloseModal,
+    ModalPush(char),
+    ModalPop,
+    ModalNext,
+    ModalPrev,
+    ModalDelete,
+}
+
+// What a key press means in the focused pane. An open dialog takes every
+// key, and otherwise Tab always moves the focus on.
+pub fn from_key(app: &App, key: KeyEvent) -> Option<Action> {
+    if let Some(modal) = &app.modal {
+        return modal_key(modal, key);
+    }
+    if key.code == KeyCode::Tab {
+        return Some(Action::CycleFocus);
+    }
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        match key.code {
+            KeyCode::Char('z') => return Some(Action::Shell),
+            KeyCode::Char('p') => return Some(Action::OpenPalette),
+            _ => {}
+        }
+    }
+    // Alt+1 to Alt+5 rate the playing track, and Alt+0 clears its rating.
+    if key.modifiers.contains(KeyModifiers::ALT)
+        && let KeyCode::Char(c @ '0'..='5') = key.code
+    {
+        return Some(Action::Rate(c as u32 - '0' as u32));
+    }
+    // A queue entry number being typed takes the keys until Enter.
+    if !app.jump_digits.is_empty() {
+        return Some(match key.code {
+            KeyCode::Enter => Action::JumpToEntry,
+            KeyCode::Backspace => Action::JumpPop,
+            KeyCode::Char(c) if c.is_ascii_digit() => Action::JumpDigit(c),
+            _ => Action::CancelJump,
+        });
+    }
+    if app.focus == Focus::Search && app.search.is_some() {
+        return match key.code {
+            KeyCode::Esc => Some(Action::CloseSearch),
+            KeyCode::Enter => Some(Action::SearchEnter),
+            KeyCode::Up => Some(Action::SearchPrev),
+            KeyCode::Down => Some(Action::SearchNext),
+            KeyCode::Backspace => Some(Action::SearchPop),
+            KeyCode::Char(c) => Some(Action::SearchPush(c)),
+            _ => None,
+        };
+    }
+
+    match (app.focus, app.view) {
+        (Focus::Playlist, _) if app.playlist_editor.is_none() => Some(Action::ShowBrowser),
+        (Focus::Playlist, _) => playlist_key(key),
+        (Focus::Queue, _) => general_key(app, key),
+        (_, View::Inbox) => inbox_key(key),
+        (_, View::Charts) => charts_key(key),
+        (_, View::Tracks) => tracks_key(key),
+        (_, View::Rename) => rename_key(key),
+        (_, View::Arrivals) => arrivals_key(key),
+        (_, View::Browser) => general_key(app, key),
+    }
+}
+
+fn modal_key(modal: &Modal, key: KeyEvent) -> Option<Action> {
+    Some(match (&modal.kind, key.code) {
+        (_, KeyCode::Esc) => Action::CloseModal,
+        (_, KeyCode::Enter) => Action::SubmitModal,
+        (ModalKind::Confirm { .. }, KeyCode::Char('y')) => Action::SubmitModal,
+        (ModalKind::Confirm { .. }, KeyCode::Char('n')) => Action::CloseModal,
+        (ModalKind::Input { .. }, KeyCode::Backspace) => Action::ModalPop,
+        (ModalKind::Input { .. }, KeyCode::Char(c)) => Action::ModalPush(c),
+        (ModalKind::Picker { .. }, KeyCode::Up | KeyCode::Char('k')) => Action::ModalPrev,
+        (ModalKind::Picker { .. }, KeyCode::Down | KeyCode::Char('j')) => Action::ModalNext,
+        (ModalKind::Picker { .. }, KeyCode::Char('d') | KeyCode::Delete) => Action::ModalDelete,
+        (ModalKind::Info { .. }, KeyCode::Up | KeyCode::Char('k')) => Action::ModalPrev,
+        (ModalKind::Info { .. }, KeyCode::Down | KeyCode::Char('j')) => Action::ModalNext,
+        (ModalKind::Info { .. }, KeyCode::Char('q' | 't')) => Action::CloseModal,
+        (ModalKind::Palette { .. }, KeyCode::Up) => Action::ModalPrev,
+        (ModalKind::Palette { .. }, KeyCode::Down) => Action::ModalNext,
+        (ModalKind::Palette { .. }, KeyCode::Tab) => Action::EnqueueFromModal,
+        (ModalKind::Palette { .. }, KeyCode::Backspace) => Action::ModalPop,
+        (ModalKind::Palette { .. }, KeyCode::Char(c)) => Action::ModalPush(c),
+        _ => return None,
+    })
+}
+
+fn playlist_key(key: KeyEvent) -> Option<Action> {
+    Some(match key.code {
+        KeyCode::Up | KeyCode::Char('k') => Action::PlaylistPrev,
+        KeyCode::Down | KeyCode::Char('j') => Action::PlaylistNext,
+        KeyCode::Char('K') => Action::PlaylistMoveUp,
+        KeyCode::Char('J') => Action::PlaylistMoveDown,
+        KeyCode::Char('d') | KeyCode::Delete => Action::PlaylistRemove,
+        KeyCode::Char('s') => Action::SavePlaylist,
+        KeyCode::Char('c') => Action::ClosePlaylist,
+        KeyCode::Char('C') => Action::DiscardPlaylist,
+        KeyCode::Backspace => Action::ShowBrowser,
+        KeyCode::Char('v') => Action::ToggleQueueView,
+        KeyCode::Char('p') => Action::ShowPlaylistEditor,
+        KeyCode::Char('a') => Action::AddToPlaylist,
+        KeyCode::Char('m') => Action::StartCuePrompt,
+        KeyCode::Char('\'') => Action::OpenCuePicker,
+        KeyCode::Char(' ') => Action::TogglePlay,
+        _ => return None,
+    })
+}
+
+fn inbox_key(key: KeyEvent) -> Option<Action> {
+    Some(match key.code {
+        KeyCode::Up | KeyCode::Char('k') => Action::InboxPrev,
+        KeyCode::Down | KeyCode::Char('j') => Action::InboxNext,
+        KeyCode::Enter => Action::InboxEnter,
+        KeyCode::Char('d') | KeyCode::Delete => Action::InboxRemove,
+        KeyCode::Char('I') | KeyCode::Backspace | KeyCode::Esc => Action::ShowBrowser,
+        KeyCode::Char('v') => Action::ToggleQueueView,
+        KeyCode::Char(' ') => Action::TogglePlay,
+        _ => return None,
+    })
+}
+
+fn charts_key(key: KeyEvent) -> Option<Action> {
+    Some(match key.code {
+        KeyCode::Up | KeyCode::Char('k') => Action::ChartsPrev,
+        KeyCode::Down | KeyCode::Char('j') => Action::ChartsNext,
+        KeyCode::Enter => Action::ChartsEnter,
+        KeyCode::Char('c') => Action::ChartsNextKind,
+        KeyCode::Char('w') => Action::ChartsNextWindow,
+        KeyCode::Char('C') | KeyCode::Backspace | KeyCode::Esc => Action::ShowBrowser,
+        KeyCode::Char('v') => Action::ToggleQueueView,
+        KeyCode::Char(' ') => Action::TogglePlay,
+        _ => return None,
+    })
+}
+
+fn tracks_key(key: KeyEvent) -> Option<Action> {
+    Some(match key.code {
+        KeyCode::Up | KeyCode::Char('k') => Action::TracksPrev,
+        KeyCode::Down | KeyCode::Char('j') => Action::TracksNext,
+        KeyCode::Enter => Action::TracksEnter,
+        KeyCode::Backspace | KeyCode::Esc => Action::ShowBrowser,
+        KeyCode::Char('v') => Action::ToggleQueueView,
+        KeyCode::Char(' ') => Action::TogglePlay,
+        _ => return None,
+    })
+}
+
+fn arrivals_key(key: KeyEvent) -> Option<Action> {
+    Some(match key.code {
+        KeyCode::Up | KeyCode::Char('k') => Action::ArrivalsPrev,
+        KeyCode::Down | KeyCode::Char('j') => Action::ArrivalsNext,
+        KeyCode::Enter => Action::ArrivalsEnter,
+        KeyCode::Char('m') => Action::MoveArrival,
+        KeyCode::Char('A') | KeyCode::Backspace | KeyCode::Esc => Action::ShowBrowser,
+        KeyCode::Char('v') => Action::ToggleQueueView,
+        KeyCode::Char(' ') => Action::TogglePlay,
+        _ => return None,
+    })
+}
+
+fn rename_key(key: KeyEvent) -> Option<Action> {
+    Some(match key.code {
+        KeyCode::Up | KeyCode::Char('k') => Action::RenamePrev,
+        KeyCode::Down | KeyCode::Char('j') => Action::RenameNext,
+        KeyCode::Enter => Action::ApplyRename,
+        KeyCode::Backspace | KeyCode::Esc => Action::ShowBrowser,
+        KeyCode::Char(' ') => Action::TogglePlay,
+        _ => return None,
+    })
+}
+
+// The file browser and the queue, plus the keys that work everywhere else.
+fn general_key(app: &App, key: KeyEvent) -> Option<Action> {
+    let keys = &app.config.keys;
+    let queue = app.focus == Focus::Queue;
+    Some(match key.code {
+        KeyCode::Esc if app.visual.is_some() => Action::CancelVisual,
+        KeyCode::Esc if app.browser_filter.is_some() && !queue => Action::ClearFilter,
+        KeyCode::Char('q') | KeyCode::Esc => Action::Quit,
+        code if keys.volume_up.contains(&code) => Action::VolumeUp,
+        code if keys.volume_down.contains(&code) => Action::VolumeDown,
+        code if keys.seek_back.contains(&code) => Action::SeekBack,
+        code if keys.seek_forward.contains(&code) => Action::SeekForward,
+        KeyCode::Char(' ') => Action::TogglePlay,
+        KeyCode::Char(c) if c.is_ascii_digit() => Action::JumpDigit(c),
+        KeyCode::Up | KeyCode::Char('k') if queue => Action::QueuePrev,
+        KeyCode::Down | KeyCode::Char('j') if queue => Action::QueueNext,
+        KeyCode::Enter if queue => Action::QueueEnter,
+        KeyCode::Char('x') if queue => Action::CropQueue,
+        KeyCode::Char('X') if queue => Action::ClearQueue,
+        KeyCode::Char('z') if queue => Action::UndoQueue,
+        KeyCode::Char('s') if queue => Action::OpenQueueSort,
+        KeyCode::Char('d') | KeyCode::Delete if queue => Action::RemoveFromQueue,
+        KeyCode::Char('K') if queue => Action::MoveQueueUp,
+        KeyCode::Char('J') if queue => Action::MoveQueueDown,
+        KeyCode::Char('V') => Action::ToggleVisual,
+        KeyCode::Up | KeyCode::Char('k') => Action::PrevItem,
+        KeyCode::Down | KeyCode::Char('j') => Action::NextItem,
+        KeyCode::Enter => Action::EnterSelected,
+        KeyCode::Char('f') => Action::PlayFolder,
+        KeyCode::Backspace => Action::GoUp,
+        KeyCode::Left => Action::PrevTrack,
+        KeyCode::Right => Action::NextTrack,
+        KeyCode::Char('u') => Action::ScanLibrary,
+        KeyCode::Char('/') => Action::OpenSearch,
+        KeyCode::Char('v') => Action::ToggleQueueView,
+        KeyCode::Char('p') => Action::ShowPlaylistEditor,
+        KeyCode::Char('a') => Action::AddToPlaylist,
+        KeyCode::Char('e') => Action::EnqueueSelected,
+        KeyCode::Char('L') => Action::LikeCurrent,
+        KeyCode::Char('E') => Action::ExportSelected,
+        KeyCode::Char('i') => Action::AddToInbox,
+        KeyCode::Char('I') => Action::ShowInbox,
+        KeyCode::Char('R') => Action::ShowRecentlyAdded,
+        KeyCode::Char('H') => Action::ShowRecentlyPlayed,
+        KeyCode::Char('C') => Action::ShowCharts,
+        KeyCode::Char('A') => Action::ShowArrivals,
+        KeyCode::Char('N') => Action::RenameFolder,
+        KeyCode::Char('o') => Action::OpenWith,
+        KeyCode::Char(':') => Action::OpenCommandLine,
+        KeyCode::Char('t') => Action::ShowTrackInfo,
+        KeyCode::Char('F') => Action::ShowPlaybackErrors,
+        KeyCode::Char('T') => Action::CyclePalette,
+        KeyCode::Char('m') => Action::StartCuePrompt,
+        KeyCode::Char('\'') => Action::OpenCuePicker,
+        KeyCode::Char('r') => Action::Replay,
+        KeyCode::Char(']') => Action::TrackGainUp,
+        KeyCode::Char('[') => Action::TrackGainDown,
+        _ => return None,
+    })
+}
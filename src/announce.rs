@@ -0,0 +1,18 @@
+use crossterm::execute;
+use crossterm::terminal::SetTitle;
+use std::io::{self, Write};
+
+use crate::config::Announce;
+
+// Screen readers pick up window title changes and terminal notifications,
+// while redraws inside the alternate screen usually go unannounced.
+pub fn announce(out: &mut impl Write, mode: Announce, text: &str) -> io::Result<()> {
+    match mode {
+        Announce::Off => Ok(()),
+        Announce::Title => execute!(out, SetTitle(text)),
+        Announce::Notify => {
+            write!(out, "\x1b]9;{}\x07", text)?;
+            out.flush()
+        }
+    }
+}
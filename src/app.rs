@@ -1,16 +1,76 @@
 use anyhow::Result;
+use crossterm::event::KeyEvent;
 use directories::UserDirs;
-use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+use rand::seq::SliceRandom;
+use ratatui::layout::{Position, Rect};
+use serde_json::{Value, json};
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
-use std::fs::{self, File};
-use std::io::BufReader;
+use std::fs;
+use std::ops::RangeInclusive;
 use std::path::{Path, PathBuf};
-use std::time::Duration;
+use std::sync::Arc;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use leek_core::arrivals::{self, Arrival};
+#[cfg(test)]
+use leek_core::backend::MockBackend;
+use leek_core::backend::{self, RodioBackend};
+use leek_core::charts::{self, ChartEntry, ChartKind, Window};
+use leek_core::clock;
+use leek_core::covers::CoverService;
+use leek_core::db::Database;
+use leek_core::export::Export;
+use leek_core::folder::{self, FolderSummary, PendingSummary};
+use leek_core::groups::{self, Group, GroupedList, Row};
+use leek_core::library::{self, Library, Scan, Track};
+use leek_core::log;
+use leek_core::player::{Playback, Player};
+use leek_core::playlist::{self, PlaylistEditor};
+use leek_core::query::{Query, Stats};
+use leek_core::remote;
+use leek_core::rename::{self, Applied, Outcome, Rename};
+use leek_core::session::Session;
+use leek_core::tags::{self, Tags};
+
+use crate::action::Action;
+use crate::command_line::{self, CommandLine};
+use crate::config::{Alarm, Announce, Config, Output, Palette, Setup};
+use crate::events::{DragEvent, Event};
+use crate::filter::NameFilter;
+use crate::format::{self, format_duration};
+use crate::i18n::{self, tr};
+use crate::ipc::{self, SharedStatus};
+use crate::journal;
+use crate::media_keys::{MediaKey, MediaKeys};
+use crate::metrics::{self, Metrics};
+use crate::modal::{Modal, ModalKind, PickerItem, Purpose};
+use crate::suspend::External;
+
+const STATUS_TIMEOUT_SECONDS: u64 = 5;
+const SEARCH_LIMIT: usize = 500;
+const PALETTE_LIMIT: usize = 100;
+const RECENTLY_PLAYED_LIMIT: usize = 200;
+const ERROR_LOG_LIMIT: usize = 500;
+const POSITION_SAVE_SECONDS: u64 = 5;
+const MOUNT_CHECK_SECONDS: u64 = 2;
+const ARRIVALS_CHECK_SECONDS: u64 = 10;
+const TRACK_GAIN_STEP_DB: f32 = 1.0;
+const JACK_DEVICE: &str = "jack";
+const SNAPCAST_DEVICE: &str = "snapcast";
+const SILENT_DEVICE: &str = "silent";
+// Past this far into a track, previous restarts it instead.
+const RESTART_THRESHOLD: Duration = Duration::from_secs(5);
+const AUDIOBOOK_FINISHED: Duration = Duration::from_secs(10);
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum FileType {
     Directory,
     AudioFile,
+    Playlist,
     Other,
 }
 
@@ -21,61 +81,479 @@ pub struct BrowserItem {
     pub file_type: FileType,
 }
 
+#[derive(Clone, Debug, Default)]
+pub struct Search {
+    pub input: String,
+    pub results: Vec<usize>,
+    pub list: GroupedList,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct QueueDrag {
+    pub from: usize,
+    pub hover: u16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QueueOrder {
+    Path,
+    Title,
+    Artist,
+    Album,
+    Duration,
+    Random,
+}
+
+impl QueueOrder {
+    pub const ALL: [QueueOrder; 6] = [
+        QueueOrder::Path,
+        QueueOrder::Title,
+        QueueOrder::Artist,
+        QueueOrder::Album,
+        QueueOrder::Duration,
+        QueueOrder::Random,
+    ];
+
+    pub fn label(self) -> String {
+        match self {
+            QueueOrder::Path => tr!("queue.order_path"),
+            QueueOrder::Title => tr!("queue.order_title"),
+            QueueOrder::Artist => tr!("queue.order_artist"),
+            QueueOrder::Album => tr!("queue.order_album"),
+            QueueOrder::Duration => tr!("queue.order_duration"),
+            QueueOrder::Random => tr!("queue.order_random"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum View {
+    Browser,
+    Inbox,
+    Tracks,
+    Charts,
+    Rename,
+    Arrivals,
+}
+
+// The pane that gets key presses. The browser pane shows whichever `View`
+// was picked last.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Focus {
+    Browser,
+    Queue,
+    Playlist,
+    Search,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Charts {
+    pub kind: ChartKind,
+    pub window: Window,
+    pub entries: Vec<ChartEntry>,
+    pub index: usize,
+}
+
+// Finished downloads waiting to be listened to or moved into the library.
+#[derive(Clone, Debug, Default)]
+pub struct NewArrivals {
+    pub list: Vec<Arrival>,
+    pub index: usize,
+    ticks: u64,
+    checking: bool,
+    // Nothing is announced as new on the first check.
+    checked: bool,
+}
+
+// The track change overlay.
+#[derive(Clone, Debug)]
+pub struct Osd {
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    // The cover comes from `App::covers` once it has been read.
+    pub path: PathBuf,
+}
+
+// A file that wouldn't play this session.
+#[derive(Clone, Debug)]
+pub struct PlaybackError {
+    pub path: PathBuf,
+    pub error: String,
+    // Seconds since the epoch.
+    pub at: u64,
+}
+
+// A read-only list of library tracks, such as the recently added ones.
+#[derive(Clone, Debug, Default)]
+pub struct TrackList {
+    pub title: String,
+    pub paths: Vec<PathBuf>,
+    pub list: GroupedList,
+}
+
+// New names worked out for a folder, shown before anything is moved.
+#[derive(Clone, Debug)]
+pub struct RenamePreview {
+    pub dir: PathBuf,
+    pub renames: Vec<Rename>,
+    pub index: usize,
+}
+
+impl RenamePreview {
+    pub fn count(&self, outcome: Outcome) -> usize {
+        self.renames.iter().filter(|r| r.outcome == outcome).count()
+    }
+}
+
+// Folder reads finished on a worker thread, so a slow network drive
+// doesn't hold up drawing.
+pub enum Loaded {
+    Directory {
+        path: PathBuf,
+        items: Result<Vec<BrowserItem>>,
+    },
+    Folder {
+        dir: PathBuf,
+        tracks: Result<Vec<PathBuf>>,
+    },
+    Renames {
+        dir: PathBuf,
+        renames: Vec<Rename>,
+    },
+    Renamed {
+        dir: PathBuf,
+        applied: Applied,
+        elsewhere: bool,
+    },
+    Arrivals(Vec<Arrival>),
+    Selection {
+        target: SelectionTarget,
+        items: Vec<(FileType, Vec<PathBuf>)>,
+    },
+    Mounts {
+        track: Option<(PathBuf, bool)>,
+        dir: PathBuf,
+        dir_exists: bool,
+    },
+    OutputDevice(Option<String>),
+}
+
+// What the tracks under a browser selection are for, once its folders have
+// been listed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SelectionTarget {
+    Queue,
+    Export,
+    Playlist,
+    Inbox,
+}
+
 pub struct App {
     pub current_directory: PathBuf,
     pub browser_items: Vec<BrowserItem>,
     pub browser_index: usize,
+    // A `:filter` narrowing the listing, with everything it hides.
+    pub browser_filter: Option<NameFilter>,
+    browser_unfiltered: Vec<BrowserItem>,
+    // The folder being read for the browser, and the row to select in it.
+    pub loading: Option<(PathBuf, usize)>,
+    loading_folder: Option<PathBuf>,
+    events: Sender<Event<KeyEvent>>,
+    pub folder_summaries: HashMap<PathBuf, FolderSummary>,
+    pending_summary: Option<PendingSummary>,
 
-    pub queue: Vec<PathBuf>,
-    pub queue_index: usize,
-    pub volume: u8,
-    pub is_playing: bool,
-
-    pub elapsed: Duration,
-    pub duration: Option<Duration>,
+    pub player: Player,
     pub tick_counter: u64,
+    autosave_ticks: u64,
+    mount_ticks: u64,
+    output_device: Option<String>,
+    alarm_rang: bool,
+    idle_ticks: u64,
+    device_volumes: BTreeMap<String, u8>,
+    saved_session: Option<Session>,
+    lost_track: Option<PathBuf>,
+    directory_lost: bool,
+    checking_mounts: bool,
+    checking_device: bool,
+
+    pub status: Option<String>,
+    status_ticks: u64,
 
-    _stream: OutputStream,
-    stream_handle: OutputStreamHandle,
-    sink: Sink,
+    pub config: Config,
+    config_modified: Option<SystemTime>,
+
+    pub library: Library,
+    scan: Option<Scan>,
+    export: Option<Export>,
+    db: Option<Database>,
+    pub search: Option<Search>,
+    pub view: View,
+    pub focus: Focus,
+    pub queue_list: GroupedList,
+    pub playlist_editor: Option<PlaylistEditor>,
+    pub queue_drag: Option<QueueDrag>,
+    pub inbox: Vec<PathBuf>,
+    pub inbox_list: GroupedList,
+    pub track_list: TrackList,
+    pub charts: Charts,
+    pub arrivals: NewArrivals,
+    pub rename: Option<RenamePreview>,
+    queue_undo: Option<(Vec<PathBuf>, usize)>,
+    pub jump_digits: String,
+    // Where a visual selection started, as a browser item or queue entry,
+    // and the pane it belongs to.
+    pub visual: Option<(Focus, usize)>,
+    // Where the queue's rows were drawn, and the first one shown.
+    pub queue_layout: Cell<(Rect, usize)>,
+    pub modal: Option<Modal>,
+    pub announcement: Option<String>,
+    // A program to hand the terminal to, run by the main loop.
+    pub external: Option<External>,
+    pub osd: Option<Osd>,
+    pub playback_errors: Vec<PlaybackError>,
+    pub covers: CoverService,
+    pub cue_marks: Vec<Duration>,
+    osd_ticks: u64,
+    media_keys: Option<MediaKeys>,
+    // For clients of the control socket.
+    shared_status: SharedStatus,
+    metrics: Arc<Metrics>,
+    // The track the journal last saw start, to tell skips from finishes.
+    journal_track: Option<PathBuf>,
+    // Started with `--minimal`, which outlasts config reloads.
+    minimal_flag: bool,
+    pub quit: bool,
 }
 
 impl App {
-    pub fn new() -> Result<Self> {
-        let (_stream, stream_handle) = OutputStream::try_default()?;
-        let sink = Sink::try_new(&stream_handle)?;
-
+    pub fn new(events: Sender<Event<KeyEvent>>) -> Result<Self> {
         let args: Vec<String> = env::args().collect();
-        let start_dir = Self::determine_start_dir(&args);
+        let minimal = args.iter().any(|arg| arg == "--minimal");
+        let (mut config, config_error) = match Config::load() {
+            Ok(config) => (config, None),
+            Err(e) => (Config::default(), Some(e)),
+        };
+        let start_dir = Self::determine_start_dir(&args, config.music_dir.as_deref());
+        if minimal || config.minimal {
+            config.make_minimal();
+        }
+
+        let (mut player, output_error) = match config.output {
+            Output::Default => (Player::new()?, None),
+            Output::Jack => match RodioBackend::jack("leek") {
+                Ok(backend) => (Player::with_backend(Box::new(backend)), None),
+                Err(e) => (Player::new()?, Some(e)),
+            },
+            Output::Snapcast => match RodioBackend::snapcast(&config.snapcast_source) {
+                Ok(backend) => (Player::with_backend(Box::new(backend)), None),
+                Err(e) => (Player::new()?, Some(e)),
+            },
+            Output::Silent => (
+                Player::with_backend(Box::new(RodioBackend::silent()?)),
+                None,
+            ),
+        };
+        player.set_max_volume(config.max_volume);
+        player.gap = config.gap();
+        player.crossfade = config.crossfade();
+        player.crossfade_albums = config.crossfade_albums;
+        player.sandbox = config.sandbox();
+        player.headphones = config.headphone_surround;
+        let output_device = match config.output {
+            Output::Jack if output_error.is_none() => Some(String::from(JACK_DEVICE)),
+            Output::Snapcast if output_error.is_none() => Some(String::from(SNAPCAST_DEVICE)),
+            Output::Silent => Some(String::from(SILENT_DEVICE)),
+            _ => backend::output_device(),
+        };
+
+        let mut app = Self::build(events, start_dir.clone(), config, player, output_device);
+        app.minimal_flag = minimal;
+
+        if let Some(e) = &config_error {
+            app.set_status(tr!("status.config_error", error = e));
+        } else if let Err(e) = i18n::init(&app.config.locale) {
+            app.set_status(tr!("status.locale_error", error = e));
+        }
+        if let Some(e) = output_error {
+            log::warn(&format!("Output unavailable: {}", e));
+            app.set_status(tr!("status.output_error", error = e));
+        }
+
+        app.update_media_keys();
+        if let Err(e) = ipc::listen(app.events.clone(), app.shared_status.clone()) {
+            log::warn(&format!("Control socket unavailable: {}", e));
+        }
+        if let Some(address) = &app.config.metrics_address
+            && let Err(e) = metrics::serve(
+                address,
+                app.config.metrics_token.clone(),
+                app.metrics.clone(),
+            )
+        {
+            log::warn(&format!("Metrics endpoint unavailable: {}", e));
+            app.set_status(tr!("status.metrics_error", error = e));
+        }
+
+        match Database::open() {
+            Ok(db) => {
+                app.library.set_tracks(db.load_tracks().unwrap_or_default());
+                app.inbox = db.inbox().unwrap_or_default();
+                app.db = Some(db);
+            }
+            Err(e) => app.set_status(tr!("status.database_error", error = e)),
+        }
+
+        match app.db.as_ref().map(Database::load_session) {
+            Some(Ok(Some(session))) => app.restore_session(session),
+            Some(Ok(None)) | None => {}
+            Some(Err(e)) => log::warn(&format!("Could not load session: {}", e)),
+        }
+        app.load_directory(&start_dir);
+        if config_error.is_none() && Config::missing() {
+            app.start_setup();
+        }
+
+        Ok(app)
+    }
 
-        let mut app = Self {
+    // The parts of startup with no side effects outside the app.
+    fn build(
+        events: Sender<Event<KeyEvent>>,
+        start_dir: PathBuf,
+        config: Config,
+        player: Player,
+        output_device: Option<String>,
+    ) -> Self {
+        let mut covers = CoverService::start();
+        covers.fetch = config.fetch_covers;
+        Self {
             current_directory: start_dir.clone(),
             browser_items: Vec::new(),
             browser_index: 0,
-            queue: Vec::new(),
-            queue_index: 0,
-            volume: 50,
-            is_playing: false,
-            elapsed: Duration::ZERO,
-            duration: None,
+            browser_filter: None,
+            browser_unfiltered: Vec::new(),
+            loading: None,
+            loading_folder: None,
+            events,
+            folder_summaries: HashMap::new(),
+            pending_summary: None,
+            player,
             tick_counter: 0,
-            _stream,
-            stream_handle,
-            sink,
-        };
+            autosave_ticks: 0,
+            mount_ticks: 0,
+            output_device,
+            alarm_rang: false,
+            idle_ticks: 0,
+            device_volumes: BTreeMap::new(),
+            saved_session: None,
+            lost_track: None,
+            directory_lost: false,
+            checking_mounts: false,
+            checking_device: false,
+            status: None,
+            status_ticks: 0,
+            config,
+            config_modified: Config::modified(),
+            library: Library::new(start_dir.clone()),
+            scan: None,
+            export: None,
+            db: None,
+            search: None,
+            view: View::Browser,
+            focus: Focus::Browser,
+            queue_list: GroupedList::default(),
+            playlist_editor: None,
+            queue_drag: None,
+            inbox: Vec::new(),
+            inbox_list: GroupedList::default(),
+            track_list: TrackList::default(),
+            charts: Charts::default(),
+            arrivals: NewArrivals::default(),
+            rename: None,
+            queue_undo: None,
+            jump_digits: String::new(),
+            visual: None,
+            queue_layout: Cell::new((Rect::default(), 0)),
+            modal: None,
+            announcement: None,
+            external: None,
+            osd: None,
+            playback_errors: Vec::new(),
+            covers,
+            cue_marks: Vec::new(),
+            osd_ticks: 0,
+            media_keys: None,
+            shared_status: SharedStatus::default(),
+            metrics: Arc::new(Metrics::new()),
+            journal_track: None,
+            minimal_flag: false,
+            quit: false,
+        }
+    }
 
-        app.load_directory(&start_dir);
-        app.sink.set_volume(app.volume as f32 / 100.0);
+    // An app on the mock backend with the default config, and no database,
+    // session, control socket or media keys, for the test harness.
+    #[cfg(test)]
+    pub fn for_test(events: Sender<Event<KeyEvent>>, dir: &Path, backend: MockBackend) -> Self {
+        let player = Player::with_backend(Box::new(backend));
+        let mut app = Self::build(events, dir.to_path_buf(), Config::default(), player, None);
+        app.load_directory(dir);
+        app
+    }
 
-        Ok(app)
+    // Brings back the last queue and volume. If leek was killed while
+    // playing, the track picks up where the last checkpoint left it.
+    fn restore_session(&mut self, session: Session) {
+        self.device_volumes = session.device_volumes.clone();
+        let volume = self.device_volume().unwrap_or(session.volume);
+        self.player.set_volume(volume);
+        if session.queue.is_empty() {
+            return;
+        }
+        if !session.playing {
+            self.player.restore_queue(session.queue, session.index);
+            return;
+        }
+
+        let position = session.position();
+        let playback = self.player.set_queue(session.queue, session.index);
+        self.after_playback(playback);
+        if !self.player.is_idle() && !position.is_zero() {
+            self.seek_to(position);
+            self.set_status(tr!("status.resumed", position = format_position(position)));
+        }
     }
 
-    fn determine_start_dir(args: &[String]) -> PathBuf {
+    // Skips the write when nothing changed since the last checkpoint.
+    pub fn save_session(&mut self, clean_exit: bool) {
+        self.remember_device_volume();
+        let mut session = Session::capture(&self.player);
+        session.device_volumes = self.device_volumes.clone();
+        if clean_exit {
+            session.playing = false;
+        }
+        if self.saved_session.as_ref() == Some(&session) {
+            return;
+        }
+        let Some(db) = &mut self.db else {
+            return;
+        };
+        match db.save_session(&session) {
+            Ok(()) => self.saved_session = Some(session),
+            Err(e) => log::warn(&format!("Could not save session: {}", e)),
+        }
+    }
+
+    fn determine_start_dir(args: &[String], music_dir: Option<&Path>) -> PathBuf {
         if args.contains(&String::from("-steins")) {
             return PathBuf::from(r"D:\Soulseek\share");
         }
-        if args.len() > 1 {
-            return PathBuf::from(&args[1]);
+        if let Some(dir) = args.iter().skip(1).find(|arg| !arg.starts_with("--")) {
+            return PathBuf::from(dir);
+        }
+        if let Some(dir) = music_dir {
+            return dir.to_path_buf();
         }
         UserDirs::new()
             .and_then(|ud| ud.audio_dir().map(|p| p.to_path_buf()))
@@ -83,211 +561,2758 @@ impl App {
             .unwrap_or_else(|| PathBuf::from("."))
     }
 
-    fn is_audio_file(path: &Path) -> bool {
-        path.extension()
-            .and_then(|ext| ext.to_str())
-            .map(|ext| matches!(ext.to_lowercase().as_str(), "mp3" | "wav" | "flac" | "ogg"))
-            .unwrap_or(false)
-    }
-
     pub fn load_directory(&mut self, path: &Path) {
-        if !path.is_dir() {
-            return;
-        }
-
-        self.browser_items.clear();
-        self.browser_index = 0;
-        self.current_directory = path.to_path_buf();
-
-        if let Ok(entries) = fs::read_dir(path) {
-            let mut items: Vec<BrowserItem> = entries
-                .flatten()
-                .map(|entry| {
-                    let path = entry.path();
-                    let name = path
-                        .file_name()
-                        .unwrap_or_default()
-                        .to_string_lossy()
-                        .into_owned();
-
-                    let file_type = if path.is_dir() {
-                        FileType::Directory
-                    } else if Self::is_audio_file(&path) {
-                        FileType::AudioFile
-                    } else {
-                        FileType::Other
-                    };
+        self.read_directory(path, 0);
+    }
 
-                    BrowserItem {
-                        path,
-                        name,
-                        file_type,
-                    }
-                })
-                .filter(|item| item.file_type != FileType::Other)
-                .collect();
+    // Reads the folder again, keeping the selection where it was.
+    fn reload_directory(&mut self) {
+        self.read_directory(&self.current_directory.clone(), self.browser_index);
+    }
 
-            items.sort_by(|a, b| {
-                let a_is_dir = a.file_type == FileType::Directory;
-                let b_is_dir = b.file_type == FileType::Directory;
+    fn read_directory(&mut self, path: &Path, index: usize) {
+        self.loading = Some((path.to_path_buf(), index));
+        let events = self.events.clone();
+        let path = path.to_path_buf();
+        thread::spawn(move || {
+            let items = if remote::is_remote(&path) {
+                Self::remote_items(&path)
+            } else {
+                Self::local_items(&path)
+            };
+            let items = items.map(|mut items| {
+                items.sort_by(|a, b| {
+                    let a_is_dir = a.file_type == FileType::Directory;
+                    let b_is_dir = b.file_type == FileType::Directory;
 
-                b_is_dir
-                    .cmp(&a_is_dir)
-                    .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+                    b_is_dir
+                        .cmp(&a_is_dir)
+                        .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+                });
+                items
             });
+            let _ = events.send(Event::Loaded(Loaded::Directory { path, items }));
+        });
+    }
 
-            self.browser_items = items;
+    // Results for anything but the latest request are dropped.
+    pub fn on_loaded(&mut self, loaded: Loaded) {
+        match loaded {
+            Loaded::Directory { path, items } => {
+                let Some((loading, index)) = self.loading.take_if(|(loading, _)| *loading == path)
+                else {
+                    return;
+                };
+                match items {
+                    Ok(items) => {
+                        self.cancel_visual();
+                        // A filter lasts while the folder is reread, not
+                        // past leaving it.
+                        if loading != self.current_directory {
+                            self.browser_filter = None;
+                        }
+                        let selected = self
+                            .browser_filter
+                            .as_ref()
+                            .and_then(|_| self.browser_items.get(index))
+                            .map(|item| item.path.clone());
+                        self.browser_index = index;
+                        self.browser_unfiltered = items;
+                        self.current_directory = loading;
+                        self.apply_browser_filter(selected);
+                    }
+                    Err(e) => {
+                        self.set_status(tr!(
+                            "status.cannot_open",
+                            path = path.display(),
+                            error = e
+                        ));
+                    }
+                }
+            }
+            Loaded::Folder { dir, tracks } => {
+                if self
+                    .loading_folder
+                    .take_if(|loading| *loading == dir)
+                    .is_none()
+                {
+                    return;
+                }
+                match tracks {
+                    Ok(mut tracks) if !tracks.is_empty() => {
+                        self.library
+                            .sort_album_order(&mut tracks, self.player.sandbox.is_none());
+                        let playback = self.player.set_queue(tracks, 0);
+                        self.after_playback(playback);
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        self.set_status(tr!("status.cannot_open", path = dir.display(), error = e));
+                    }
+                }
+            }
+            Loaded::Renames { dir, renames } => {
+                if renames.is_empty() {
+                    self.set_status(tr!("rename.no_files"));
+                    return;
+                }
+                self.rename = Some(RenamePreview {
+                    dir,
+                    renames,
+                    index: 0,
+                });
+                self.view = View::Rename;
+                self.focus = Focus::Browser;
+            }
+            Loaded::Renamed {
+                dir,
+                applied,
+                elsewhere,
+            } => self.on_renamed(dir, applied, elsewhere),
+            Loaded::Arrivals(list) => {
+                let arrivals = &mut self.arrivals;
+                let new: Vec<&Arrival> = list
+                    .iter()
+                    .filter(|arrival| !arrivals.list.iter().any(|old| old.dir == arrival.dir))
+                    .collect();
+                if let Some(first) = new.first()
+                    && arrivals.checked
+                {
+                    let name = first.dir.file_name().unwrap_or_default().to_string_lossy();
+                    self.status = Some(tr!("arrivals.new", name = name, count = new.len()));
+                    self.status_ticks = 0;
+                }
+                arrivals.list = list;
+                arrivals.index = arrivals.index.min(arrivals.list.len().saturating_sub(1));
+                arrivals.checking = false;
+                arrivals.checked = true;
+            }
+            Loaded::Selection { target, items } => {
+                let paths: Vec<PathBuf> = items
+                    .into_iter()
+                    .flat_map(|(file_type, mut tracks)| {
+                        if file_type == FileType::Directory {
+                            self.library
+                                .sort_album_order(&mut tracks, self.player.sandbox.is_none());
+                        }
+                        tracks
+                    })
+                    .collect();
+                match target {
+                    SelectionTarget::Queue => self.enqueue_tracks(paths),
+                    SelectionTarget::Export => self.export_tracks(paths),
+                    SelectionTarget::Playlist => self.add_to_playlist(paths),
+                    SelectionTarget::Inbox => self.add_to_inbox(paths),
+                }
+            }
+            Loaded::Mounts {
+                track,
+                dir,
+                dir_exists,
+            } => self.on_mounts(track, dir, dir_exists),
+            Loaded::OutputDevice(device) => self.on_output_device(device),
         }
     }
 
-    pub fn on_tick(&mut self) {
-        if self.is_playing {
-            self.tick_counter += 1;
-            self.elapsed += Duration::from_millis(250);
+    fn local_items(path: &Path) -> Result<Vec<BrowserItem>> {
+        Ok(fs::read_dir(path)?
+            .flatten()
+            .map(|entry| {
+                let path = entry.path();
+                let name = path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned();
 
-            if self.sink.empty() && !self.queue.is_empty() && self.duration.is_some() {
-                self.next_track();
-            }
+                let file_type = if path.is_dir() {
+                    FileType::Directory
+                } else if library::is_audio_file(&path) {
+                    FileType::AudioFile
+                } else if playlist::is_playlist_file(&path) {
+                    FileType::Playlist
+                } else {
+                    FileType::Other
+                };
+
+                BrowserItem {
+                    path,
+                    name,
+                    file_type,
+                }
+            })
+            .filter(|item| item.file_type != FileType::Other)
+            .collect())
+    }
+
+    // Remote folders only list their subfolders and audio files.
+    fn remote_items(path: &Path) -> Result<Vec<BrowserItem>> {
+        Ok(remote::list(path)?
+            .into_iter()
+            .map(|entry| {
+                let path = path.join(&entry.name);
+                let file_type = if entry.is_dir {
+                    FileType::Directory
+                } else if library::is_audio_file(&path) {
+                    FileType::AudioFile
+                } else {
+                    FileType::Other
+                };
+                BrowserItem {
+                    path,
+                    name: entry.name,
+                    file_type,
+                }
+            })
+            .filter(|item| item.file_type != FileType::Other)
+            .collect())
+    }
+
+    pub fn selected_directory(&self) -> Option<&Path> {
+        let selected = self.browser_items.get(self.browser_index)?;
+        (selected.file_type == FileType::Directory).then_some(selected.path.as_path())
+    }
+
+    // Starts reading the summary for the selected folder, unless a current
+    // one is cached or already on its way.
+    fn update_folder_summary(&mut self) {
+        let dir = self
+            .selected_directory()
+            .filter(|dir| !remote::is_remote(dir))
+            .map(Path::to_path_buf);
+        let Some(dir) = dir else {
+            self.pending_summary = None;
+            return;
+        };
+        if let Some(summary) = self.folder_summaries.get(&dir)
+            && summary.is_current(&dir)
+        {
+            self.pending_summary = None;
+            return;
+        }
+        if self.pending_summary.as_ref().map(|p| &p.dir) == Some(&dir) {
+            return;
         }
+        self.pending_summary = Some(PendingSummary::spawn(dir));
     }
 
-    pub fn enter_selected(&mut self) {
-        if self.browser_items.is_empty() {
+    fn poll_folder_summary(&mut self) {
+        let Some(pending) = &self.pending_summary else {
             return;
+        };
+        if let Some(summary) = pending.poll() {
+            let pending = self.pending_summary.take().unwrap();
+            self.folder_summaries.insert(pending.dir, summary);
         }
+    }
 
-        let selected = &self.browser_items[self.browser_index].clone();
+    pub fn set_status(&mut self, message: impl Into<String>) {
+        self.status = Some(message.into());
+        self.status_ticks = 0;
+    }
 
-        match selected.file_type {
-            FileType::Directory => self.load_directory(&selected.path),
-            FileType::AudioFile => {
-                self.queue = self
-                    .browser_items
-                    .iter()
-                    .filter(|item| item.file_type == FileType::AudioFile)
-                    .map(|item| item.path.clone())
-                    .collect();
+    // Polled from the tick so edits to config.toml apply without a restart.
+    // A broken file keeps the previous settings.
+    fn reload_config(&mut self) {
+        let modified = Config::modified();
+        if modified == self.config_modified {
+            return;
+        }
+        self.config_modified = modified;
 
-                if let Some(idx) = self.queue.iter().position(|p| p == &selected.path) {
-                    self.queue_index = idx;
-                    self.play_queue_item();
+        match Config::load() {
+            Ok(mut config) => {
+                if self.minimal_flag || config.minimal {
+                    config.make_minimal();
                 }
+                self.config = config;
+                self.player.set_max_volume(self.config.max_volume);
+                self.player.gap = self.config.gap();
+                self.covers.fetch = self.config.fetch_covers;
+                self.player.crossfade = self.config.crossfade();
+                self.player.crossfade_albums = self.config.crossfade_albums;
+                self.player.sandbox = self.config.sandbox();
+                self.player.headphones = self.config.headphone_surround;
+                self.set_status(tr!("status.config_reloaded"));
+                self.update_media_keys();
             }
-            FileType::Other => {}
+            Err(e) => self.set_status(tr!("status.config_error", error = e)),
         }
     }
 
-    pub fn play_folder(&mut self) {
-        if self.browser_items.is_empty() {
+    // Heard at once, and remembered for the next time the track plays.
+    fn adjust_track_gain(&mut self, step: f32) {
+        let Some(path) = self.current_path() else {
             return;
+        };
+        self.player.set_track_gain(self.player.track_gain() + step);
+        let gain = self.player.track_gain();
+        if let Some(db) = &self.db
+            && let Err(e) = db.set_track_gain(&path, gain)
+        {
+            log::warn(&format!("Could not save track gain: {}", e));
         }
+        self.set_status(tr!("status.track_gain", gain = format!("{:+}", gain)));
+    }
 
-        let selected = &self.browser_items[self.browser_index];
-        if selected.file_type != FileType::Directory {
+    // Kept in the library for `rating:` searches and `leek write-tags`.
+    fn rate_current(&mut self, stars: u32) {
+        let Some(path) = self.current_path() else {
             return;
-        }
-
-        if let Ok(entries) = fs::read_dir(&selected.path) {
-            let mut folder_files: Vec<PathBuf> = entries
-                .flatten()
-                .map(|e| e.path())
-                .filter(|path| Self::is_audio_file(path))
-                .collect();
-
-            folder_files.sort();
+        };
+        let Some(db) = &self.db else {
+            return;
+        };
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let message = match db.set_rating(&path, stars) {
+            Ok(()) if stars == 0 => tr!("status.unrated", name = name),
+            Ok(()) => tr!(
+                "status.rated",
+                name = name,
+                stars = "*".repeat(stars as usize)
+            ),
+            Err(e) => tr!("status.rating_error", error = e),
+        };
+        self.set_status(message);
+    }
 
-            if !folder_files.is_empty() {
-                self.queue = folder_files;
-                self.queue_index = 0;
-                self.play_queue_item();
-            }
-        }
+    // Lasts until config.toml is next changed.
+    fn cycle_palette(&mut self) {
+        self.config.palette = self.config.palette.next();
+        let name = self.config.palette.label();
+        self.set_status(tr!("status.palette", name = name));
     }
 
-    pub fn go_up(&mut self) {
-        if let Some(parent) = self.current_directory.parent() {
-            self.load_directory(&parent.to_path_buf());
+    fn update_media_keys(&mut self) {
+        if self.config.media_keys == self.media_keys.is_some() {
+            return;
+        }
+        if !self.config.media_keys {
+            self.media_keys = None;
+            return;
+        }
+        match MediaKeys::register() {
+            Ok(keys) => self.media_keys = Some(keys),
+            Err(e) => self.set_status(tr!("status.media_keys_error", error = e)),
         }
     }
 
-    fn play_queue_item(&mut self) {
-        let Some(path) = self.queue.get(self.queue_index) else {
+    fn poll_media_keys(&mut self) {
+        let Some(key) = self.media_keys.as_ref().and_then(MediaKeys::poll) else {
             return;
         };
+        self.note_input();
+        self.dispatch(match key {
+            MediaKey::PlayPause => Action::TogglePlay,
+            MediaKey::Next => Action::NextTrack,
+            MediaKey::Previous => Action::PrevTrack,
+        });
+    }
 
-        self.sink.stop();
-        if let Ok(new_sink) = Sink::try_new(&self.stream_handle) {
-            self.sink = new_sink;
-            self.sink.set_volume(self.volume as f32 / 100.0);
+    pub fn scan_library(&mut self) {
+        if self.scan.is_some() {
+            return;
         }
+        self.scan = Some(Scan::start(self.library.root.clone(), &self.library.tracks));
+        self.set_status(tr!("status.scanning"));
+    }
 
-        if let Ok(file) = File::open(path) {
-            let reader = BufReader::new(file);
-            if let Ok(source) = Decoder::new(reader) {
-                self.duration = source.total_duration();
-                self.elapsed = Duration::ZERO;
-                self.sink.append(source);
-                self.sink.play();
-                self.is_playing = true;
-            }
+    fn poll_scan(&mut self) {
+        let Some(scan) = &mut self.scan else {
+            return;
+        };
+
+        if scan.poll() {
+            let scan = self.scan.take().unwrap();
+            let reused = scan.reused();
+            self.library.set_tracks(scan.finish());
+            let saved = match &mut self.db {
+                Some(db) => db.save_tracks(&self.library.tracks),
+                None => Ok(()),
+            };
+            let message = match saved {
+                Ok(()) => tr!(
+                    "status.scan_done",
+                    count = self.library.tracks.len(),
+                    reused = reused
+                ),
+                Err(e) => tr!("status.scan_save_error", error = e),
+            };
+            self.set_status(message);
+        } else {
+            let (done, found) = scan.progress();
+            self.set_status(tr!("status.scan_progress", done = done, found = found));
         }
     }
 
-    pub fn toggle_play(&mut self) {
-        if self.sink.empty() && !self.queue.is_empty() {
-            self.play_queue_item();
-        } else if self.sink.is_paused() {
-            self.sink.play();
-            self.is_playing = true;
+    // Exports the selected entry from the browser, or the playing track
+    // from the other views.
+    pub fn export_selected(&mut self) {
+        if self.focus == Focus::Browser && self.view == View::Browser {
+            self.with_selected_tracks(SelectionTarget::Export);
         } else {
-            self.sink.pause();
-            self.is_playing = false;
+            self.export_tracks(self.current_path().into_iter().collect());
         }
     }
 
-    pub fn next_track(&mut self) {
-        if self.queue.is_empty() {
+    fn export_tracks(&mut self, paths: Vec<PathBuf>) {
+        if self.export.is_some() {
+            self.set_status(tr!("status.export_busy"));
+            return;
+        }
+        let Some(dest) = self.config.export_dir.clone() else {
+            self.set_status(tr!("status.export_no_dir"));
+            return;
+        };
+        if paths.is_empty() {
             return;
         }
-        self.queue_index = (self.queue_index + 1) % self.queue.len();
-        self.play_queue_item();
+        self.export = Some(Export::start(
+            paths,
+            dest,
+            self.library.root.clone(),
+            self.config.transcode(),
+        ));
     }
 
-    pub fn prev_track(&mut self) {
-        if self.queue.is_empty() {
+    fn poll_export(&mut self) {
+        let Some(export) = &mut self.export else {
             return;
-        }
-        self.queue_index = if self.queue_index > 0 {
-            self.queue_index - 1
-        } else {
-            self.queue.len() - 1
         };
-        self.play_queue_item();
+
+        if export.poll() {
+            let export = self.export.take().unwrap();
+            for (path, e) in &export.failed {
+                log::warn(&format!("Could not export {}: {}", path.display(), e));
+            }
+            let (done, _) = export.files();
+            let message = if export.failed.is_empty() {
+                tr!("status.export_done", count = done)
+            } else {
+                tr!(
+                    "status.export_failed",
+                    count = done - export.failed.len(),
+                    failed = export.failed.len()
+                )
+            };
+            self.set_status(message);
+        } else {
+            let (done, total) = export.files();
+            let message = tr!(
+                "status.export_progress",
+                done = done,
+                total = total,
+                percent = export.percent()
+            );
+            self.set_status(message);
+        }
     }
 
-    pub fn next_item(&mut self) {
-        if !self.browser_items.is_empty() {
-            self.browser_index = (self.browser_index + 1) % self.browser_items.len();
+    pub fn dispatch(&mut self, action: Action) {
+        match action {
+            Action::Quit => self.quit = true,
+
+            Action::TogglePlay => self.toggle_play(),
+            Action::NextTrack => self.next_track(),
+            Action::PrevTrack => self.prev_track(),
+            Action::VolumeUp => self.volume_up(),
+            Action::VolumeDown => self.volume_down(),
+            Action::SeekBack => self.seek_back(),
+            Action::SeekForward => self.seek_forward(),
+            Action::Replay => self.replay(),
+            Action::Duck => self.player.duck(self.config.duck_db),
+            Action::Unduck => self.player.duck(0.0),
+
+            Action::NextItem => self.next_item(),
+            Action::PrevItem => self.prev_item(),
+            Action::EnterSelected => self.enter_selected(),
+            Action::PlayFolder => self.play_folder(),
+            Action::GoUp => self.go_up(),
+            Action::EnqueueSelected => self.enqueue_selected(),
+            Action::AddToPlaylist => self.add_selected_to_playlist(),
+            Action::AddToInbox => self.add_selected_to_inbox(),
+            Action::ExportSelected => self.export_selected(),
+            Action::LikeCurrent => self.like_current(),
+            Action::ScanLibrary => self.scan_library(),
+
+            Action::ShowBrowser => self.show_browser(),
+            Action::CycleFocus => {
+                self.cancel_visual();
+                self.cycle_focus();
+            }
+            Action::ToggleQueueView => {
+                self.cancel_visual();
+                self.toggle_queue_view();
+            }
+            Action::ShowPlaylistEditor => self.show_playlist_editor(),
+            Action::ShowInbox => self.show_inbox(),
+            Action::ShowRecentlyAdded => self.show_recently_added(),
+            Action::ShowRecentlyPlayed => self.show_recently_played(),
+            Action::ShowCharts => self.show_charts(),
+            Action::ShowArrivals => self.show_arrivals(),
+            Action::TrackGainUp => self.adjust_track_gain(TRACK_GAIN_STEP_DB),
+            Action::TrackGainDown => self.adjust_track_gain(-TRACK_GAIN_STEP_DB),
+            Action::Rate(stars) => self.rate_current(stars),
+            Action::RenameFolder => self.open_rename_prompt(),
+            Action::OpenWith => self.open_with(),
+            Action::Shell => self.external = Some(External::shell()),
+            Action::OpenCommandLine => self.open_command_line(),
+            Action::OpenPalette => self.open_palette(),
+            Action::ShowTrackInfo => self.show_track_info(),
+            Action::ShowPlaybackErrors => self.show_playback_errors(),
+            Action::CyclePalette => self.cycle_palette(),
+            Action::ToggleVisual => self.toggle_visual(),
+            Action::CancelVisual => self.cancel_visual(),
+            Action::ClearFilter => self.clear_browser_filter(),
+
+            Action::OpenSearch => self.open_search(),
+            Action::CloseSearch => self.close_search(),
+            Action::SearchNext => self.search_next(),
+            Action::SearchPrev => self.search_prev(),
+            Action::SearchEnter => self.search_enter(),
+            Action::SearchPush(c) => self.search_push(c),
+            Action::SearchPop => self.search_pop(),
+
+            Action::QueueNext => self.queue_next(),
+            Action::QueuePrev => self.queue_prev(),
+            Action::QueueEnter => self.queue_enter(),
+            Action::CropQueue => self.crop_queue(),
+            Action::ClearQueue => self.clear_queue(),
+            Action::UndoQueue => self.undo_queue(),
+            Action::OpenQueueSort => self.open_queue_sort(),
+            Action::RemoveFromQueue => self.remove_from_queue(),
+            Action::MoveQueueUp => self.move_queue_selection(false),
+            Action::MoveQueueDown => self.move_queue_selection(true),
+            Action::JumpDigit(c) => {
+                self.jump_digits.push(c);
+                self.set_status(tr!("status.jump", number = self.jump_digits));
+            }
+            Action::JumpPop => {
+                self.jump_digits.pop();
+                self.set_status(tr!("status.jump", number = self.jump_digits));
+            }
+            Action::JumpToEntry => {
+                let number = std::mem::take(&mut self.jump_digits);
+                self.jump_to(number.parse().unwrap_or(0));
+            }
+            Action::CancelJump => {
+                self.jump_digits.clear();
+                self.status = None;
+            }
+
+            Action::PlaylistNext
+            | Action::PlaylistPrev
+            | Action::PlaylistMoveUp
+            | Action::PlaylistMoveDown
+            | Action::PlaylistRemove => {
+                let Some(editor) = &mut self.playlist_editor else {
+                    return;
+                };
+                match action {
+                    Action::PlaylistNext => editor.next(),
+                    Action::PlaylistPrev => editor.prev(),
+                    Action::PlaylistMoveUp => editor.move_up(),
+                    Action::PlaylistMoveDown => editor.move_down(),
+                    _ => editor.remove(),
+                }
+            }
+            Action::SavePlaylist => self.save_playlist(),
+            Action::ClosePlaylist => self.close_playlist_editor(false),
+            Action::DiscardPlaylist => self.close_playlist_editor(true),
+
+            Action::InboxNext => self.inbox_next(),
+            Action::InboxPrev => self.inbox_prev(),
+            Action::InboxEnter => self.inbox_enter(),
+            Action::InboxRemove => self.inbox_remove(),
+
+            Action::ChartsNext => self.charts_next(),
+            Action::ChartsPrev => self.charts_prev(),
+            Action::ChartsEnter => self.charts_enter(),
+            Action::ChartsNextKind => self.charts_next_kind(),
+            Action::ChartsNextWindow => self.charts_next_window(),
+
+            Action::TracksNext => self.tracks_next(),
+            Action::TracksPrev => self.tracks_prev(),
+            Action::TracksEnter => self.tracks_enter(),
+
+            Action::ArrivalsNext => self.arrivals_next(),
+            Action::ArrivalsPrev => self.arrivals_prev(),
+            Action::ArrivalsEnter => self.arrivals_enter(),
+            Action::MoveArrival => self.move_arrival(),
+
+            Action::RenameNext => self.rename_next(),
+            Action::RenamePrev => self.rename_prev(),
+            Action::ApplyRename => self.apply_rename(),
+
+            Action::StartCuePrompt => self.start_cue_prompt(),
+            Action::OpenCuePicker => self.open_cue_picker(),
+
+            Action::SubmitModal => self.submit_modal(),
+            Action::CloseModal => self.close_modal(),
+            Action::EnqueueFromModal => self.enqueue_from_palette(),
+            Action::ModalPush(c) => {
+                self.modal.iter_mut().for_each(|modal| modal.push(c));
+                self.refresh_palette();
+            }
+            Action::ModalPop => {
+                self.modal.iter_mut().for_each(Modal::pop);
+                self.refresh_palette();
+            }
+            Action::ModalNext => self.modal.iter_mut().for_each(Modal::next),
+            Action::ModalPrev => self.modal.iter_mut().for_each(Modal::prev),
+            Action::ModalDelete => self.modal_delete(),
         }
     }
 
-    pub fn prev_item(&mut self) {
-        if !self.browser_items.is_empty() {
-            self.browser_index = if self.browser_index > 0 {
-                self.browser_index - 1
-            } else {
-                self.browser_items.len() - 1
-            };
+    pub fn on_tick(&mut self) {
+        self.reload_config();
+        self.poll_scan();
+        self.poll_export();
+        self.poll_folder_summary();
+        self.poll_media_keys();
+        self.covers.poll();
+
+        if self.osd.is_some() {
+            self.osd_ticks += 1;
+            if self.osd_ticks >= self.ticks(self.config.osd_seconds) {
+                self.osd = None;
+            }
+        }
+
+        if self.status.is_some() {
+            self.status_ticks += 1;
+            if self.status_ticks >= self.ticks(STATUS_TIMEOUT_SECONDS) {
+                self.status = None;
+            }
+        }
+
+        if self.player.is_playing {
+            self.idle_ticks += 1;
+            self.check_idle();
+            self.tick_counter += 1;
+            if self.player.settings.audiobook
+                && self
+                    .tick_counter
+                    .is_multiple_of(self.ticks(POSITION_SAVE_SECONDS))
+            {
+                self.save_position();
+            }
+        }
+        let playback = self.player.tick(self.config.tick_rate());
+        self.after_playback(playback);
+        self.autosave();
+        self.update_shared_status();
+        self.metrics
+            .note_buffering(self.player.buffering().is_some_and(|p| p.stalled));
+
+        self.mount_ticks += 1;
+        if self.mount_ticks >= self.ticks(MOUNT_CHECK_SECONDS) {
+            self.mount_ticks = 0;
+            self.check_mounts();
+            self.check_output_device();
+            self.check_alarm();
+        }
+
+        self.arrivals.ticks += 1;
+        if self.arrivals.ticks >= self.ticks(ARRIVALS_CHECK_SECONDS) {
+            self.check_arrivals();
         }
     }
 
-    pub fn volume_up(&mut self) {
-        self.volume = (self.volume + 5).min(100);
-        self.sink.set_volume(self.volume as f32 / 100.0);
+    fn update_shared_status(&self) {
+        let path = self.player.current();
+        let tags = path
+            .and_then(|path| self.library.find(path))
+            .map(|track| &track.tags);
+        let state = match (path, self.player.is_playing) {
+            (None, _) => "stopped",
+            (Some(_), true) => "playing",
+            (Some(_), false) => "paused",
+        };
+        let status = json!({
+            "state": state,
+            "path": path,
+            "artist": tags.and_then(|t| t.artist.as_ref()),
+            "album": tags.and_then(|t| t.album.as_ref()),
+            "title": tags.and_then(|t| t.title.as_ref()),
+            "elapsed": self.player.elapsed.as_secs(),
+            "duration": self.player.duration.map(|d| d.as_secs()),
+            "volume": self.player.volume,
+            "position": path.map(|_| self.player.queue_index + 1),
+            "queue_length": self.player.queue.len(),
+        })
+        .to_string();
+        if let Ok(mut shared) = self.shared_status.lock() {
+            *shared = status;
+        }
     }
 
-    pub fn volume_down(&mut self) {
-        self.volume = self.volume.saturating_sub(5);
-        self.sink.set_volume(self.volume as f32 / 100.0);
+    // How many ticks make up `seconds` at the configured tick rate.
+    fn ticks(&self, seconds: u64) -> u64 {
+        seconds * 1000 / self.config.tick_rate().as_millis() as u64
+    }
+
+    pub fn note_input(&mut self) {
+        self.idle_ticks = 0;
     }
+
+    // Pauses after hours without a key press, so a player left on overnight
+    // doesn't keep playing (and scrobbling) to an empty room.
+    fn check_idle(&mut self) {
+        let hours = self.config.idle_pause_hours;
+        if hours == 0 || self.idle_ticks < self.ticks(hours * 3600) {
+            return;
+        }
+        self.idle_ticks = 0;
+        self.toggle_play();
+        self.modal = Some(Modal::confirm(
+            tr!("idle.title"),
+            tr!("idle.message", hours = hours),
+            Purpose::StillListening,
+        ));
+    }
+
+    // Goes off once when the clock reaches the alarm time, and is ready
+    // again for the next day as soon as that minute has passed.
+    fn check_alarm(&mut self) {
+        let Some(alarm) = self.config.alarm.clone() else {
+            return;
+        };
+        if clock::local_minutes() != alarm.time {
+            self.alarm_rang = false;
+            return;
+        }
+        if !self.alarm_rang {
+            self.alarm_rang = true;
+            self.ring_alarm(&alarm);
+        }
+    }
+
+    fn ring_alarm(&mut self, alarm: &Alarm) {
+        let name = alarm.play.file_name().unwrap_or_default().to_string_lossy();
+        self.set_status(tr!("status.alarm", name = name));
+        self.player.fade_in(Duration::from_secs(alarm.fade_seconds));
+
+        if playlist::is_playlist_file(&alarm.play) {
+            match playlist::load(&alarm.play) {
+                Ok(tracks) if !tracks.is_empty() => {
+                    let playback = self.player.set_queue(tracks, 0);
+                    self.after_playback(playback);
+                }
+                Ok(_) => {}
+                Err(e) => self.set_status(tr!(
+                    "status.cannot_open",
+                    path = alarm.play.display(),
+                    error = e
+                )),
+            }
+        } else {
+            self.play_dir(alarm.play.clone());
+        }
+    }
+
+    // Headphones and speakers each keep their own volume. When the system
+    // switches devices, the new one gets back whatever it was last set to.
+    // Asking the sound server means running pactl, so it is done on a
+    // worker thread.
+    fn check_output_device(&mut self) {
+        if self.checking_device || self.own_output() {
+            return;
+        }
+        self.checking_device = true;
+        let events = self.events.clone();
+        thread::spawn(move || {
+            let device = backend::output_device();
+            let _ = events.send(Event::Loaded(Loaded::OutputDevice(device)));
+        });
+    }
+
+    fn on_output_device(&mut self, device: Option<String>) {
+        self.checking_device = false;
+        if device.is_none() || device == self.output_device || self.own_output() {
+            return;
+        }
+
+        self.remember_device_volume();
+        self.output_device = device;
+        if let Some(volume) = self.device_volume() {
+            self.player.set_volume(volume);
+            self.set_status(tr!("status.device_volume", volume = volume));
+        }
+    }
+
+    // Outputs leek opened itself rather than the system's default device.
+    fn own_output(&self) -> bool {
+        matches!(
+            self.output_device.as_deref(),
+            Some(JACK_DEVICE | SNAPCAST_DEVICE | SILENT_DEVICE)
+        )
+    }
+
+    fn device_volume(&self) -> Option<u8> {
+        let device = self.output_device.as_ref()?;
+        self.device_volumes.get(device).copied()
+    }
+
+    fn remember_device_volume(&mut self) {
+        if let Some(device) = &self.output_device {
+            self.device_volumes
+                .insert(device.clone(), self.player.volume);
+        }
+    }
+
+    // Network shares can drop out from under the player. The lost track and
+    // folder are checked every couple of seconds and picked up again once
+    // they are back. Looking is done on a worker thread, since a dropped
+    // share can take a while to answer.
+    fn check_mounts(&mut self) {
+        if self.checking_mounts {
+            return;
+        }
+        self.checking_mounts = true;
+        let track = self.lost_track.clone();
+        let dir = self.current_directory.clone();
+        let events = self.events.clone();
+        thread::spawn(move || {
+            let track = track.map(|path| {
+                let exists = path.exists();
+                (path, exists)
+            });
+            let dir_exists = remote::is_remote(&dir) || dir.is_dir();
+            let _ = events.send(Event::Loaded(Loaded::Mounts {
+                track,
+                dir,
+                dir_exists,
+            }));
+        });
+    }
+
+    fn on_mounts(&mut self, track: Option<(PathBuf, bool)>, dir: PathBuf, exists: bool) {
+        self.checking_mounts = false;
+        if let Some((path, available)) = track
+            && self.lost_track.as_ref() == Some(&path)
+        {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if !available {
+                self.set_status(tr!("status.track_unavailable", name = name));
+            } else if let Err(e) = self.player.reconnect() {
+                log::warn(&format!("Could not resume {}: {}", path.display(), e));
+            } else {
+                self.set_status(tr!("status.reconnected", name = name));
+                self.lost_track = None;
+            }
+        }
+
+        if dir != self.current_directory {
+            return;
+        }
+        if !exists && !self.directory_lost {
+            self.set_status(tr!(
+                "status.folder_unavailable",
+                path = self.current_directory.display()
+            ));
+        } else if exists && self.directory_lost {
+            self.reload_directory();
+        }
+        self.directory_lost = !exists;
+    }
+
+    fn autosave(&mut self) {
+        let interval = self.ticks(self.config.autosave_seconds);
+        if interval == 0 {
+            return;
+        }
+        self.autosave_ticks += 1;
+        if self.autosave_ticks >= interval {
+            self.autosave_ticks = 0;
+            self.save_session(false);
+        }
+    }
+
+    pub fn enter_selected(&mut self) {
+        if self.browser_items.is_empty() {
+            return;
+        }
+
+        let selected = &self.browser_items[self.browser_index].clone();
+
+        match selected.file_type {
+            FileType::Directory => self.load_directory(&selected.path),
+            FileType::AudioFile => {
+                let queue: Vec<PathBuf> = self
+                    .browser_items
+                    .iter()
+                    .filter(|item| item.file_type == FileType::AudioFile)
+                    .map(|item| item.path.clone())
+                    .collect();
+
+                if let Some(idx) = queue.iter().position(|p| p == &selected.path) {
+                    let playback = self.player.set_queue(queue, idx);
+                    self.after_playback(playback);
+                }
+            }
+            FileType::Playlist => self.open_playlist_editor(&selected.path),
+            FileType::Other => {}
+        }
+    }
+
+    pub fn play_folder(&mut self) {
+        let Some(selected) = self.browser_items.get(self.browser_index) else {
+            return;
+        };
+        if selected.file_type != FileType::Directory {
+            return;
+        }
+
+        let dir = selected.path.clone();
+        self.play_dir(dir);
+    }
+
+    // Queues a folder's tracks in album order once they have been listed.
+    fn play_dir(&mut self, dir: PathBuf) {
+        self.loading_folder = Some(dir.clone());
+        let events = self.events.clone();
+        thread::spawn(move || {
+            let tracks = list_tracks(&dir);
+            let _ = events.send(Event::Loaded(Loaded::Folder { dir, tracks }));
+        });
+    }
+
+    pub fn go_up(&mut self) {
+        if let Some(parent) = self.current_directory.parent() {
+            self.load_directory(&parent.to_path_buf());
+        }
+    }
+
+    // Reports what the player did: status messages for skipped files, and
+    // history and announcements for the track that started.
+    fn after_playback(&mut self, playback: Playback) {
+        if let Some(path) = playback.lost {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            self.set_status(tr!("status.track_unavailable", name = name));
+            log::warn(&format!("{} went away during playback", path.display()));
+            self.log_playback_error(&path, tr!("errors.went_away"));
+            self.lost_track = Some(path);
+            return;
+        }
+        if let Some(path) = &playback.finished {
+            self.finish_inbox_track(path);
+            self.journal("finished", path, json!({}));
+            if self.journal_track.as_ref() == Some(path) {
+                self.journal_track = None;
+            }
+        }
+        for (path, error) in &playback.skipped {
+            self.metrics.decode_error();
+            self.journal(
+                "skipped",
+                path,
+                json!({ "reason": "error", "error": error }),
+            );
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            self.set_status(tr!("status.skipped", name = name));
+            self.log_playback_error(path, error.clone());
+        }
+
+        let Some(path) = playback.started else {
+            if !playback.skipped.is_empty() {
+                self.set_status(tr!("status.nothing_playable"));
+            }
+            return;
+        };
+        self.lost_track = None;
+        self.metrics.track_played();
+        let gain = match &self.db {
+            Some(db) => db.track_gain(&path).unwrap_or_default(),
+            None => 0.0,
+        };
+        self.player.set_track_gain(gain);
+        if let Some(previous) = self.journal_track.replace(path.clone()) {
+            self.journal("skipped", &previous, json!({ "reason": "user" }));
+        }
+        let tags = self.library.find(&path).map(|track| &track.tags);
+        self.journal(
+            "started",
+            &path,
+            json!({
+                "artist": tags.and_then(|t| t.artist.as_ref()),
+                "album": tags.and_then(|t| t.album.as_ref()),
+                "title": tags.and_then(|t| t.title.as_ref()),
+            }),
+        );
+        self.load_cue_marks(&path);
+
+        if self.player.settings.audiobook {
+            self.resume_position(&path);
+        }
+        if self.config.announce != Announce::Off {
+            self.announcement = self.now_playing();
+        }
+        if self.config.osd {
+            self.show_osd(&path);
+        }
+        if let Some(db) = &self.db
+            && let Err(e) = db.record_play(&path)
+        {
+            log::warn(&format!(
+                "Could not record play of {}: {}",
+                path.display(),
+                e
+            ));
+        }
+    }
+
+    fn log_playback_error(&mut self, path: &Path, error: String) {
+        if self.playback_errors.len() >= ERROR_LOG_LIMIT {
+            self.playback_errors.remove(0);
+        }
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.playback_errors.push(PlaybackError {
+            path: path.to_path_buf(),
+            error,
+            at,
+        });
+    }
+
+    pub fn show_playback_errors(&mut self) {
+        if self.playback_errors.is_empty() {
+            self.set_status(tr!("errors.none"));
+            return;
+        }
+        let rows = self
+            .playback_errors
+            .iter()
+            .rev()
+            .flat_map(|entry| {
+                [
+                    (
+                        clock::local_time(entry.at),
+                        entry.path.display().to_string(),
+                    ),
+                    (String::new(), entry.error.clone()),
+                ]
+            })
+            .collect();
+        self.modal = Some(Modal::info(
+            tr!("errors.title", count = self.playback_errors.len()),
+            rows,
+            Purpose::PlaybackErrors,
+        ));
+    }
+
+    fn resume_position(&mut self, path: &Path) {
+        let Some(db) = &self.db else {
+            return;
+        };
+        if let Ok(Some(position)) = db.position(path)
+            && position > self.player.elapsed
+        {
+            self.seek_to(position);
+            self.set_status(tr!("status.resumed", position = format_position(position)));
+        }
+    }
+
+    // Audiobook folders keep each track's position, forgetting it again
+    // once the track is nearly done.
+    fn save_position(&self) {
+        let (Some(db), Some(path)) = (&self.db, self.player.current()) else {
+            return;
+        };
+        let finished = self
+            .player
+            .duration
+            .is_some_and(|d| d.saturating_sub(self.player.elapsed) < AUDIOBOOK_FINISHED);
+        let saved = if finished {
+            db.clear_position(path)
+        } else {
+            db.save_position(path, self.player.elapsed)
+        };
+        if let Err(e) = saved {
+            log::warn(&format!(
+                "Could not save position of {}: {}",
+                path.display(),
+                e
+            ));
+        }
+    }
+
+    pub fn seek_to(&mut self, position: Duration) {
+        let from = self.player.elapsed;
+        match self.player.seek_to(position) {
+            Ok(()) => {
+                if let Some(path) = self.current_path() {
+                    self.journal(
+                        "seeked",
+                        &path,
+                        json!({ "from": from.as_secs_f64(), "to": position.as_secs_f64() }),
+                    );
+                }
+            }
+            Err(e) => self.set_status(tr!("status.cannot_seek", error = e)),
+        }
+    }
+
+    fn journal(&self, event: &str, path: &Path, details: Value) {
+        if self.config.journal {
+            journal::record(event, path, details);
+        }
+    }
+
+    // Tags from the library, or from the file itself. A sandboxed player
+    // only has the playing track's, which its decoder sent.
+    fn tags_of(&self, path: &Path) -> Tags {
+        if let Some(track) = self.library.find(path) {
+            return track.tags.clone();
+        }
+        if self.player.sandbox.is_none() {
+            return tags::read(path).unwrap_or_default();
+        }
+        if self.player.current() == Some(path) {
+            return self.player.tags.clone();
+        }
+        Tags::default()
+    }
+
+    fn show_osd(&mut self, path: &Path) {
+        let tags = self.tags_of(path);
+        let title = tags.title.unwrap_or_else(|| {
+            path.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        });
+        self.osd = Some(Osd {
+            title,
+            artist: tags.artist,
+            album: tags.album,
+            path: path.to_path_buf(),
+        });
+        self.covers.request(path);
+        self.osd_ticks = 0;
+    }
+
+    pub fn now_playing(&self) -> Option<String> {
+        let path = self.player.current()?;
+        let tags = self.library.find(path).map(|track| &track.tags);
+        Some(
+            match tags.and_then(|t| t.artist.as_ref().zip(t.title.as_ref())) {
+                Some((artist, title)) => format!("{} - {}", artist, title),
+                None => path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+            },
+        )
+    }
+
+    // The playing track through one of the user's format strings.
+    pub fn format_now_playing(&self, format: &str) -> String {
+        let path = self.player.current();
+        let tags = path
+            .and_then(|path| self.library.find(path))
+            .map(|track| &track.tags);
+        let file = path.map(|path| {
+            path.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        });
+        let elapsed = self.player.elapsed;
+        format::expand(format, |name| match name {
+            "artist" => Some(tags.and_then(|t| t.artist.clone())),
+            "title" => Some(tags.and_then(|t| t.title.clone()).or_else(|| file.clone())),
+            "album" => Some(tags.and_then(|t| t.album.clone())),
+            "albumartist" => Some(tags.and_then(|t| t.display_album_artist())),
+            "year" => Some(tags.and_then(|t| t.year).map(|n| n.to_string())),
+            "track" => Some(tags.and_then(|t| t.track_number).map(|n| n.to_string())),
+            "file" => Some(file.clone()),
+            "elapsed" => Some(Some(format_duration(elapsed))),
+            "duration" => Some(self.player.duration.map(format_duration)),
+            "remaining" => Some(
+                self.player
+                    .duration
+                    .map(|d| format_duration(d.saturating_sub(elapsed))),
+            ),
+            "state" => Some(Some(if self.player.is_playing {
+                tr!("info.playing")
+            } else {
+                tr!("info.paused")
+            })),
+            "volume" => Some(Some(self.player.volume.to_string())),
+            "position" => Some(path.map(|_| (self.player.queue_index + 1).to_string())),
+            "total" => Some(Some(self.player.queue.len().to_string())),
+            _ => None,
+        })
+    }
+
+    // What `title_format` puts in the terminal's title bar, if it's set.
+    pub fn terminal_title(&self) -> Option<String> {
+        let format = self.config.title_format.as_ref()?;
+        Some(match self.player.current() {
+            Some(_) => self.format_now_playing(format),
+            None => String::from("leek"),
+        })
+    }
+
+    pub fn replay(&mut self) {
+        if self.player.is_idle() {
+            return;
+        }
+        let step = self.config.replay_step();
+        self.seek_to(self.player.elapsed.saturating_sub(step));
+    }
+
+    pub fn seek_back(&mut self) {
+        if self.player.is_idle() {
+            return;
+        }
+        let step = self.config.seek_step();
+        self.seek_to(self.player.elapsed.saturating_sub(step));
+    }
+
+    pub fn seek_forward(&mut self) {
+        if self.player.is_idle() {
+            return;
+        }
+        let step = self.config.seek_step();
+        self.seek_to(self.player.elapsed + step);
+    }
+
+    pub fn toggle_play(&mut self) {
+        if self.lost_track.is_some() {
+            self.check_mounts();
+            return;
+        }
+        let playback = self.player.toggle_play();
+        self.after_playback(playback);
+    }
+
+    pub fn next_track(&mut self) {
+        let playback = self.player.next_track();
+        self.after_playback(playback);
+    }
+
+    // Like a CD player: the first press goes back to the start of the
+    // track, and a second one right after moves to the one before.
+    pub fn prev_track(&mut self) {
+        if !self.player.is_idle() && self.player.elapsed > RESTART_THRESHOLD {
+            self.seek_to(Duration::ZERO);
+            return;
+        }
+        let playback = self.player.prev_track();
+        self.after_playback(playback);
+    }
+
+    pub fn next_item(&mut self) {
+        if !self.browser_items.is_empty() {
+            self.browser_index = (self.browser_index + 1) % self.browser_items.len();
+        }
+        self.update_folder_summary();
+    }
+
+    pub fn prev_item(&mut self) {
+        if !self.browser_items.is_empty() {
+            self.browser_index = if self.browser_index > 0 {
+                self.browser_index - 1
+            } else {
+                self.browser_items.len() - 1
+            };
+        }
+        self.update_folder_summary();
+    }
+
+    pub fn volume_up(&mut self) {
+        let volume = self.player.volume.saturating_add(self.config.volume_step);
+        self.player.set_volume(volume);
+    }
+
+    pub fn volume_down(&mut self) {
+        let volume = self.player.volume.saturating_sub(self.config.volume_step);
+        self.player.set_volume(volume);
+    }
+
+    pub fn open_search(&mut self) {
+        self.focus = Focus::Search;
+        if self.search.is_some() {
+            return;
+        }
+        self.search = Some(Search::default());
+        self.refresh_search();
+    }
+
+    pub fn close_search(&mut self) {
+        self.search = None;
+        self.focus = Focus::Browser;
+    }
+
+    pub fn search_push(&mut self, c: char) {
+        if let Some(search) = &mut self.search {
+            search.input.push(c);
+        }
+        self.refresh_search();
+    }
+
+    pub fn search_pop(&mut self) {
+        if let Some(search) = &mut self.search {
+            search.input.pop();
+        }
+        self.refresh_search();
+    }
+
+    fn refresh_search(&mut self) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+
+        search.results = search_library(&self.library, self.db.as_ref(), &search.input)
+            .take(SEARCH_LIMIT)
+            .collect();
+        search.list.cursor = 0;
+    }
+
+    pub fn show_track_info(&mut self) {
+        let Some(path) = self
+            .selected_item()
+            .filter(|path| !remote::is_remote(path) && library::is_audio_file(path))
+        else {
+            return;
+        };
+        let info = match tags::read_info(&path) {
+            Ok(info) => info,
+            Err(e) => {
+                self.set_status(tr!("status.cannot_open", path = path.display(), error = e));
+                return;
+            }
+        };
+
+        let mut rows = vec![(tr!("track_info.path"), path.display().to_string())];
+        if let Ok(meta) = fs::metadata(&path) {
+            rows.push((tr!("track_info.size"), folder::format_size(meta.len())));
+            let modified = meta
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|since| since.as_secs());
+            if let Some(secs) = modified {
+                rows.push((tr!("track_info.modified"), clock::local_time(secs)));
+            }
+        }
+        let stream = [
+            (tr!("track_info.codec"), info.codec),
+            (
+                tr!("track_info.sample_rate"),
+                info.sample_rate.map(|rate| format!("{} Hz", rate)),
+            ),
+            (
+                tr!("track_info.channels"),
+                info.channels.map(|n| n.to_string()),
+            ),
+            (
+                tr!("track_info.bits"),
+                info.bits_per_sample.map(|bits| bits.to_string()),
+            ),
+            (
+                tr!("track_info.duration"),
+                info.duration.map(format_position),
+            ),
+            (tr!("track_info.pictures"), Some(info.pictures.to_string())),
+        ];
+        rows.extend(
+            stream
+                .into_iter()
+                .filter_map(|(label, value)| Some((label, value?))),
+        );
+        rows.extend(info.tags);
+
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        self.modal = Some(Modal::info(
+            tr!("track_info.title", name = name),
+            rows,
+            Purpose::TrackInfo,
+        ));
+    }
+
+    pub fn open_palette(&mut self) {
+        self.modal = Some(Modal::palette(
+            tr!("palette.title"),
+            Purpose::Palette {
+                results: Vec::new(),
+            },
+        ));
+        self.refresh_palette();
+    }
+
+    fn refresh_palette(&mut self) {
+        let Some(Modal {
+            kind:
+                ModalKind::Palette {
+                    input,
+                    items,
+                    index,
+                },
+            purpose: Purpose::Palette { results },
+            ..
+        }) = &mut self.modal
+        else {
+            return;
+        };
+        let tracks: Vec<&Track> = search_library(&self.library, self.db.as_ref(), input)
+            .take(PALETTE_LIMIT)
+            .map(|i| &self.library.tracks[i])
+            .collect();
+        *items = tracks
+            .iter()
+            .map(|track| PickerItem {
+                detail: track.tags.duration.map(format_position).unwrap_or_default(),
+                label: track_label(track),
+            })
+            .collect();
+        *results = tracks.iter().map(|track| track.path.clone()).collect();
+        *index = 0;
+    }
+
+    // Tab in the palette queues the pick and leaves it open for more.
+    fn enqueue_from_palette(&mut self) {
+        let Some(Modal {
+            kind: ModalKind::Palette { index, .. },
+            purpose: Purpose::Palette { results },
+            ..
+        }) = &self.modal
+        else {
+            return;
+        };
+        let Some(path) = results.get(*index).cloned() else {
+            return;
+        };
+        let name = self
+            .library
+            .find(&path)
+            .map(track_label)
+            .unwrap_or_default();
+        let playback = self.player.enqueue(vec![path]);
+        self.after_playback(playback);
+        self.set_status(tr!("status.palette_queued", name = name));
+    }
+
+    pub fn search_groups(&self) -> Vec<Group> {
+        let Some(search) = &self.search else {
+            return Vec::new();
+        };
+        groups::build(search.results.iter().map(|&i| {
+            let track = &self.library.tracks[i];
+            (track.path.as_path(), Some(track))
+        }))
+    }
+
+    pub fn search_next(&mut self) {
+        let rows = groups::rows(&self.search_groups(), &self.search_collapsed());
+        if let Some(search) = &mut self.search {
+            search.list.next(rows.len());
+        }
+    }
+
+    pub fn search_prev(&mut self) {
+        let rows = groups::rows(&self.search_groups(), &self.search_collapsed());
+        if let Some(search) = &mut self.search {
+            search.list.prev(rows.len());
+        }
+    }
+
+    fn search_collapsed(&self) -> HashSet<String> {
+        self.search
+            .as_ref()
+            .map(|s| s.list.collapsed.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn search_enter(&mut self) {
+        let groups = self.search_groups();
+        let rows = groups::rows(&groups, &self.search_collapsed());
+        let Some(search) = &mut self.search else {
+            return;
+        };
+
+        match search.list.selected(&rows) {
+            Some(Row::Header(g)) => {
+                search.list.toggle(&groups[g]);
+                let rows = groups::rows(&groups, &search.list.collapsed);
+                search.list.clamp(rows.len());
+            }
+            Some(Row::Item(i)) => {
+                let search = self.search.take().unwrap();
+                self.focus = Focus::Browser;
+                let queue = search
+                    .results
+                    .iter()
+                    .map(|&r| self.library.tracks[r].path.clone())
+                    .collect();
+                let playback = self.player.set_queue(queue, i);
+                self.after_playback(playback);
+            }
+            None => {}
+        }
+    }
+
+    pub fn toggle_queue_view(&mut self) {
+        self.focus = match self.focus {
+            Focus::Queue => Focus::Browser,
+            Focus::Browser | Focus::Playlist | Focus::Search => Focus::Queue,
+        };
+    }
+
+    // Tab moves through the panes in order, skipping the playlist editor
+    // and search when they aren't open.
+    pub fn cycle_focus(&mut self) {
+        let order = [Focus::Browser, Focus::Queue, Focus::Playlist, Focus::Search];
+        let start = order.iter().position(|&f| f == self.focus).unwrap_or(0);
+        self.focus = (1..=order.len())
+            .map(|step| order[(start + step) % order.len()])
+            .find(|&focus| match focus {
+                Focus::Playlist => self.playlist_editor.is_some(),
+                Focus::Search => self.search.is_some(),
+                Focus::Browser | Focus::Queue => true,
+            })
+            .unwrap_or(Focus::Browser);
+    }
+
+    pub fn show_browser(&mut self) {
+        self.view = View::Browser;
+        self.focus = Focus::Browser;
+    }
+
+    pub fn show_playlist_editor(&mut self) {
+        if self.playlist_editor.is_some() {
+            self.focus = Focus::Playlist;
+        }
+    }
+
+    fn open_playlist_editor(&mut self, path: &Path) {
+        match PlaylistEditor::open(path) {
+            Ok(editor) => {
+                self.playlist_editor = Some(editor);
+                self.focus = Focus::Playlist;
+            }
+            Err(e) => self.set_status(tr!("status.cannot_open", path = path.display(), error = e)),
+        }
+    }
+
+    pub fn close_playlist_editor(&mut self, force: bool) {
+        if let Some(editor) = &self.playlist_editor
+            && editor.dirty
+            && !force
+        {
+            let name = editor
+                .path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy();
+            self.modal = Some(Modal::confirm(
+                tr!("playlist.discard_title"),
+                tr!("playlist.discard", name = name),
+                Purpose::DiscardPlaylist,
+            ));
+            return;
+        }
+        self.playlist_editor = None;
+        self.focus = Focus::Browser;
+    }
+
+    pub fn save_playlist(&mut self) {
+        let Some(editor) = &mut self.playlist_editor else {
+            return;
+        };
+        let message = match editor.save() {
+            Ok(()) => tr!("status.playlist_saved", path = editor.path.display()),
+            Err(e) => tr!("status.playlist_save_error", error = e),
+        };
+        self.set_status(message);
+    }
+
+    pub fn add_selected_to_playlist(&mut self) {
+        if self.playlist_editor.is_none() {
+            self.set_status(tr!("status.no_playlist"));
+            return;
+        }
+        self.with_selected_tracks(SelectionTarget::Playlist);
+    }
+
+    fn add_to_playlist(&mut self, paths: Vec<PathBuf>) {
+        // The editor may have been closed while the folders were listed.
+        let Some(editor) = &mut self.playlist_editor else {
+            return;
+        };
+        let count = paths.len();
+        editor.insert(paths);
+        self.set_status(tr!("status.playlist_added", count = count));
+    }
+
+    pub fn like_current(&mut self) {
+        let Some(path) = self.current_path() else {
+            return;
+        };
+        let Some(liked) = self.config.liked_playlist() else {
+            self.set_status(tr!("status.no_data_dir"));
+            return;
+        };
+
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let playlist = liked.file_name().unwrap_or_default().to_string_lossy();
+        let message = match playlist::append(&liked, &path) {
+            Ok(true) => tr!("status.liked", name = name, playlist = playlist),
+            Ok(false) => tr!("status.already_liked", name = name, playlist = playlist),
+            Err(e) => tr!("status.playlist_save_error", error = e),
+        };
+        self.set_status(message);
+    }
+
+    pub fn enqueue_selected(&mut self) {
+        self.with_selected_tracks(SelectionTarget::Queue);
+    }
+
+    fn enqueue_tracks(&mut self, mut paths: Vec<PathBuf>) {
+        if paths.is_empty() {
+            return;
+        }
+
+        let mut skipped = 0;
+        if self.config.queue_dedup {
+            let mut seen: HashSet<PathBuf> = self.player.queue.iter().cloned().collect();
+            let before = paths.len();
+            paths.retain(|path| seen.insert(path.clone()));
+            skipped = before - paths.len();
+        }
+
+        let count = paths.len();
+        let playback = self.player.enqueue(paths);
+        self.after_playback(playback);
+        if skipped > 0 {
+            self.set_status(tr!(
+                "status.queue_added_skipped",
+                count = count,
+                skipped = skipped
+            ));
+        } else {
+            self.set_status(tr!("status.queue_added", count = count));
+        }
+    }
+
+    // Lists the tracks under the browser selection, or under every item of
+    // a visual selection, on a worker thread, since folders can be large or
+    // remote. They come back as `Loaded::Selection` for `target`.
+    fn with_selected_tracks(&mut self, target: SelectionTarget) {
+        let range = self
+            .visual_range(Focus::Browser)
+            .unwrap_or(self.browser_index..=self.browser_index);
+        let Some(items) = self.browser_items.get(range) else {
+            return;
+        };
+        let items: Vec<(FileType, PathBuf)> = items
+            .iter()
+            .map(|item| (item.file_type.clone(), item.path.clone()))
+            .collect();
+        let events = self.events.clone();
+        thread::spawn(move || {
+            let items = items
+                .into_iter()
+                .map(|(file_type, path)| {
+                    let tracks = match file_type {
+                        FileType::AudioFile => vec![path],
+                        FileType::Directory => list_tracks(&path).unwrap_or_else(|e| {
+                            log::warn(&format!("Could not list {}: {}", path.display(), e));
+                            Vec::new()
+                        }),
+                        FileType::Playlist => playlist::load(&path).unwrap_or_default(),
+                        FileType::Other => Vec::new(),
+                    };
+                    (file_type, tracks)
+                })
+                .collect();
+            let _ = events.send(Event::Loaded(Loaded::Selection { target, items }));
+        });
+    }
+
+    // The queue entry under the cursor, or the first of a group whose
+    // header is selected.
+    fn queue_cursor_entry(&self) -> Option<usize> {
+        let groups = self.queue_groups();
+        let rows = groups::rows(&groups, &self.queue_list.collapsed);
+        match self.queue_list.selected(&rows)? {
+            Row::Item(i) => Some(i),
+            Row::Header(g) => Some(groups[g].start),
+        }
+    }
+
+    fn visual_cursor(&self, focus: Focus) -> Option<usize> {
+        match focus {
+            Focus::Queue => self.queue_cursor_entry(),
+            Focus::Browser if self.view == View::Browser => Some(self.browser_index),
+            _ => None,
+        }
+    }
+
+    // The span between the visual anchor and the cursor, if a visual
+    // selection is open in `focus`.
+    pub fn visual_range(&self, focus: Focus) -> Option<RangeInclusive<usize>> {
+        let (visual_focus, anchor) = self.visual?;
+        if visual_focus != focus {
+            return None;
+        }
+        let cursor = self.visual_cursor(focus)?;
+        Some(anchor.min(cursor)..=anchor.max(cursor))
+    }
+
+    pub fn toggle_visual(&mut self) {
+        if self.visual.take().is_some() {
+            return;
+        }
+        if let Some(cursor) = self.visual_cursor(self.focus) {
+            self.visual = Some((self.focus, cursor));
+            self.set_status(tr!("status.visual"));
+        }
+    }
+
+    pub fn cancel_visual(&mut self) {
+        if self.visual.take().is_some() {
+            self.status = None;
+        }
+    }
+
+    pub fn remove_from_queue(&mut self) {
+        let Some(range) = self.visual_range(Focus::Queue).or_else(|| {
+            let entry = self.queue_cursor_entry()?;
+            Some(entry..=entry)
+        }) else {
+            return;
+        };
+        let count = range.clone().count();
+        self.queue_undo = Some((self.player.queue.clone(), self.player.queue_index));
+        self.visual = None;
+        let playback = self.player.remove_range(range);
+        self.after_playback(playback);
+        let rows = groups::rows(&self.queue_groups(), &self.queue_list.collapsed);
+        self.queue_list.clamp(rows.len());
+        self.set_status(tr!("status.queue_removed", count = count));
+    }
+
+    // Moves the visual selection, or the entry under the cursor, one place,
+    // taking the cursor along.
+    pub fn move_queue_selection(&mut self, down: bool) {
+        let Some(cursor) = self.queue_cursor_entry() else {
+            return;
+        };
+        let range = self.visual_range(Focus::Queue).unwrap_or(cursor..=cursor);
+        if !self.player.move_range(range, down) {
+            return;
+        }
+        let shift = |i: usize| if down { i + 1 } else { i - 1 };
+        if let Some((_, anchor)) = &mut self.visual {
+            *anchor = shift(*anchor);
+        }
+        let rows = groups::rows(&self.queue_groups(), &self.queue_list.collapsed);
+        if let Some(row) = rows.iter().position(|row| *row == Row::Item(shift(cursor))) {
+            self.queue_list.cursor = row;
+        }
+    }
+
+    pub fn queue_groups(&self) -> Vec<Group> {
+        groups::build(
+            self.player
+                .queue
+                .iter()
+                .map(|path| (path.as_path(), self.library.find(path))),
+        )
+    }
+
+    pub fn queue_next(&mut self) {
+        let rows = groups::rows(&self.queue_groups(), &self.queue_list.collapsed);
+        self.queue_list.next(rows.len());
+    }
+
+    pub fn queue_prev(&mut self) {
+        let rows = groups::rows(&self.queue_groups(), &self.queue_list.collapsed);
+        self.queue_list.prev(rows.len());
+    }
+
+    pub fn queue_enter(&mut self) {
+        let groups = self.queue_groups();
+        let rows = groups::rows(&groups, &self.queue_list.collapsed);
+
+        match self.queue_list.selected(&rows) {
+            Some(Row::Header(g)) => {
+                self.queue_list.toggle(&groups[g]);
+                let rows = groups::rows(&groups, &self.queue_list.collapsed);
+                self.queue_list.clamp(rows.len());
+            }
+            Some(Row::Item(i)) => {
+                let playback = self.player.play_index(i);
+                self.after_playback(playback);
+            }
+            None => {}
+        }
+    }
+
+    // Plays queue entry `number`, counting from 1, and moves the queue
+    // cursor onto it.
+    pub fn jump_to(&mut self, number: usize) {
+        if number == 0 || number > self.player.queue.len() {
+            self.set_status(tr!(
+                "status.jump_out_of_range",
+                number = number,
+                total = self.player.queue.len()
+            ));
+            return;
+        }
+        let index = number - 1;
+        let playback = self.player.play_index(index);
+        self.after_playback(playback);
+
+        let rows = groups::rows(&self.queue_groups(), &self.queue_list.collapsed);
+        if let Some(row) = rows.iter().position(|row| *row == Row::Item(index)) {
+            self.queue_list.cursor = row;
+        }
+    }
+
+    pub fn clear_queue(&mut self) {
+        if self.player.queue.is_empty() {
+            return;
+        }
+        self.queue_undo = Some((self.player.queue.clone(), self.player.queue_index));
+        self.player.clear();
+        self.lost_track = None;
+        self.queue_list.clamp(0);
+        self.set_status(tr!("status.queue_cleared"));
+    }
+
+    pub fn crop_queue(&mut self) {
+        if self.player.queue.len() < 2 {
+            return;
+        }
+        self.queue_undo = Some((self.player.queue.clone(), self.player.queue_index));
+        self.player.crop();
+        let rows = groups::rows(&self.queue_groups(), &self.queue_list.collapsed);
+        self.queue_list.clamp(rows.len());
+        self.set_status(tr!("status.queue_cropped"));
+    }
+
+    pub fn undo_queue(&mut self) {
+        let Some((queue, index)) = self.queue_undo.take() else {
+            self.set_status(tr!("status.nothing_to_undo"));
+            return;
+        };
+        self.player.restore_queue(queue, index);
+        self.set_status(tr!("status.queue_restored"));
+    }
+
+    pub fn open_queue_sort(&mut self) {
+        if self.player.queue.len() < 2 {
+            return;
+        }
+        let items = QueueOrder::ALL
+            .iter()
+            .map(|order| PickerItem {
+                detail: String::new(),
+                label: order.label(),
+            })
+            .collect();
+        self.modal = Some(Modal::picker(
+            tr!("queue.sort_title"),
+            items,
+            Purpose::SortQueue,
+        ));
+    }
+
+    pub fn sort_queue(&mut self, order: QueueOrder) {
+        self.queue_undo = Some((self.player.queue.clone(), self.player.queue_index));
+        let mut queue = self.player.queue.clone();
+        let tags = |path: &PathBuf| self.tags_of(path);
+        match order {
+            QueueOrder::Path => queue.sort(),
+            QueueOrder::Title => queue.sort_by_cached_key(|path| {
+                let tags = tags(path);
+                (tags.title.map(|title| title.to_lowercase()), path.clone())
+            }),
+            QueueOrder::Artist => queue.sort_by_cached_key(|path| {
+                let tags = tags(path);
+                (
+                    tags.artist.map(|artist| artist.to_lowercase()),
+                    tags.album.map(|album| album.to_lowercase()),
+                    tags.disc_number,
+                    tags.track_number,
+                    path.clone(),
+                )
+            }),
+            QueueOrder::Album => queue.sort_by_cached_key(|path| {
+                let tags = tags(path);
+                (
+                    tags.album.map(|album| album.to_lowercase()),
+                    tags.disc_number,
+                    tags.track_number,
+                    path.clone(),
+                )
+            }),
+            QueueOrder::Duration => queue.sort_by_cached_key(|path| tags(path).duration),
+            QueueOrder::Random => queue.shuffle(&mut rand::rng()),
+        }
+        self.player.reorder(queue);
+        self.set_status(tr!("status.queue_sorted", order = order.label()));
+    }
+
+    pub fn show_inbox(&mut self) {
+        self.view = View::Inbox;
+        self.focus = Focus::Browser;
+    }
+
+    pub fn add_selected_to_inbox(&mut self) {
+        if self.db.is_some() {
+            self.with_selected_tracks(SelectionTarget::Inbox);
+        }
+    }
+
+    fn add_to_inbox(&mut self, paths: Vec<PathBuf>) {
+        let Some(db) = &mut self.db else {
+            return;
+        };
+        match db.add_to_inbox(&paths) {
+            Ok(added) => {
+                self.inbox = db.inbox().unwrap_or_default();
+                self.set_status(tr!("status.inbox_added", count = added));
+            }
+            Err(e) => self.set_status(tr!("status.inbox_error", error = e)),
+        }
+    }
+
+    pub fn inbox_groups(&self) -> Vec<Group> {
+        groups::build(
+            self.inbox
+                .iter()
+                .map(|path| (path.as_path(), self.library.find(path))),
+        )
+    }
+
+    pub fn inbox_next(&mut self) {
+        let rows = groups::rows(&self.inbox_groups(), &self.inbox_list.collapsed);
+        self.inbox_list.next(rows.len());
+    }
+
+    pub fn inbox_prev(&mut self) {
+        let rows = groups::rows(&self.inbox_groups(), &self.inbox_list.collapsed);
+        self.inbox_list.prev(rows.len());
+    }
+
+    fn inbox_selected(&self) -> Option<Row> {
+        let rows = groups::rows(&self.inbox_groups(), &self.inbox_list.collapsed);
+        self.inbox_list.selected(&rows)
+    }
+
+    // Plays the inbox from the selected track, or folds a header.
+    pub fn inbox_enter(&mut self) {
+        match self.inbox_selected() {
+            Some(Row::Header(g)) => {
+                let groups = self.inbox_groups();
+                self.inbox_list.toggle(&groups[g]);
+                let rows = groups::rows(&groups, &self.inbox_list.collapsed);
+                self.inbox_list.clamp(rows.len());
+            }
+            Some(Row::Item(i)) => {
+                let playback = self.player.set_queue(self.inbox.clone(), i);
+                self.after_playback(playback);
+            }
+            None => {}
+        }
+    }
+
+    pub fn inbox_remove(&mut self) {
+        let Some(Row::Item(i)) = self.inbox_selected() else {
+            return;
+        };
+        let path = self.inbox[i].clone();
+        self.remove_from_inbox(&path);
+    }
+
+    fn finish_inbox_track(&mut self, path: &Path) {
+        if self.inbox.iter().any(|p| p == path) {
+            self.remove_from_inbox(path);
+        }
+    }
+
+    fn remove_from_inbox(&mut self, path: &Path) {
+        if let Some(db) = &self.db
+            && let Err(e) = db.remove_from_inbox(path)
+        {
+            self.set_status(tr!("status.inbox_error", error = e));
+            return;
+        }
+        self.inbox.retain(|p| p != path);
+        let rows = groups::rows(&self.inbox_groups(), &self.inbox_list.collapsed);
+        self.inbox_list.clamp(rows.len());
+    }
+
+    fn show_tracks(&mut self, title: String, paths: Vec<PathBuf>) {
+        self.track_list = TrackList {
+            title,
+            paths,
+            list: GroupedList::default(),
+        };
+        self.view = View::Tracks;
+        self.focus = Focus::Browser;
+    }
+
+    pub fn show_recently_added(&mut self) {
+        let since = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+            .saturating_sub(self.config.recent_days * 24 * 60 * 60);
+        let paths = self
+            .library
+            .added_since(since)
+            .into_iter()
+            .map(|track| track.path.clone())
+            .collect();
+        let title = tr!("tracks.recently_added", days = self.config.recent_days);
+        self.show_tracks(title, paths);
+    }
+
+    pub fn show_recently_played(&mut self) {
+        let Some(db) = &self.db else {
+            return;
+        };
+        match db.recently_played(RECENTLY_PLAYED_LIMIT) {
+            Ok(paths) => self.show_tracks(tr!("tracks.recently_played"), paths),
+            Err(e) => self.set_status(tr!("status.history_error", error = e)),
+        }
+    }
+
+    // The browser's selection, the selected queue or list entry, or else
+    // the playing track.
+    fn selected_item(&self) -> Option<PathBuf> {
+        match (self.focus, self.view) {
+            (Focus::Browser, View::Browser) => self
+                .browser_items
+                .get(self.browser_index)
+                .map(|item| item.path.clone()),
+            (Focus::Queue, _) => {
+                let groups = self.queue_groups();
+                let rows = groups::rows(&groups, &self.queue_list.collapsed);
+                match self.queue_list.selected(&rows) {
+                    Some(Row::Item(i)) => self.player.queue.get(i).cloned(),
+                    _ => None,
+                }
+            }
+            (Focus::Browser, View::Tracks) => {
+                let groups = self.track_groups();
+                let rows = groups::rows(&groups, &self.track_list.list.collapsed);
+                match self.track_list.list.selected(&rows) {
+                    Some(Row::Item(i)) => self.track_list.paths.get(i).cloned(),
+                    _ => None,
+                }
+            }
+            _ => self.current_path(),
+        }
+    }
+
+    pub fn open_with(&mut self) {
+        let Some(path) = self.selected_item().filter(|path| !remote::is_remote(path)) else {
+            return;
+        };
+        match self.config.open_command(&path) {
+            Some(command) => self.external = Some(External::program(command)),
+            None => self.set_status(tr!("status.open_with_unset")),
+        }
+    }
+
+    pub fn open_command_line(&mut self) {
+        self.modal = Some(Modal::input(
+            tr!("command.prompt"),
+            String::new(),
+            Purpose::CommandLine,
+        ));
+    }
+
+    fn run_command_line(&mut self, input: &str) {
+        match command_line::parse(input) {
+            Some(CommandLine::Shell(line)) if line.is_empty() => {
+                self.external = Some(External::shell())
+            }
+            Some(CommandLine::Shell(line)) => self.external = Some(External::shell_command(&line)),
+            Some(CommandLine::Jump(number)) => self.jump_to(number),
+            Some(CommandLine::Filter(pattern)) if pattern.is_empty() => self.clear_browser_filter(),
+            Some(CommandLine::Filter(pattern)) => match NameFilter::parse(&pattern) {
+                Ok(filter) => {
+                    self.browser_filter = Some(filter);
+                    self.apply_browser_filter(self.selected_browser_path());
+                    self.view = View::Browser;
+                    self.focus = Focus::Browser;
+                }
+                Err(e) => self.set_status(tr!("status.filter_error", error = e)),
+            },
+            None if input.trim().is_empty() => {}
+            None => self.set_status(tr!("command.unknown", command = input.trim())),
+        }
+    }
+
+    fn selected_browser_path(&self) -> Option<PathBuf> {
+        let item = self.browser_items.get(self.browser_index)?;
+        Some(item.path.clone())
+    }
+
+    // Shows the items the filter lets through, keeping `selected` selected
+    // if it is still listed.
+    fn apply_browser_filter(&mut self, selected: Option<PathBuf>) {
+        self.browser_items = match &self.browser_filter {
+            Some(filter) => self
+                .browser_unfiltered
+                .iter()
+                .filter(|item| filter.matches(&item.name))
+                .cloned()
+                .collect(),
+            None => self.browser_unfiltered.clone(),
+        };
+        self.browser_index = selected
+            .and_then(|path| self.browser_items.iter().position(|item| item.path == path))
+            .unwrap_or(self.browser_index)
+            .min(self.browser_items.len().saturating_sub(1));
+        self.update_folder_summary();
+    }
+
+    pub fn clear_browser_filter(&mut self) {
+        if self.browser_filter.take().is_some() {
+            self.apply_browser_filter(self.selected_browser_path());
+        }
+    }
+
+    pub fn external_finished(&mut self, result: Result<()>) {
+        if let Err(e) = result {
+            self.set_status(tr!("status.external_error", error = e));
+        }
+        // The program may have moved, renamed or retagged things.
+        self.folder_summaries.clear();
+        self.reload_directory();
+    }
+
+    pub fn open_rename_prompt(&mut self) {
+        let Some(dir) = self
+            .selected_directory()
+            .filter(|dir| !remote::is_remote(dir))
+        else {
+            return;
+        };
+        self.modal = Some(Modal::input(
+            tr!("rename.prompt"),
+            self.config.rename_pattern.clone(),
+            Purpose::RenamePattern {
+                dir: dir.to_path_buf(),
+                dest: dir.to_path_buf(),
+            },
+        ));
+    }
+
+    // Reading every file's tags can take a while, so the plan is made on a
+    // worker thread and shown when it arrives.
+    fn plan_rename(&mut self, dir: PathBuf, dest: PathBuf, pattern: String) {
+        self.set_status(tr!("rename.planning"));
+        let known: HashMap<PathBuf, Tags> = self
+            .library
+            .tracks
+            .iter()
+            .filter(|track| track.path.starts_with(&dir))
+            .map(|track| (track.path.clone(), track.tags.clone()))
+            .collect();
+        let events = self.events.clone();
+        thread::spawn(move || {
+            let renames = rename::plan_into(&dir, &dest, &pattern, &known);
+            let _ = events.send(Event::Loaded(Loaded::Renames { dir, renames }));
+        });
+    }
+
+    pub fn rename_next(&mut self) {
+        if let Some(preview) = &mut self.rename
+            && !preview.renames.is_empty()
+        {
+            preview.index = (preview.index + 1) % preview.renames.len();
+        }
+    }
+
+    pub fn rename_prev(&mut self) {
+        if let Some(preview) = &mut self.rename
+            && !preview.renames.is_empty()
+        {
+            preview.index = preview
+                .index
+                .checked_sub(1)
+                .unwrap_or(preview.renames.len() - 1);
+        }
+    }
+
+    // Moving into the library may mean copying whole albums to another
+    // disk, so it happens on a worker thread.
+    pub fn apply_rename(&mut self) {
+        let Some(preview) = self.rename.take() else {
+            return;
+        };
+        self.set_status(tr!("rename.applying"));
+        self.view = View::Browser;
+        let events = self.events.clone();
+        thread::spawn(move || {
+            let applied = rename::apply(&preview.renames);
+            // Moved somewhere else, such as from the downloads into the library.
+            let elsewhere = preview.renames.iter().any(|rename| {
+                rename.outcome == Outcome::Rename && !rename.to.starts_with(&preview.dir)
+            });
+            let _ = events.send(Event::Loaded(Loaded::Renamed {
+                dir: preview.dir,
+                applied,
+                elsewhere,
+            }));
+        });
+    }
+
+    fn on_renamed(&mut self, dir: PathBuf, applied: Applied, elsewhere: bool) {
+        self.follow_moves(&applied.moved);
+        let count = applied.moved.len();
+        match applied.error {
+            None => self.set_status(tr!("rename.done", count = count)),
+            Some(e) => self.set_status(tr!("rename.error", count = count, error = e)),
+        }
+        self.folder_summaries.remove(&dir);
+        self.reload_directory();
+        if elsewhere {
+            self.scan_library();
+            self.check_arrivals();
+        }
+    }
+
+    // Rereads the downloads folder on a worker thread, since it may be big
+    // or on a slow disk.
+    fn check_arrivals(&mut self) {
+        self.arrivals.ticks = 0;
+        let Some(downloads) = self.config.downloads_dir.clone() else {
+            return;
+        };
+        if self.arrivals.checking {
+            return;
+        }
+        self.arrivals.checking = true;
+        let events = self.events.clone();
+        thread::spawn(move || {
+            let list = arrivals::scan(&downloads);
+            let _ = events.send(Event::Loaded(Loaded::Arrivals(list)));
+        });
+    }
+
+    pub fn show_arrivals(&mut self) {
+        if self.config.downloads_dir.is_none() {
+            self.set_status(tr!("arrivals.no_downloads"));
+            return;
+        }
+        self.check_arrivals();
+        self.view = View::Arrivals;
+        self.focus = Focus::Browser;
+    }
+
+    pub fn arrivals_next(&mut self) {
+        if !self.arrivals.list.is_empty() {
+            self.arrivals.index = (self.arrivals.index + 1) % self.arrivals.list.len();
+        }
+    }
+
+    pub fn arrivals_prev(&mut self) {
+        if !self.arrivals.list.is_empty() {
+            self.arrivals.index = self
+                .arrivals
+                .index
+                .checked_sub(1)
+                .unwrap_or(self.arrivals.list.len() - 1);
+        }
+    }
+
+    // Opens the download in the browser, to listen before keeping it.
+    pub fn arrivals_enter(&mut self) {
+        let Some(arrival) = self.arrivals.list.get(self.arrivals.index) else {
+            return;
+        };
+        let dir = arrival.dir.clone();
+        self.view = View::Browser;
+        self.load_directory(&dir);
+    }
+
+    // Asks for the pattern, then previews the move into the library like
+    // any other rename.
+    pub fn move_arrival(&mut self) {
+        let Some(arrival) = self.arrivals.list.get(self.arrivals.index) else {
+            return;
+        };
+        self.modal = Some(Modal::input(
+            tr!("arrivals.move_prompt"),
+            self.config.rename_pattern.clone(),
+            Purpose::RenamePattern {
+                dir: arrival.dir.clone(),
+                dest: self.library.root.clone(),
+            },
+        ));
+    }
+
+    // Points everything that knew a moved file by its path at the new one:
+    // its database rows, the queue, the library and the inbox.
+    fn follow_moves(&mut self, moved: &[(PathBuf, PathBuf)]) {
+        if moved.is_empty() {
+            return;
+        }
+        if let Some(db) = &mut self.db {
+            for (from, to) in moved {
+                if let Err(e) = db.move_path(from, to) {
+                    log::warn(&format!(
+                        "Could not move {} in the database: {}",
+                        from.display(),
+                        e
+                    ));
+                }
+            }
+            self.inbox = db.inbox().unwrap_or_default();
+        }
+        let renamed: HashMap<&PathBuf, &PathBuf> =
+            moved.iter().map(|(from, to)| (from, to)).collect();
+        for path in &mut self.player.queue {
+            if let Some(to) = renamed.get(path) {
+                *path = (*to).clone();
+            }
+        }
+        let mut tracks = std::mem::take(&mut self.library.tracks);
+        for track in &mut tracks {
+            if let Some(to) = renamed.get(&track.path) {
+                track.path = (*to).clone();
+            }
+        }
+        self.library.set_tracks(tracks);
+    }
+
+    pub fn show_charts(&mut self) {
+        self.refresh_charts();
+        self.view = View::Charts;
+        self.focus = Focus::Browser;
+    }
+
+    fn refresh_charts(&mut self) {
+        let Some(db) = &self.db else {
+            return;
+        };
+        match db.play_counts_since(self.charts.window.since()) {
+            Ok(counts) => {
+                self.charts.entries = charts::build(&counts, &self.library, self.charts.kind);
+                self.charts.index = 0;
+            }
+            Err(e) => self.set_status(tr!("status.history_error", error = e)),
+        }
+    }
+
+    pub fn charts_next_kind(&mut self) {
+        self.charts.kind = self.charts.kind.next();
+        self.refresh_charts();
+    }
+
+    pub fn charts_next_window(&mut self) {
+        self.charts.window = self.charts.window.next();
+        self.refresh_charts();
+    }
+
+    pub fn charts_next(&mut self) {
+        if !self.charts.entries.is_empty() {
+            self.charts.index = (self.charts.index + 1) % self.charts.entries.len();
+        }
+    }
+
+    pub fn charts_prev(&mut self) {
+        if !self.charts.entries.is_empty() {
+            self.charts.index = self
+                .charts
+                .index
+                .checked_sub(1)
+                .unwrap_or(self.charts.entries.len() - 1);
+        }
+    }
+
+    // Plays the selected track, or the played tracks of an artist or album
+    // in album order.
+    pub fn charts_enter(&mut self) {
+        let Some(entry) = self.charts.entries.get(self.charts.index) else {
+            return;
+        };
+        let mut paths = entry.paths.clone();
+        if self.charts.kind != ChartKind::Tracks {
+            self.library
+                .sort_album_order(&mut paths, self.player.sandbox.is_none());
+        }
+        let playback = self.player.set_queue(paths, 0);
+        self.after_playback(playback);
+    }
+
+    pub fn track_groups(&self) -> Vec<Group> {
+        groups::build(
+            self.track_list
+                .paths
+                .iter()
+                .map(|path| (path.as_path(), self.library.find(path))),
+        )
+    }
+
+    pub fn tracks_next(&mut self) {
+        let rows = groups::rows(&self.track_groups(), &self.track_list.list.collapsed);
+        self.track_list.list.next(rows.len());
+    }
+
+    pub fn tracks_prev(&mut self) {
+        let rows = groups::rows(&self.track_groups(), &self.track_list.list.collapsed);
+        self.track_list.list.prev(rows.len());
+    }
+
+    pub fn tracks_enter(&mut self) {
+        let groups = self.track_groups();
+        let rows = groups::rows(&groups, &self.track_list.list.collapsed);
+
+        match self.track_list.list.selected(&rows) {
+            Some(Row::Header(g)) => {
+                self.track_list.list.toggle(&groups[g]);
+                let rows = groups::rows(&groups, &self.track_list.list.collapsed);
+                self.track_list.list.clamp(rows.len());
+            }
+            Some(Row::Item(i)) => {
+                let playback = self.player.set_queue(self.track_list.paths.clone(), i);
+                self.after_playback(playback);
+            }
+            None => {}
+        }
+    }
+
+    fn queue_row_at(&self, column: u16, row: u16) -> Option<usize> {
+        let (area, offset) = self.queue_layout.get();
+        let inside = area.contains(Position::new(column, row));
+        inside.then(|| offset + (row - area.y) as usize)
+    }
+
+    pub fn on_drag(&mut self, event: DragEvent) {
+        self.note_input();
+        if self.focus != Focus::Queue {
+            return;
+        }
+
+        let groups = self.queue_groups();
+        let rows = groups::rows(&groups, &self.queue_list.collapsed);
+
+        match event {
+            DragEvent::Click { column, row } => {
+                if let Some(row) = self.queue_row_at(column, row)
+                    && row < rows.len()
+                {
+                    self.queue_list.cursor = row;
+                }
+            }
+            DragEvent::Start { column, row } => {
+                self.queue_drag = self
+                    .queue_row_at(column, row)
+                    .and_then(|r| match rows.get(r)? {
+                        Row::Item(i) => Some(*i),
+                        Row::Header(_) => None,
+                    })
+                    .map(|from| QueueDrag { from, hover: row });
+            }
+            DragEvent::Move { row, .. } => {
+                if let Some(drag) = &mut self.queue_drag {
+                    drag.hover = row;
+                }
+            }
+            DragEvent::Drop { column, row } => {
+                let Some(drag) = self.queue_drag.take() else {
+                    return;
+                };
+                // Dropping onto a header places the entry at the start of that
+                // group, which is one earlier once the entry has left from above.
+                let to = match self.queue_row_at(column, row).and_then(|r| rows.get(r)) {
+                    Some(Row::Item(i)) => *i,
+                    Some(Row::Header(g)) if drag.from < groups[*g].start => groups[*g].start - 1,
+                    Some(Row::Header(g)) => groups[*g].start,
+                    None => self.player.queue.len().saturating_sub(1),
+                };
+                self.player.move_entry(drag.from, to);
+            }
+        }
+    }
+
+    fn current_path(&self) -> Option<PathBuf> {
+        self.player.current().map(Path::to_path_buf)
+    }
+
+    pub fn start_cue_prompt(&mut self) {
+        if self.current_path().is_none() {
+            return;
+        }
+        let position = self.player.elapsed;
+        self.modal = Some(Modal::input(
+            tr!("cue.prompt", position = format_position(position)),
+            String::new(),
+            Purpose::CueName { position },
+        ));
+    }
+
+    pub fn open_cue_picker(&mut self) {
+        let (Some(path), Some(db)) = (self.current_path(), &self.db) else {
+            return;
+        };
+        match db.cues(&path) {
+            Ok(cues) if cues.is_empty() => self.set_status(tr!("status.no_cues")),
+            Ok(cues) => {
+                let items = cues
+                    .iter()
+                    .map(|cue| PickerItem {
+                        detail: format_position(cue.position),
+                        label: cue.name.clone(),
+                    })
+                    .collect();
+                self.modal = Some(Modal::picker(
+                    tr!("cue.picker_title"),
+                    items,
+                    Purpose::JumpToCue { cues },
+                ));
+            }
+            Err(e) => self.set_status(tr!("status.cue_load_error", error = e)),
+        }
+    }
+
+    // Leaving the first-run setup early still writes a config, with the
+    // defaults for whatever wasn't asked yet, so it isn't asked again.
+    fn close_modal(&mut self) {
+        match self.modal.take().map(|modal| modal.purpose) {
+            Some(Purpose::SetupMusicDir) => self.finish_setup(Setup::default()),
+            Some(Purpose::SetupPalette(setup) | Purpose::SetupMediaKeys(setup)) => {
+                self.finish_setup(setup)
+            }
+            _ => {}
+        }
+    }
+
+    // First launch, with no config file yet: asks for the basics instead
+    // of leaving a new user in whatever folder leek guessed.
+    fn start_setup(&mut self) {
+        self.modal = Some(Modal::input(
+            tr!("setup.music_dir"),
+            self.current_directory.display().to_string(),
+            Purpose::SetupMusicDir,
+        ));
+    }
+
+    fn setup_music_dir(&mut self, input: &str) {
+        let dir = PathBuf::from(input.trim());
+        if !dir.is_dir() {
+            self.set_status(tr!("setup.not_a_folder", path = dir.display()));
+            self.modal = Some(Modal::input(
+                tr!("setup.music_dir"),
+                input.to_string(),
+                Purpose::SetupMusicDir,
+            ));
+            return;
+        }
+        let items = Palette::ALL
+            .iter()
+            .map(|palette| PickerItem {
+                detail: String::new(),
+                label: palette.label(),
+            })
+            .collect();
+        self.modal = Some(Modal::picker(
+            tr!("setup.palette"),
+            items,
+            Purpose::SetupPalette(Setup {
+                music_dir: Some(dir),
+                ..Setup::default()
+            }),
+        ));
+    }
+
+    fn setup_palette(&mut self, mut setup: Setup, index: usize) {
+        setup.palette = Palette::ALL.get(index).copied().unwrap_or_default();
+        // Shown right away, to judge it by.
+        self.config.palette = setup.palette;
+        let items = [tr!("setup.no"), tr!("setup.yes")]
+            .into_iter()
+            .map(|label| PickerItem {
+                detail: String::new(),
+                label,
+            })
+            .collect();
+        self.modal = Some(Modal::picker(
+            tr!("setup.media_keys"),
+            items,
+            Purpose::SetupMediaKeys(setup),
+        ));
+    }
+
+    fn finish_setup(&mut self, setup: Setup) {
+        match setup.write() {
+            Ok(path) => self.set_status(tr!("setup.done", path = path.display())),
+            Err(e) => self.set_status(tr!("setup.error", error = e)),
+        }
+        self.config_modified = Config::modified();
+        self.config.palette = setup.palette;
+        self.config.media_keys = setup.media_keys;
+        self.update_media_keys();
+        if let Some(dir) = setup.music_dir {
+            self.library.root = dir.clone();
+            self.load_directory(&dir);
+            self.config.music_dir = Some(dir);
+        }
+    }
+
+    pub fn submit_modal(&mut self) {
+        let Some(modal) = self.modal.take() else {
+            return;
+        };
+        match (modal.kind, modal.purpose) {
+            (ModalKind::Input { input }, Purpose::CommandLine) => self.run_command_line(&input),
+            (ModalKind::Palette { index, .. }, Purpose::Palette { results }) => {
+                if let Some(path) = results.get(index) {
+                    let playback = self.player.play_now(path.clone());
+                    self.after_playback(playback);
+                }
+            }
+            (ModalKind::Input { input }, Purpose::RenamePattern { dir, dest }) => {
+                self.plan_rename(dir, dest, input)
+            }
+            (ModalKind::Input { input }, Purpose::SetupMusicDir) => self.setup_music_dir(&input),
+            (ModalKind::Picker { index, .. }, Purpose::SetupPalette(setup)) => {
+                self.setup_palette(setup, index)
+            }
+            (ModalKind::Picker { index, .. }, Purpose::SetupMediaKeys(mut setup)) => {
+                setup.media_keys = index == 1;
+                self.finish_setup(setup);
+            }
+            (ModalKind::Input { input }, Purpose::CueName { position }) => {
+                self.save_cue(position, &input)
+            }
+            (ModalKind::Picker { index, .. }, Purpose::JumpToCue { cues }) => {
+                if let Some(cue) = cues.get(index) {
+                    self.seek_to(cue.position);
+                    self.set_status(tr!("status.cue_jumped", name = cue.name));
+                }
+            }
+            (ModalKind::Confirm { .. }, Purpose::DiscardPlaylist) => {
+                self.close_playlist_editor(true)
+            }
+            (ModalKind::Picker { index, .. }, Purpose::SortQueue) => {
+                if let Some(&order) = QueueOrder::ALL.get(index) {
+                    self.sort_queue(order);
+                }
+            }
+            (ModalKind::Confirm { .. }, Purpose::StillListening) if !self.player.is_playing => {
+                self.toggle_play()
+            }
+            _ => {}
+        }
+    }
+
+    fn modal_delete(&mut self) {
+        let Some(modal) = &mut self.modal else {
+            return;
+        };
+        if !matches!(modal.purpose, Purpose::JumpToCue { .. }) {
+            return;
+        }
+        let Some(index) = modal.remove_selected() else {
+            return;
+        };
+        let Purpose::JumpToCue { cues } = &mut modal.purpose else {
+            return;
+        };
+        let cue = cues.remove(index);
+        if modal.is_empty() {
+            self.modal = None;
+        }
+
+        if let Some(db) = &self.db
+            && let Err(e) = db.delete_cue(cue.id)
+        {
+            self.set_status(tr!("status.cue_delete_error", error = e));
+        }
+        self.cue_marks.retain(|&position| position != cue.position);
+    }
+
+    // The playing track's cue points, marked on the progress bar.
+    fn load_cue_marks(&mut self, path: &Path) {
+        self.cue_marks = self
+            .db
+            .as_ref()
+            .and_then(|db| db.cues(path).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|cue| cue.position)
+            .collect();
+    }
+
+    fn save_cue(&mut self, position: Duration, input: &str) {
+        let (Some(path), Some(db)) = (self.current_path(), &self.db) else {
+            return;
+        };
+
+        let name = if input.trim().is_empty() {
+            tr!("cue.default_name", position = format_position(position))
+        } else {
+            input.trim().to_string()
+        };
+        let message = match db.add_cue(&path, position, &name) {
+            Ok(()) => tr!("status.cue_saved", name = name),
+            Err(e) => tr!("status.cue_save_error", error = e),
+        };
+        self.set_status(message);
+        self.load_cue_marks(&path);
+    }
+}
+
+// The audio files in a folder and its subfolders, local or remote.
+fn list_tracks(dir: &Path) -> Result<Vec<PathBuf>> {
+    if remote::is_remote(dir) {
+        return remote::audio_files(dir);
+    }
+    fs::read_dir(dir)?;
+    Ok(library::audio_files(dir))
+}
+
+// Library tracks matching a search query, by index, in library order.
+fn search_library<'a>(
+    library: &'a Library,
+    db: Option<&Database>,
+    input: &str,
+) -> impl Iterator<Item = usize> + 'a {
+    let query = Query::parse(input);
+    let stats = match db {
+        Some(db) if query.needs_stats() => db.track_stats().unwrap_or_default(),
+        _ => HashMap::new(),
+    };
+    let no_stats = Stats::default();
+    library
+        .tracks
+        .iter()
+        .enumerate()
+        .filter(move |(_, track)| query.matches(track, stats.get(&track.path).unwrap_or(&no_stats)))
+        .map(|(i, _)| i)
+}
+
+fn track_label(track: &Track) -> String {
+    let tags = &track.tags;
+    match (&tags.artist, &tags.title) {
+        (Some(artist), Some(title)) => format!("{} - {}", artist, title),
+        (None, Some(title)) => title.clone(),
+        _ => track
+            .path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+    }
+}
+
+pub fn format_position(position: Duration) -> String {
+    let secs = position.as_secs();
+    format!("{}:{:02}", secs / 60, secs % 60)
 }
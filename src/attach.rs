@@ -0,0 +1,195 @@
+use anyhow::{Result, anyhow};
+use crossterm::{
+    event::{self, Event as CEvent, KeyCode, KeyEventKind},
+    execute,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+};
+use ratatui::{
+    Frame, Terminal,
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, LineGauge, Paragraph},
+};
+use serde_json::Value;
+use std::io::{self, BufRead, BufReader, Stdout, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use crate::app::format_position;
+use crate::config::{Config, Theme};
+use crate::i18n::tr;
+use crate::ipc;
+
+// How long to wait for a key before looking for a new status.
+const POLL: Duration = Duration::from_millis(100);
+
+// A thin client for a leek running elsewhere, such as one with the silent
+// output on a machine by the speakers: it draws what the control socket's
+// `watch` stream says and sends keys back as commands. Any number can be
+// attached at once, and detaching leaves the player running.
+pub fn run() -> Result<()> {
+    let (mut control, watch) = ipc::attach()?;
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(watch).lines().map_while(Result::ok) {
+            if let Ok(status) = serde_json::from_str::<Value>(&line)
+                && tx.send(status).is_err()
+            {
+                return;
+            }
+        }
+    });
+    let theme = Config::load().unwrap_or_default().theme().clone();
+
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+    let result = attached(&mut terminal, &mut control, &rx, &theme);
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    result
+}
+
+fn attached(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    control: &mut impl Write,
+    statuses: &Receiver<Value>,
+    theme: &Theme,
+) -> Result<()> {
+    let mut status = Value::Null;
+    loop {
+        loop {
+            match statuses.try_recv() {
+                Ok(latest) => status = latest,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Err(anyhow!("leek stopped")),
+            }
+        }
+        terminal.draw(|f| draw(f, &status, theme))?;
+
+        if !event::poll(POLL)? {
+            continue;
+        }
+        let CEvent::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let command = match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char(' ') => "play-pause",
+            KeyCode::Char('n') => "next",
+            KeyCode::Char('p') => "previous",
+            KeyCode::Char('+') | KeyCode::Char('=') | KeyCode::Up => "volume-up",
+            KeyCode::Char('-') | KeyCode::Down => "volume-down",
+            KeyCode::Right => "seek-forward",
+            KeyCode::Left => "seek-back",
+            KeyCode::Char('d') => "duck",
+            KeyCode::Char('u') => "unduck",
+            _ => continue,
+        };
+        writeln!(control, "{}", command)?;
+    }
+}
+
+fn draw(f: &mut Frame, status: &Value, theme: &Theme) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(tr!("attach.title"))
+        .border_style(Style::default().fg(theme.accent));
+    let area = block.inner(f.area());
+    f.render_widget(block, f.area());
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(4),
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .split(area);
+
+    let text = |key: &str| status[key].as_str().unwrap_or_default().to_string();
+    let seconds = |key: &str| status[key].as_u64().map(Duration::from_secs);
+    let title = match status["title"].as_str() {
+        Some(title) => title.to_string(),
+        None => status["path"]
+            .as_str()
+            .and_then(|path| Path::new(path).file_name())
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+    };
+    let state = match status["state"].as_str() {
+        Some("playing") => tr!("info.playing"),
+        Some("paused") => tr!("info.paused"),
+        Some(_) => tr!("attach.stopped"),
+        None => tr!("attach.waiting"),
+    };
+    let track = Paragraph::new(vec![
+        Line::from(Span::styled(
+            title,
+            Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(Span::styled(
+            text("artist"),
+            Style::default().fg(theme.secondary),
+        )),
+        Line::from(Span::styled(
+            text("album"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(state, Style::default().fg(theme.status))),
+    ]);
+    f.render_widget(track, chunks[0]);
+
+    let elapsed = seconds("elapsed").unwrap_or_default();
+    let duration = seconds("duration");
+    let ratio = match duration {
+        Some(duration) if !duration.is_zero() => {
+            (elapsed.as_secs_f64() / duration.as_secs_f64()).clamp(0.0, 1.0)
+        }
+        _ => 0.0,
+    };
+    let label = match duration {
+        Some(duration) => format!(
+            "{} / {}",
+            format_position(elapsed),
+            format_position(duration)
+        ),
+        None => format_position(elapsed),
+    };
+    let progress = LineGauge::default()
+        .filled_style(Style::default().fg(theme.accent))
+        .unfilled_style(Style::default().fg(theme.muted))
+        .ratio(ratio)
+        .label(label);
+    f.render_widget(progress, chunks[1]);
+
+    let queue = tr!(
+        "attach.queue",
+        position = status["position"].as_u64().unwrap_or_default(),
+        length = status["queue_length"].as_u64().unwrap_or_default(),
+        volume = status["volume"].as_u64().unwrap_or_default()
+    );
+    f.render_widget(
+        Paragraph::new(Span::styled(queue, Style::default().fg(theme.muted))),
+        chunks[2],
+    );
+    f.render_widget(
+        Paragraph::new(Span::styled(
+            tr!("attach.keys"),
+            Style::default().fg(theme.muted),
+        )),
+        chunks[4],
+    );
+}
@@ -0,0 +1,151 @@
+use anyhow::{Result, bail};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use leek_core::decode;
+use leek_core::library::{self, Scan, Track};
+use leek_core::tags;
+
+const USAGE: &str = "usage: leek bench scan|tags|decode|all <folder> [--threads N] [--limit N]";
+// Decoding is far slower than reading tags, so only the first few files
+// are decoded unless `--limit` asks for more.
+const DECODE_LIMIT: usize = 20;
+
+struct Options {
+    dir: PathBuf,
+    threads: Option<usize>,
+    limit: Option<usize>,
+}
+
+// Times the scanner, the tag reader and the decoders on a folder, for
+// tuning the number of scan threads and seeing what the track cache saves.
+pub fn run(args: &[String]) -> Result<()> {
+    let Some((what, rest)) = args.split_first() else {
+        bail!(USAGE);
+    };
+    let mut dir = None;
+    let mut threads = None;
+    let mut limit = None;
+    let mut rest = rest.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--threads" => threads = rest.next().and_then(|n| n.parse().ok()),
+            "--limit" => limit = rest.next().and_then(|n| n.parse().ok()),
+            _ => dir = Some(PathBuf::from(arg)),
+        }
+    }
+    let Some(dir) = dir else {
+        bail!(USAGE);
+    };
+    let options = Options {
+        dir,
+        threads,
+        limit,
+    };
+
+    match what.as_str() {
+        "scan" => scan(&options),
+        "tags" => read_tags(&options),
+        "decode" => decode(&options),
+        "all" => {
+            scan(&options);
+            read_tags(&options);
+            decode(&options);
+        }
+        _ => bail!(USAGE),
+    }
+    Ok(())
+}
+
+fn per_second(count: f64, elapsed: Duration) -> f64 {
+    count / elapsed.as_secs_f64().max(0.001)
+}
+
+fn files(options: &Options, default_limit: usize) -> Vec<PathBuf> {
+    let mut files = library::audio_files(&options.dir);
+    files.truncate(options.limit.unwrap_or(default_limit));
+    files
+}
+
+fn run_scan(dir: &Path, previous: &[Track], workers: usize) -> (Vec<Track>, usize, Duration) {
+    let started = Instant::now();
+    let mut scan = Scan::with_workers(dir.to_path_buf(), previous, workers);
+    while !scan.poll() {
+        thread::sleep(Duration::from_millis(5));
+    }
+    let reused = scan.reused();
+    (scan.finish(), reused, started.elapsed())
+}
+
+// A cold scan reads every file's tags; the warm one after it reuses them
+// all, the way a rescan does with the database's copy.
+fn scan(options: &Options) {
+    let workers = options.threads.unwrap_or_else(|| {
+        thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(4)
+    });
+    println!("Scan ({} threads)", workers);
+    let (tracks, _, cold) = run_scan(&options.dir, &[], workers);
+    println!(
+        "  cold: {} files in {:.2}s, {:.0} files/s",
+        tracks.len(),
+        cold.as_secs_f64(),
+        per_second(tracks.len() as f64, cold)
+    );
+    let (rescanned, reused, warm) = run_scan(&options.dir, &tracks, workers);
+    println!(
+        "  warm: {} files in {:.2}s, {:.0} files/s, {} from the cache",
+        rescanned.len(),
+        warm.as_secs_f64(),
+        per_second(rescanned.len() as f64, warm),
+        reused
+    );
+}
+
+// One thread, so it measures the tag reader rather than the disk's
+// parallelism.
+fn read_tags(options: &Options) {
+    let files = files(options, usize::MAX);
+    let started = Instant::now();
+    let failed = files
+        .iter()
+        .filter(|path| tags::read(path).is_err())
+        .count();
+    let elapsed = started.elapsed();
+    println!("Tags");
+    println!(
+        "  {} files in {:.2}s, {:.0} files/s, {} failed",
+        files.len(),
+        elapsed.as_secs_f64(),
+        per_second(files.len() as f64, elapsed),
+        failed
+    );
+}
+
+fn decode(options: &Options) {
+    let files = files(options, DECODE_LIMIT);
+    let started = Instant::now();
+    let mut audio = Duration::ZERO;
+    let mut failed = 0;
+    for path in &files {
+        match decode::decode_all(path) {
+            Ok(length) => audio += length,
+            Err(e) => {
+                eprintln!("  {}: {}", path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+    let elapsed = started.elapsed();
+    println!("Decode");
+    println!(
+        "  {} files, {:.0}s of audio in {:.2}s, {:.0}x real time, {} failed",
+        files.len(),
+        audio.as_secs_f64(),
+        elapsed.as_secs_f64(),
+        per_second(audio.as_secs_f64(), elapsed),
+        failed
+    );
+}
@@ -0,0 +1,27 @@
+// Commands typed after `:`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CommandLine {
+    // `!command`, or a bare `!` for an interactive shell.
+    Shell(String),
+    // `jump N`: play queue entry N, counting from 1.
+    Jump(usize),
+    // `filter PATTERN` narrows the browser; a bare `filter` clears it.
+    Filter(String),
+}
+
+pub fn parse(input: &str) -> Option<CommandLine> {
+    let input = input.trim();
+    if let Some(line) = input.strip_prefix('!') {
+        return Some(CommandLine::Shell(line.trim().to_string()));
+    }
+    if let Some(pattern) = input.strip_prefix("filter")
+        && (pattern.is_empty() || pattern.starts_with(' '))
+    {
+        return Some(CommandLine::Filter(pattern.trim().to_string()));
+    }
+    let mut words = input.split_whitespace();
+    match (words.next()?, words.next(), words.next()) {
+        ("jump", Some(number), None) => number.parse().ok().map(CommandLine::Jump),
+        _ => None,
+    }
+}
@@ -0,0 +1,389 @@
+use anyhow::{Result, bail};
+use serde_json::json;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use leek_core::db::Database;
+use leek_core::fingerprint;
+use leek_core::itunes::{self, PrefixMap};
+use leek_core::library;
+use leek_core::paths;
+use leek_core::playlist;
+use leek_core::remote;
+use leek_core::sandbox;
+use leek_core::sync;
+use leek_core::tags;
+
+#[cfg(unix)]
+use crate::attach;
+use crate::bench;
+use crate::completions;
+use crate::config::Config;
+use crate::ipc;
+
+#[cfg(feature = "acoustid")]
+const IDENTIFY_MATCHES: usize = 3;
+
+// Runs a non-interactive subcommand if one was given. Returns None when
+// the arguments should start the TUI instead.
+pub fn run(args: &[String]) -> Option<Result<()>> {
+    let command = args.get(1)?;
+    let rest = &args[2..];
+    match command.as_str() {
+        "export-history" => Some(export_history(rest)),
+        "import-itunes" => Some(import_itunes(rest)),
+        "write-tags" => Some(write_tags(rest)),
+        "identify" => Some(identify(rest)),
+        "duplicates" => Some(duplicates(rest)),
+        "sync" => Some(sync(rest)),
+        "decode-child" => Some(decode_child(rest)),
+        "bench" => Some(bench::run(rest)),
+        "completions" => Some(completions::run(rest)),
+        "duck" | "unduck" | "play-pause" | "next" | "previous" | "volume-up" | "volume-down"
+        | "seek-forward" | "seek-back" | "status" | "watch" => Some(ipc::send(command)),
+        #[cfg(unix)]
+        "attach" => Some(attach::run()),
+        #[cfg(not(unix))]
+        "attach" => Some(Err(anyhow::anyhow!("attaching needs a Unix system"))),
+        _ => None,
+    }
+}
+
+fn export_history(args: &[String]) -> Result<()> {
+    let mut format = None;
+    let mut stats = false;
+    let mut output = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => format = args.next().cloned(),
+            "--stats" => stats = true,
+            _ => output = Some(arg.clone()),
+        }
+    }
+
+    let Some(output) = output else {
+        bail!("usage: leek export-history [--format csv|json] [--stats] <output>");
+    };
+    let output = Path::new(&output);
+    let format = format
+        .or_else(|| {
+            output
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+        })
+        .unwrap_or_else(|| String::from("csv"));
+
+    let db = Database::open()?;
+    let contents = match (format.as_str(), stats) {
+        ("csv", false) => history_csv(&db)?,
+        ("csv", true) => stats_csv(&db)?,
+        ("json", _) => history_json(&db)?,
+        (other, _) => bail!("unknown export format: {}", other),
+    };
+
+    fs::write(output, contents)?;
+    println!("Exported play history to {}", output.display());
+    Ok(())
+}
+
+fn import_itunes(args: &[String]) -> Result<()> {
+    let mut library = None;
+    let mut out_dir = None;
+    let mut maps = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => out_dir = args.next().map(PathBuf::from),
+            "--map" => {
+                let spec = args.next().map(String::as_str).unwrap_or_default();
+                match PrefixMap::parse(spec) {
+                    Some(map) => maps.push(map),
+                    None => bail!("--map expects FROM=TO, got '{}'", spec),
+                }
+            }
+            _ => library = Some(PathBuf::from(arg)),
+        }
+    }
+
+    let Some(library) = library else {
+        bail!("usage: leek import-itunes <Library.xml> [--out DIR] [--map FROM=TO]...");
+    };
+    let Some(out_dir) = out_dir.or_else(paths::playlists_dir) else {
+        bail!("no playlist directory available, pass --out");
+    };
+    fs::create_dir_all(&out_dir)?;
+
+    for imported in itunes::import(&library, &maps)? {
+        let target = out_dir.join(itunes::file_name_for(&imported.name));
+        let missing = imported.entries.iter().filter(|p| !p.exists()).count();
+        playlist::save(&target, &imported.entries)?;
+        println!(
+            "{}: {} tracks ({} missing) -> {}",
+            imported.name,
+            imported.entries.len(),
+            missing,
+            target.display()
+        );
+    }
+    Ok(())
+}
+
+// Copies play counts and ratings into the files' own tags. This rewrites
+// the files, so without --write it only lists what would change.
+fn write_tags(args: &[String]) -> Result<()> {
+    let mut write = false;
+    let mut dir = None;
+    for arg in args {
+        match arg.as_str() {
+            "--write" => write = true,
+            _ => dir = Some(PathBuf::from(arg)),
+        }
+    }
+
+    let db = Database::open()?;
+    let mut stats: Vec<_> = db
+        .track_stats()?
+        .into_iter()
+        .filter(|(path, _)| !remote::is_remote(path) && path.exists())
+        .filter(|(path, _)| dir.as_ref().is_none_or(|dir| path.starts_with(dir)))
+        .collect();
+    stats.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut failed = 0;
+    for (path, stats) in &stats {
+        let rating = stats
+            .rating
+            .map_or_else(|| String::from("-"), |rating| rating.to_string());
+        println!(
+            "{} plays, rating {}: {}",
+            stats.plays,
+            rating,
+            path.display()
+        );
+        if write && let Err(e) = tags::write_stats(path, stats.plays, stats.rating) {
+            eprintln!("  {}", e);
+            failed += 1;
+        }
+    }
+
+    if !write {
+        println!(
+            "{} files would be rewritten. Run again with --write to update their tags.",
+            stats.len()
+        );
+    } else if failed > 0 {
+        bail!("{} of {} files could not be updated", failed, stats.len());
+    }
+    Ok(())
+}
+
+// Looks files up on AcoustID by how they sound, for untagged or mistagged
+// downloads. Prints the MusicBrainz recordings that match best.
+#[cfg(feature = "acoustid")]
+fn identify(args: &[String]) -> Result<()> {
+    if args.is_empty() {
+        bail!("usage: leek identify <file>...");
+    }
+    let Some(key) = Config::load()?.acoustid_key else {
+        bail!("set acoustid_key in the config, from https://acoustid.org/new-application");
+    };
+
+    for arg in args {
+        let path = Path::new(arg);
+        println!("{}", path.display());
+        let matches = fingerprint::lookup(&fingerprint::fingerprint(path)?, &key)?;
+        if matches.is_empty() {
+            println!("  no match");
+        }
+        for found in matches.iter().take(IDENTIFY_MATCHES) {
+            println!(
+                "  {:3.0}%  {} - {}  https://musicbrainz.org/recording/{}",
+                found.score * 100.0,
+                found.artists.join(", "),
+                found.title.as_deref().unwrap_or("?"),
+                found.recording_id
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "acoustid"))]
+fn identify(_args: &[String]) -> Result<()> {
+    bail!("built without AcoustID support")
+}
+
+// Finds files under a folder that hold the same recording, going by the
+// audio rather than names or tags.
+fn duplicates(args: &[String]) -> Result<()> {
+    let [dir] = args else {
+        bail!("usage: leek duplicates <folder>");
+    };
+
+    let mut prints = Vec::new();
+    for path in library::audio_files(Path::new(dir)) {
+        match fingerprint::raw_fingerprint(&path) {
+            Ok(print) => prints.push((path, print)),
+            Err(e) => eprintln!("Skipping {}: {}", path.display(), e),
+        }
+    }
+
+    let mut found = 0;
+    for (i, (path, print)) in prints.iter().enumerate() {
+        for (other_path, other) in &prints[i + 1..] {
+            if print.same_recording(other) {
+                println!("{}\n{}\n", path.display(), other_path.display());
+                found += 1;
+            }
+        }
+    }
+    println!("{} duplicate pairs among {} files", found, prints.len());
+    Ok(())
+}
+
+// Brings a device up to date with one of the config's sync profiles,
+// converting with the same settings as exports.
+fn sync(args: &[String]) -> Result<()> {
+    let [name] = args else {
+        bail!("usage: leek sync <profile>");
+    };
+    let config = Config::load()?;
+    let Some(profile) = config.sync.iter().find(|profile| &profile.name == name) else {
+        bail!("no [[sync]] profile named {} in the config", name);
+    };
+
+    let summary = sync::sync(
+        &profile.playlists(),
+        &profile.target,
+        config.music_dir.as_deref(),
+        config.transcode(),
+        |done, total, path| println!("[{}/{}] {}", done, total, path.display()),
+    )?;
+    for (path, e) in &summary.failed {
+        eprintln!("Failed {}: {}", path.display(), e);
+    }
+    println!(
+        "{} copied, {} already there, {} removed, {} failed",
+        summary.copied,
+        summary.kept,
+        summary.pruned,
+        summary.failed.len()
+    );
+    Ok(())
+}
+
+// Run by the player itself with `sandbox_decode`, not by hand.
+fn decode_child(args: &[String]) -> Result<()> {
+    let [path] = args else {
+        bail!("usage: leek decode-child <file>");
+    };
+    sandbox::serve(Path::new(path))
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn history_csv(db: &Database) -> Result<String> {
+    let mut out = String::from("played_at,played_at_utc,artist,album,title,path\n");
+    for entry in db.history()? {
+        let fields = [
+            entry.played_at.to_string(),
+            format_utc(entry.played_at),
+            entry.artist.unwrap_or_default(),
+            entry.album.unwrap_or_default(),
+            entry.title.unwrap_or_default(),
+            entry.path.to_string_lossy().into_owned(),
+        ];
+        let line: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&line.join(","));
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn stats_csv(db: &Database) -> Result<String> {
+    let mut out = String::from("plays,last_played_utc,path\n");
+    for count in db.play_counts()? {
+        out.push_str(&format!(
+            "{},{},{}\n",
+            count.plays,
+            format_utc(count.last_played),
+            csv_field(&count.path.to_string_lossy())
+        ));
+    }
+    Ok(out)
+}
+
+fn history_json(db: &Database) -> Result<String> {
+    let history: Vec<_> = db
+        .history()?
+        .into_iter()
+        .map(|entry| {
+            json!({
+                "played_at": entry.played_at,
+                "played_at_utc": format_utc(entry.played_at),
+                "artist": entry.artist,
+                "album": entry.album,
+                "title": entry.title,
+                "path": entry.path,
+            })
+        })
+        .collect();
+
+    let counts = db.play_counts()?;
+    let top_tracks: Vec<_> = counts
+        .iter()
+        .map(|count| {
+            json!({
+                "path": count.path,
+                "plays": count.plays,
+                "last_played_utc": format_utc(count.last_played),
+            })
+        })
+        .collect();
+
+    let document = json!({
+        "history": history,
+        "stats": {
+            "total_plays": history.len(),
+            "unique_tracks": counts.len(),
+            "tracks": top_tracks,
+        },
+    });
+    Ok(serde_json::to_string_pretty(&document)?)
+}
+
+// Formats a unix timestamp as an ISO 8601 UTC string without pulling in a
+// date library (civil-from-days, Howard Hinnant).
+pub fn format_utc(timestamp: i64) -> String {
+    let days = timestamp.div_euclid(86_400);
+    let secs = timestamp.rem_euclid(86_400);
+
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    )
+}
@@ -0,0 +1,128 @@
+use anyhow::{Result, bail};
+
+use crate::config::Config;
+
+// Written by hand, since the arguments are parsed by hand too. Sync
+// profile names come from `leek completions profiles` when completing.
+const BASH: &str = r#"_leek() {
+    local cur prev commands
+    cur="${COMP_WORDS[COMP_CWORD]}"
+    prev="${COMP_WORDS[COMP_CWORD-1]}"
+    commands="export-history import-itunes write-tags identify duplicates sync bench duck unduck play-pause next previous volume-up volume-down seek-forward seek-back status watch attach completions"
+
+    if [ "$COMP_CWORD" -eq 1 ]; then
+        COMPREPLY=($(compgen -W "$commands --minimal" -- "$cur") $(compgen -d -- "$cur"))
+        return
+    fi
+    case "${COMP_WORDS[1]}" in
+        sync) COMPREPLY=($(compgen -W "$(leek completions profiles 2>/dev/null)" -- "$cur")) ;;
+        bench)
+            if [ "$COMP_CWORD" -eq 2 ]; then
+                COMPREPLY=($(compgen -W "scan tags decode all" -- "$cur"))
+            else
+                COMPREPLY=($(compgen -W "--threads --limit" -- "$cur") $(compgen -d -- "$cur"))
+            fi ;;
+        completions) COMPREPLY=($(compgen -W "bash zsh fish" -- "$cur")) ;;
+        export-history)
+            case "$prev" in
+                --format) COMPREPLY=($(compgen -W "csv json" -- "$cur")) ;;
+                *) COMPREPLY=($(compgen -W "--format --stats" -- "$cur") $(compgen -f -- "$cur")) ;;
+            esac ;;
+        import-itunes) COMPREPLY=($(compgen -W "--out --map" -- "$cur") $(compgen -f -- "$cur")) ;;
+        write-tags) COMPREPLY=($(compgen -W "--write" -- "$cur") $(compgen -d -- "$cur")) ;;
+        identify) COMPREPLY=($(compgen -f -- "$cur")) ;;
+        duplicates) COMPREPLY=($(compgen -d -- "$cur")) ;;
+        --minimal) COMPREPLY=($(compgen -d -- "$cur")) ;;
+    esac
+}
+complete -o filenames -F _leek leek
+"#;
+
+const ZSH: &str = r#"#compdef leek
+
+_leek() {
+    local -a commands
+    commands=(
+        'export-history:export the play history as CSV or JSON'
+        'import-itunes:import playlists from an iTunes library'
+        'write-tags:store play counts and ratings in tags'
+        'identify:recognise tracks through AcoustID'
+        'duplicates:find copies of the same recording'
+        'sync:bring a device up to date with a sync profile'
+        'bench:time scanning, tag reading and decoding'
+        'duck:lower the volume of a running leek'
+        'unduck:restore the volume of a running leek'
+        'play-pause:toggle playback in a running leek'
+        'next:skip to the next track in a running leek'
+        'previous:go back a track in a running leek'
+        'volume-up:turn up a running leek'
+        'volume-down:turn down a running leek'
+        'seek-forward:skip ahead in a running leek'
+        'seek-back:skip back in a running leek'
+        'status:print what a running leek is playing'
+        'watch:follow what a running leek is playing'
+        'attach:control a running leek from a small player screen'
+        'completions:print a shell completion script'
+    )
+
+    if (( CURRENT == 2 )); then
+        _describe 'command' commands
+        _arguments '--minimal[light on CPU and plain ASCII]' '*:folder:_directories'
+        return
+    fi
+    case "$words[2]" in
+        sync) _values 'profile' ${(f)"$(leek completions profiles 2>/dev/null)"} ;;
+        bench)
+            if (( CURRENT == 3 )); then
+                _values 'benchmark' scan tags decode all
+            else
+                _arguments '--threads[scan threads]:count' '--limit[files to read]:count' '*:folder:_directories'
+            fi ;;
+        completions) _values 'shell' bash zsh fish ;;
+        export-history) _arguments '--format[output format]:format:(csv json)' '--stats[play counts per track]' '*:output:_files' ;;
+        import-itunes) _arguments '--out[playlist folder]:folder:_directories' '*--map[rewrite paths]:FROM=TO' '*:library:_files' ;;
+        write-tags) _arguments '--write[rewrite the files]' '*:folder:_directories' ;;
+        identify) _files ;;
+        duplicates|--minimal) _directories ;;
+    esac
+}
+
+_leek "$@"
+"#;
+
+const FISH: &str = r#"set -l commands export-history import-itunes write-tags identify duplicates sync bench duck unduck play-pause next previous volume-up volume-down seek-forward seek-back status watch attach completions
+
+complete -c leek -n "not __fish_seen_subcommand_from $commands" -a "$commands"
+complete -c leek -n "not __fish_seen_subcommand_from $commands" -l minimal -d 'light on CPU and plain ASCII'
+complete -c leek -n "__fish_seen_subcommand_from sync" -f -a "(leek completions profiles 2>/dev/null)"
+complete -c leek -n "__fish_seen_subcommand_from bench; and not __fish_seen_subcommand_from scan tags decode all" -f -a "scan tags decode all"
+complete -c leek -n "__fish_seen_subcommand_from bench" -l threads -r -d 'scan threads'
+complete -c leek -n "__fish_seen_subcommand_from bench" -l limit -r -d 'files to read'
+complete -c leek -n "__fish_seen_subcommand_from completions" -f -a "bash zsh fish"
+complete -c leek -n "__fish_seen_subcommand_from export-history" -l format -r -f -a "csv json"
+complete -c leek -n "__fish_seen_subcommand_from export-history" -l stats
+complete -c leek -n "__fish_seen_subcommand_from import-itunes" -l out -r
+complete -c leek -n "__fish_seen_subcommand_from import-itunes" -l map -r
+complete -c leek -n "__fish_seen_subcommand_from write-tags" -l write
+complete -c leek -n "__fish_seen_subcommand_from duck unduck play-pause next previous volume-up volume-down seek-forward seek-back status watch attach" -f
+"#;
+
+// `leek completions bash|zsh|fish` prints a script to source;
+// `leek completions profiles` lists sync profiles for those scripts.
+pub fn run(args: &[String]) -> Result<()> {
+    let [shell] = args else {
+        bail!("usage: leek completions bash|zsh|fish");
+    };
+    match shell.as_str() {
+        "bash" => print!("{}", BASH),
+        "zsh" => print!("{}", ZSH),
+        "fish" => print!("{}", FISH),
+        "profiles" => {
+            for profile in Config::load()?.sync {
+                println!("{}", profile.name);
+            }
+        }
+        other => bail!("no completions for {}, only bash, zsh and fish", other),
+    }
+    Ok(())
+}
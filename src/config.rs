@@ -0,0 +1,514 @@
+use anyhow::{Result, anyhow};
+use ratatui::style::Color;
+use serde::{Deserialize, Deserializer};
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use leek_core::export::{Codec, Transcode};
+use leek_core::paths;
+
+use crate::i18n::tr;
+use crate::icons::IconSet;
+use crate::keymap::Keymap;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Announce {
+    #[default]
+    Off,
+    Title,
+    Notify,
+}
+
+// `[theme]`, or one of the bundled palettes for low vision and colour
+// blindness.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Palette {
+    #[default]
+    Theme,
+    HighContrast,
+    Deuteranopia,
+}
+
+impl Palette {
+    pub const ALL: [Palette; 3] = [Palette::Theme, Palette::HighContrast, Palette::Deuteranopia];
+
+    pub fn next(self) -> Palette {
+        match self {
+            Palette::Theme => Palette::HighContrast,
+            Palette::HighContrast => Palette::Deuteranopia,
+            Palette::Deuteranopia => Palette::Theme,
+        }
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            Palette::Theme => tr!("colours.theme"),
+            Palette::HighContrast => tr!("colours.high_contrast"),
+            Palette::Deuteranopia => tr!("colours.deuteranopia"),
+        }
+    }
+
+    // As written in config.toml.
+    fn name(self) -> &'static str {
+        match self {
+            Palette::Theme => "theme",
+            Palette::HighContrast => "high-contrast",
+            Palette::Deuteranopia => "deuteranopia",
+        }
+    }
+}
+
+// The answers to the first-run setup.
+#[derive(Clone, Debug, Default)]
+pub struct Setup {
+    pub music_dir: Option<PathBuf>,
+    pub palette: Palette,
+    pub media_keys: bool,
+}
+
+impl Setup {
+    // A short config.toml with just these; everything else stays at its
+    // default until the user adds it.
+    pub fn write(&self) -> Result<PathBuf> {
+        let path = paths::config_file().ok_or_else(|| anyhow!("no config directory"))?;
+        let mut table = toml::Table::new();
+        if let Some(dir) = &self.music_dir {
+            table.insert(
+                "music_dir".into(),
+                dir.to_string_lossy().into_owned().into(),
+            );
+        }
+        table.insert("palette".into(), self.palette.name().into());
+        table.insert("media_keys".into(), self.media_keys.into());
+
+        let text = format!(
+            "# Written by leek's first-run setup. The README lists every setting.\n{}",
+            toml::to_string(&table)?
+        );
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, text)?;
+        Ok(path)
+    }
+}
+
+// Where the info pane goes when the terminal is narrower than
+// `narrow_columns`.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NarrowLayout {
+    #[default]
+    Stack,
+    Hide,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Output {
+    #[default]
+    Default,
+    Jack,
+    Snapcast,
+    Silent,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Copy,
+    Opus,
+    Mp3,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    #[serde(deserialize_with = "color")]
+    pub accent: Color,
+    #[serde(deserialize_with = "color")]
+    pub border: Color,
+    #[serde(deserialize_with = "color")]
+    pub text: Color,
+    #[serde(deserialize_with = "color")]
+    pub muted: Color,
+    #[serde(deserialize_with = "color")]
+    pub secondary: Color,
+    #[serde(deserialize_with = "color")]
+    pub heading: Color,
+    #[serde(deserialize_with = "color")]
+    pub status: Color,
+    #[serde(deserialize_with = "color")]
+    pub error: Color,
+    #[serde(deserialize_with = "color")]
+    pub background: Color,
+}
+
+// Accepts names like "cyan" or "light-blue", "#rrggbb" and palette indexes.
+fn color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    Color::from_str(&name)
+        .map_err(|_| serde::de::Error::custom(format!("unknown color \"{}\"", name)))
+}
+
+impl Theme {
+    const HIGH_CONTRAST: Theme = Theme {
+        accent: Color::LightYellow,
+        border: Color::White,
+        text: Color::White,
+        muted: Color::Gray,
+        secondary: Color::White,
+        heading: Color::LightYellow,
+        status: Color::LightCyan,
+        error: Color::LightRed,
+        background: Color::Black,
+    };
+
+    // Blues and oranges from the Okabe-Ito set, with nothing that needs
+    // red told apart from green.
+    const DEUTERANOPIA: Theme = Theme {
+        accent: Color::Rgb(0x56, 0xb4, 0xe9),
+        border: Color::Rgb(0x00, 0x72, 0xb2),
+        text: Color::White,
+        muted: Color::Gray,
+        secondary: Color::Rgb(0xcc, 0xcc, 0xcc),
+        heading: Color::Rgb(0x56, 0xb4, 0xe9),
+        status: Color::Rgb(0xe6, 0x9f, 0x00),
+        error: Color::Rgb(0xd5, 0x5e, 0x00),
+        background: Color::Black,
+    };
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            accent: Color::Cyan,
+            border: Color::LightBlue,
+            text: Color::White,
+            muted: Color::DarkGray,
+            secondary: Color::Gray,
+            heading: Color::LightCyan,
+            status: Color::Yellow,
+            error: Color::Red,
+            background: Color::Black,
+        }
+    }
+}
+
+// Columns for the queue and track lists. Title, artist and album take a
+// percentage of the width and duration a number of cells; 0 hides one.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Table {
+    pub enabled: bool,
+    pub title: u16,
+    pub artist: u16,
+    pub album: u16,
+    pub duration: u16,
+}
+
+impl Default for Table {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            title: 45,
+            artist: 25,
+            album: 30,
+            duration: 8,
+        }
+    }
+}
+
+// Starts playing a playlist or folder at a time of day, rising from
+// silence over `fade_seconds`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Alarm {
+    #[serde(deserialize_with = "time_of_day")]
+    pub time: u32,
+    pub play: PathBuf,
+    #[serde(default = "Alarm::default_fade_seconds")]
+    pub fade_seconds: u64,
+}
+
+impl Alarm {
+    fn default_fade_seconds() -> u64 {
+        60
+    }
+}
+
+// A set of playlists kept on a device with `leek sync <name>`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SyncProfile {
+    pub name: String,
+    pub playlists: Vec<PathBuf>,
+    pub target: PathBuf,
+}
+
+impl SyncProfile {
+    // Relative names live in the playlists directory, like the liked
+    // playlist.
+    pub fn playlists(&self) -> Vec<PathBuf> {
+        let dir = paths::playlists_dir().unwrap_or_default();
+        self.playlists.iter().map(|path| dir.join(path)).collect()
+    }
+}
+
+// "07:30" as minutes since midnight.
+fn time_of_day<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    text.split_once(':')
+        .and_then(|(hours, minutes)| {
+            Some((hours.parse::<u32>().ok()?, minutes.parse::<u32>().ok()?))
+        })
+        .filter(|&(hours, minutes)| hours < 24 && minutes < 60)
+        .map(|(hours, minutes)| hours * 60 + minutes)
+        .ok_or_else(|| {
+            serde::de::Error::custom(format!("invalid time \"{}\", expected HH:MM", text))
+        })
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub replay_seconds: u64,
+    pub seek_seconds: u64,
+    pub volume_step: u8,
+    pub max_volume: u8,
+    pub duck_db: f32,
+    pub queue_dedup: bool,
+    pub liked_playlist: PathBuf,
+    pub export_dir: Option<PathBuf>,
+    pub downloads_dir: Option<PathBuf>,
+    pub music_dir: Option<PathBuf>,
+    pub export_format: ExportFormat,
+    pub export_bitrate: u32,
+    pub recent_days: u64,
+    pub autosave_seconds: u64,
+    pub tick_ms: u64,
+    pub redraw_ms: u64,
+    pub icons: IconSet,
+    pub accessible: bool,
+    pub minimal: bool,
+    pub palette: Palette,
+    pub narrow_columns: u16,
+    pub narrow_layout: NarrowLayout,
+    pub announce: Announce,
+    pub media_keys: bool,
+    pub osd: bool,
+    pub osd_seconds: u64,
+    pub fetch_covers: bool,
+    pub alarm: Option<Alarm>,
+    pub sync: Vec<SyncProfile>,
+    pub sandbox_decode: bool,
+    pub headphone_surround: bool,
+    pub idle_pause_hours: u64,
+    pub gap_seconds: f32,
+    pub crossfade_seconds: f32,
+    pub crossfade_albums: bool,
+    pub acoustid_key: Option<String>,
+    pub rename_pattern: String,
+    pub open_with: String,
+    pub footer_format: Option<String>,
+    pub title_format: Option<String>,
+    pub locale: String,
+    pub output: Output,
+    pub snapcast_source: String,
+    pub metrics_address: Option<String>,
+    pub metrics_token: Option<String>,
+    pub journal: bool,
+    pub theme: Theme,
+    pub table: Table,
+    pub keys: Keymap,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            replay_seconds: 10,
+            seek_seconds: 5,
+            volume_step: 5,
+            max_volume: 100,
+            duck_db: 15.0,
+            queue_dedup: false,
+            liked_playlist: PathBuf::from("liked.m3u"),
+            export_dir: None,
+            downloads_dir: None,
+            music_dir: None,
+            export_format: ExportFormat::default(),
+            export_bitrate: 160,
+            recent_days: 30,
+            autosave_seconds: 5,
+            tick_ms: 250,
+            redraw_ms: 250,
+            icons: IconSet::default(),
+            accessible: false,
+            minimal: false,
+            palette: Palette::default(),
+            narrow_columns: 80,
+            narrow_layout: NarrowLayout::default(),
+            announce: Announce::default(),
+            media_keys: false,
+            osd: false,
+            osd_seconds: 2,
+            fetch_covers: false,
+            alarm: None,
+            sync: Vec::new(),
+            sandbox_decode: false,
+            headphone_surround: false,
+            idle_pause_hours: 0,
+            gap_seconds: 0.0,
+            crossfade_seconds: 0.0,
+            crossfade_albums: false,
+            acoustid_key: None,
+            rename_pattern: String::from("%artist% - %album%/%track% - %title%.%ext%"),
+            open_with: String::from(if cfg!(target_os = "macos") {
+                "open"
+            } else {
+                "xdg-open"
+            }),
+            footer_format: None,
+            title_format: None,
+            locale: String::from("en"),
+            output: Output::default(),
+            snapcast_source: String::from("/tmp/snapfifo"),
+            metrics_address: None,
+            metrics_token: None,
+            journal: false,
+            theme: Theme::default(),
+            table: Table::default(),
+            keys: Keymap::default(),
+        }
+    }
+}
+
+impl Config {
+    // Nothing has been set up yet.
+    pub fn missing() -> bool {
+        paths::config_file().is_some_and(|path| !path.exists())
+    }
+
+    pub fn load() -> Result<Config> {
+        let Some(path) = paths::config_file() else {
+            return Ok(Config::default());
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(toml::from_str(&text)?)
+    }
+
+    // Up to five seconds of silence between tracks.
+    pub fn gap(&self) -> Duration {
+        Duration::try_from_secs_f32(self.gap_seconds.clamp(0.0, 5.0)).unwrap_or_default()
+    }
+
+    // Up to twelve seconds of overlap between tracks.
+    pub fn crossfade(&self) -> Duration {
+        Duration::try_from_secs_f32(self.crossfade_seconds.clamp(0.0, 12.0)).unwrap_or_default()
+    }
+
+    // `open_with` split on spaces, with the path in place of `{path}` or
+    // after the last argument.
+    pub fn open_command(&self, path: &Path) -> Option<Command> {
+        let mut words = self.open_with.split_whitespace();
+        let mut command = Command::new(words.next()?);
+        let mut placed = false;
+        for word in words {
+            if word == "{path}" {
+                command.arg(path);
+                placed = true;
+            } else {
+                command.arg(word);
+            }
+        }
+        if !placed {
+            command.arg(path);
+        }
+        Some(command)
+    }
+
+    // Cuts drawing down for slow SSH links and serial consoles: ASCII icons,
+    // text in place of gauges and covers, no OSD and at most a redraw a
+    // second.
+    pub fn make_minimal(&mut self) {
+        self.minimal = true;
+        self.icons = IconSet::Ascii;
+        self.osd = false;
+        self.redraw_ms = self.redraw_ms.max(1000);
+    }
+
+    // Text instead of gauges and cover art.
+    pub fn text_only(&self) -> bool {
+        self.accessible || self.minimal
+    }
+
+    pub fn theme(&self) -> &Theme {
+        match self.palette {
+            Palette::Theme => &self.theme,
+            Palette::HighContrast => &Theme::HIGH_CONTRAST,
+            Palette::Deuteranopia => &Theme::DEUTERANOPIA,
+        }
+    }
+
+    // How often the position moves on and timers are checked, from 50ms
+    // to two seconds.
+    pub fn tick_rate(&self) -> Duration {
+        Duration::from_millis(self.tick_ms.clamp(50, 2000))
+    }
+
+    // The screen is redrawn every this many ticks, besides after each
+    // key press.
+    pub fn ticks_per_redraw(&self) -> u64 {
+        (self.redraw_ms / self.tick_rate().as_millis() as u64).max(1)
+    }
+
+    pub fn replay_step(&self) -> Duration {
+        Duration::from_secs(self.replay_seconds)
+    }
+
+    // Relative names live in the playlists directory.
+    pub fn liked_playlist(&self) -> Option<PathBuf> {
+        if self.liked_playlist.is_absolute() {
+            return Some(self.liked_playlist.clone());
+        }
+        Some(paths::playlists_dir()?.join(&self.liked_playlist))
+    }
+
+    pub fn transcode(&self) -> Option<Transcode> {
+        let codec = match self.export_format {
+            ExportFormat::Copy => return None,
+            ExportFormat::Opus => Codec::Opus,
+            ExportFormat::Mp3 => Codec::Mp3,
+        };
+        Some(Transcode {
+            codec,
+            bitrate: self.export_bitrate,
+        })
+    }
+
+    // leek itself, run as the decoder child.
+    pub fn sandbox(&self) -> Option<PathBuf> {
+        if !self.sandbox_decode {
+            return None;
+        }
+        env::current_exe().ok()
+    }
+
+    pub fn seek_step(&self) -> Duration {
+        Duration::from_secs(self.seek_seconds)
+    }
+
+    pub fn modified() -> Option<SystemTime> {
+        fs::metadata(paths::config_file()?).ok()?.modified().ok()
+    }
+}
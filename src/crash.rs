@@ -0,0 +1,135 @@
+use crossterm::{
+    cursor::Show,
+    event::DisableMouseCapture,
+    execute,
+    terminal::{LeaveAlternateScreen, disable_raw_mode},
+};
+use std::backtrace::Backtrace;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::panic::{self, PanicHookInfo};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use leek_core::log;
+use leek_core::paths;
+
+use crate::action::Action;
+
+const RECENT_ACTIONS: usize = 20;
+
+// What the player was doing, kept up to date from the main loop so a
+// crash report has more to go on than the backtrace.
+struct Context {
+    actions: VecDeque<Action>,
+    track: Option<PathBuf>,
+    queue_len: usize,
+}
+
+static CONTEXT: Mutex<Context> = Mutex::new(Context {
+    actions: VecDeque::new(),
+    track: None,
+    queue_len: 0,
+});
+
+pub fn note_action(action: Action) {
+    if let Ok(mut context) = CONTEXT.lock() {
+        if context.actions.len() == RECENT_ACTIONS {
+            context.actions.pop_front();
+        }
+        context.actions.push_back(action);
+    }
+}
+
+pub fn note_playing(track: Option<PathBuf>, queue_len: usize) {
+    if let Ok(mut context) = CONTEXT.lock() {
+        context.track = track;
+        context.queue_len = queue_len;
+    }
+}
+
+// A panic on the main thread gives the terminal back before saying
+// anything, then writes a report to the data directory. Worker threads
+// going down only get a line in the log, since the TUI carries on.
+pub fn install() {
+    panic::set_hook(Box::new(|info| {
+        let message = describe(info);
+        if thread::current().name() != Some("main") {
+            log::warn(&format!("Thread panicked: {}", message));
+            return;
+        }
+
+        let _ = disable_raw_mode();
+        let _ = execute!(
+            io::stdout(),
+            LeaveAlternateScreen,
+            DisableMouseCapture,
+            Show
+        );
+
+        eprintln!("leek crashed: {}", message);
+        match write_report(&message) {
+            Ok(path) => eprintln!(
+                "A crash report was saved to {}\nPlease attach it to a bug report.",
+                path.display()
+            ),
+            Err(e) => eprintln!("Could not save a crash report: {}", e),
+        }
+    }));
+}
+
+fn describe(info: &PanicHookInfo) -> String {
+    let payload = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|text| text.to_string())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| String::from("unknown panic"));
+    match info.location() {
+        Some(location) => format!("{} at {}", payload, location),
+        None => payload,
+    }
+}
+
+fn write_report(message: &str) -> io::Result<PathBuf> {
+    let dir = paths::data_dir().ok_or_else(|| io::Error::other("no data directory"))?;
+    fs::create_dir_all(&dir)?;
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let path = dir.join(format!("crash-{}.txt", time));
+
+    let mut report = String::new();
+    let _ = writeln!(report, "leek {} crashed", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(report, "Time: {}", time);
+    let _ = writeln!(
+        report,
+        "OS: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    let _ = writeln!(report, "Panic: {}", message);
+    // The lock may be poisoned by the very panic being reported.
+    let context = CONTEXT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let track = context
+        .track
+        .as_ref()
+        .map(|track| track.display().to_string());
+    let _ = writeln!(report, "Track: {}", track.as_deref().unwrap_or("none"));
+    let _ = writeln!(report, "Queue: {} tracks", context.queue_len);
+    let _ = writeln!(report, "\nLast actions, oldest first:");
+    for action in &context.actions {
+        let _ = writeln!(report, "  {:?}", action);
+    }
+    let _ = writeln!(report, "\nBacktrace:\n{}", Backtrace::force_capture());
+
+    fs::write(&path, report)?;
+    Ok(path)
+}
@@ -1,15 +1,86 @@
-use crossterm::event::{self, Event as CEvent, KeyEvent, KeyEventKind};
+use crossterm::event::{
+    self, Event as CEvent, KeyEvent, KeyEventKind, MouseButton, MouseEvent, MouseEventKind,
+};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::mpsc;
 use std::thread;
 use std::time::Duration;
 
+use crate::action::Action;
+use crate::app::Loaded;
+
 pub enum Event<I> {
     Input(I),
+    Drag(DragEvent),
+    Loaded(Loaded),
+    // From the control socket.
+    Action(Action),
     Tick,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DragEvent {
+    Click { column: u16, row: u16 },
+    Start { column: u16, row: u16 },
+    Move { column: u16, row: u16 },
+    Drop { column: u16, row: u16 },
+}
+
+// Turns raw mouse-down/drag/up events into higher level clicks and drags.
+// A press that is released on the same cell without moving is a click.
+#[derive(Default)]
+struct DragTracker {
+    origin: Option<(u16, u16)>,
+    dragging: bool,
+}
+
+impl DragTracker {
+    fn update(&mut self, mouse: MouseEvent) -> Option<DragEvent> {
+        let (column, row) = (mouse.column, mouse.row);
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.origin = Some((column, row));
+                self.dragging = false;
+                None
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                let (origin_column, origin_row) = self.origin?;
+                if !self.dragging {
+                    if (column, row) == (origin_column, origin_row) {
+                        return None;
+                    }
+                    self.dragging = true;
+                    return Some(DragEvent::Start {
+                        column: origin_column,
+                        row: origin_row,
+                    });
+                }
+                Some(DragEvent::Move { column, row })
+            }
+            MouseEventKind::Up(MouseButton::Left) => {
+                let (origin_column, origin_row) = self.origin.take()?;
+                if std::mem::take(&mut self.dragging) {
+                    Some(DragEvent::Drop { column, row })
+                } else {
+                    Some(DragEvent::Click {
+                        column: origin_column,
+                        row: origin_row,
+                    })
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
 pub struct Events {
     rx: mpsc::Receiver<Event<KeyEvent>>,
+    tx: mpsc::Sender<Event<KeyEvent>>,
+    // Set while another program has the terminal, so its keys aren't read.
+    paused: Arc<AtomicBool>,
+    // Milliseconds between ticks, changeable while running.
+    tick_rate: Arc<AtomicU64>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -34,30 +105,74 @@ impl Events {
         let (tx, rx) = mpsc::channel();
 
         let tx_input = tx.clone();
+        let tx_tick = tx.clone();
+        let paused = Arc::new(AtomicBool::new(false));
+        let input_paused = paused.clone();
+        let tick_rate = Arc::new(AtomicU64::new(config.tick_rate.as_millis() as u64));
+        let tick_thread_rate = tick_rate.clone();
         thread::spawn(move || {
+            let mut drag = DragTracker::default();
             loop {
-                if let Ok(true) = event::poll(Duration::from_millis(100)) {
-                    if let Ok(CEvent::Key(key)) = event::read() {
-                        if key.kind == KeyEventKind::Press {
-                            if tx_input.send(Event::Input(key)).is_err() {
-                                return;
-                            }
-                        }
-                    }
+                if input_paused.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(100));
+                    continue;
+                }
+                if !matches!(event::poll(Duration::from_millis(100)), Ok(true))
+                    || input_paused.load(Ordering::Relaxed)
+                {
+                    continue;
+                }
+                let event = match event::read() {
+                    Ok(CEvent::Key(key)) if key.kind == KeyEventKind::Press => Event::Input(key),
+                    Ok(CEvent::Mouse(mouse)) => match drag.update(mouse) {
+                        Some(drag_event) => Event::Drag(drag_event),
+                        None => continue,
+                    },
+                    _ => continue,
+                };
+                if tx_input.send(event).is_err() {
+                    return;
                 }
             }
         });
 
         thread::spawn(move || {
             loop {
-                if tx.send(Event::Tick).is_err() {
+                if tx_tick.send(Event::Tick).is_err() {
                     break;
                 }
-                thread::sleep(config.tick_rate);
+                thread::sleep(Duration::from_millis(
+                    tick_thread_rate.load(Ordering::Relaxed),
+                ));
             }
         });
 
-        Events { rx }
+        Events {
+            rx,
+            tx,
+            paused,
+            tick_rate,
+        }
+    }
+
+    pub fn set_tick_rate(&self, tick_rate: Duration) {
+        self.tick_rate
+            .store(tick_rate.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+        // Lets a poll that was already waiting run out.
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    // For worker threads that report back to the main loop.
+    pub fn sender(&self) -> mpsc::Sender<Event<KeyEvent>> {
+        self.tx.clone()
     }
 
     pub fn next(&self) -> Result<Event<KeyEvent>, mpsc::RecvError> {
@@ -0,0 +1,63 @@
+use anyhow::Result;
+use regex::{Regex, RegexBuilder};
+
+// Narrows the browser listing by name. `re:` starts a regular expression;
+// anything else is a glob, and text without wildcards matches anywhere in
+// the name. Case is ignored either way.
+#[derive(Clone, Debug)]
+pub struct NameFilter {
+    pub text: String,
+    regex: Regex,
+}
+
+impl NameFilter {
+    pub fn parse(text: &str) -> Result<NameFilter> {
+        let pattern = match text.strip_prefix("re:") {
+            Some(pattern) => pattern.to_string(),
+            None if text.contains(['*', '?', '[']) => glob_to_regex(text),
+            None => regex::escape(text),
+        };
+        Ok(NameFilter {
+            text: text.to_string(),
+            regex: RegexBuilder::new(&pattern).case_insensitive(true).build()?,
+        })
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        self.regex.is_match(name)
+    }
+}
+
+// `*` and `?` match any run of characters or any one, and `[...]` a set,
+// over the whole name.
+fn glob_to_regex(glob: &str) -> String {
+    let mut pattern = String::from("^");
+    let mut in_set = false;
+    for c in glob.chars() {
+        match c {
+            '[' if !in_set => {
+                in_set = true;
+                pattern.push('[');
+            }
+            ']' if in_set => {
+                in_set = false;
+                pattern.push(']');
+            }
+            '!' if in_set && pattern.ends_with('[') => pattern.push('^'),
+            c if in_set => {
+                if c == '\\' || c == '^' {
+                    pattern.push('\\');
+                }
+                pattern.push(c);
+            }
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    if in_set {
+        pattern.push(']');
+    }
+    pattern.push('$');
+    pattern
+}
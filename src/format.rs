@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+// Fills in the `{name}` fields of a format string like
+// `{artist} – {title} [{elapsed}/{duration}]`. Fields with no value come
+// out empty (`field` gives `Some(None)`); unknown names (`None`) are left
+// as written so typos show.
+pub fn expand(format: &str, field: impl Fn(&str) -> Option<Option<String>>) -> String {
+    let mut out = String::new();
+    let mut rest = format;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start..];
+        let Some(end) = after.find('}') else {
+            rest = after;
+            break;
+        };
+        match field(&after[1..end]) {
+            Some(value) => out.push_str(&value.unwrap_or_default()),
+            None => out.push_str(&after[..=end]),
+        }
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, (secs % 3600) / 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    }
+}
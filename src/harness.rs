@@ -0,0 +1,251 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{Terminal, backend::TestBackend};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+use tempfile::TempDir;
+
+use leek_core::backend::MockBackend;
+
+use crate::app::App;
+use crate::events::Event;
+use crate::ui;
+
+// Long enough for a worker thread to list a small folder.
+const LOAD_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Drives an `App` the way the main loop does, from scripted keys instead
+// of a terminal, with the mock backend standing in for the sound card.
+pub struct Harness {
+    pub app: App,
+    pub backend: MockBackend,
+    rx: Receiver<Event<KeyEvent>>,
+    terminal: Terminal<TestBackend>,
+}
+
+impl Harness {
+    // Starts in `dir` with its listing loaded.
+    pub fn new(dir: &Path) -> Harness {
+        let (tx, rx) = mpsc::channel();
+        let backend = MockBackend::new();
+        let app = App::for_test(tx, dir, backend.clone());
+        let terminal = Terminal::new(TestBackend::new(100, 30)).unwrap();
+        let mut harness = Harness {
+            app,
+            backend,
+            rx,
+            terminal,
+        };
+        harness.settle();
+        harness
+    }
+
+    // Presses each key in `script` in turn: plain characters, or names in
+    // angle brackets such as `<Enter>`, `<Esc>`, `<Up>` and `<Space>`.
+    pub fn keys(&mut self, script: &str) {
+        let mut chars = script.chars();
+        while let Some(c) = chars.next() {
+            let code = if c == '<' {
+                let name: String = chars.by_ref().take_while(|&c| c != '>').collect();
+                key_named(&name)
+            } else {
+                KeyCode::Char(c)
+            };
+            self.send(Event::Input(KeyEvent::new(code, KeyModifiers::NONE)));
+        }
+    }
+
+    pub fn send(&mut self, event: Event<KeyEvent>) {
+        crate::handle(&mut self.app, event);
+        self.settle();
+    }
+
+    // Handles what worker threads sent back, waiting for a folder that is
+    // still being listed.
+    pub fn settle(&mut self) {
+        while self.app.loading.is_some() {
+            let event = self
+                .rx
+                .recv_timeout(LOAD_TIMEOUT)
+                .expect("folder never finished loading");
+            crate::handle(&mut self.app, event);
+        }
+        while let Ok(event) = self.rx.try_recv() {
+            crate::handle(&mut self.app, event);
+        }
+    }
+
+    // The screen as text, one line per row.
+    pub fn screen(&mut self) -> String {
+        self.terminal.draw(|f| ui::draw(f, &self.app)).unwrap();
+        let buffer = self.terminal.backend().buffer();
+        let width = buffer.area.width as usize;
+        buffer
+            .content
+            .chunks(width)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn key_named(name: &str) -> KeyCode {
+    match name {
+        "Enter" => KeyCode::Enter,
+        "Esc" => KeyCode::Esc,
+        "Backspace" => KeyCode::Backspace,
+        "Tab" => KeyCode::Tab,
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "Space" => KeyCode::Char(' '),
+        "Delete" => KeyCode::Delete,
+        _ => panic!("unknown key <{}>", name),
+    }
+}
+
+// A scratch folder for one test, deleted when the test is done with it.
+pub struct Fixture {
+    pub root: PathBuf,
+    _dir: TempDir,
+}
+
+impl Fixture {
+    pub fn new() -> Fixture {
+        let dir = tempfile::tempdir().unwrap();
+        Fixture {
+            root: dir.path().to_path_buf(),
+            _dir: dir,
+        }
+    }
+
+    pub fn dir(&self, relative: &str) -> PathBuf {
+        let path = self.root.join(relative);
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    // A second of silence as a WAV file, which every decoder can open.
+    pub fn track(&self, relative: &str) -> PathBuf {
+        const RATE: u32 = 8000;
+        let data = RATE * 2;
+        let mut wav = Vec::new();
+        wav.extend(b"RIFF");
+        wav.extend((36 + data).to_le_bytes());
+        wav.extend(b"WAVEfmt ");
+        wav.extend(16u32.to_le_bytes());
+        wav.extend(1u16.to_le_bytes());
+        wav.extend(1u16.to_le_bytes());
+        wav.extend(RATE.to_le_bytes());
+        wav.extend((RATE * 2).to_le_bytes());
+        wav.extend(2u16.to_le_bytes());
+        wav.extend(16u16.to_le_bytes());
+        wav.extend(b"data");
+        wav.extend(data.to_le_bytes());
+        wav.resize(wav.len() + data as usize, 0);
+
+        let path = self.root.join(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(&path, wav).unwrap();
+        path
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{Focus, View};
+
+    #[test]
+    fn enters_folders_and_goes_back_up() {
+        let fixture = Fixture::new();
+        fixture.dir("Album A");
+        let album = fixture.dir("Album B");
+        fixture.track("Album B/01.wav");
+
+        let mut harness = Harness::new(&fixture.root);
+        assert_eq!(harness.app.browser_items.len(), 2);
+
+        harness.keys("j<Enter>");
+        assert_eq!(harness.app.current_directory, album);
+        assert_eq!(harness.app.browser_items[0].name, "01.wav");
+
+        harness.keys("<Backspace>");
+        assert_eq!(harness.app.current_directory, fixture.root);
+    }
+
+    #[test]
+    fn selection_wraps_around() {
+        let fixture = Fixture::new();
+        fixture.dir("a");
+        fixture.dir("b");
+        fixture.dir("c");
+
+        let mut harness = Harness::new(&fixture.root);
+        harness.keys("k");
+        assert_eq!(harness.app.browser_index, 2);
+        harness.keys("j");
+        assert_eq!(harness.app.browser_index, 0);
+    }
+
+    #[test]
+    fn playing_a_track_queues_its_folder() {
+        let fixture = Fixture::new();
+        for name in ["01.wav", "02.wav", "03.wav"] {
+            fixture.track(name);
+        }
+
+        let mut harness = Harness::new(&fixture.root);
+        harness.keys("j<Enter>");
+        assert_eq!(harness.app.player.queue.len(), 3);
+        assert_eq!(harness.app.player.queue_index, 1);
+        assert!(harness.backend.state().loaded);
+
+        harness.keys("<Right>");
+        assert_eq!(harness.app.player.queue_index, 2);
+        harness.keys("<Space>");
+        assert!(harness.backend.state().paused);
+    }
+
+    #[test]
+    fn track_gain_follows_the_playing_track() {
+        let fixture = Fixture::new();
+        fixture.track("quiet.wav");
+
+        let mut harness = Harness::new(&fixture.root);
+        harness.keys("]");
+        assert_eq!(harness.app.player.track_gain(), 0.0);
+        harness.keys("<Enter>]]]");
+        assert_eq!(harness.app.player.track_gain(), 3.0);
+        harness.keys("[");
+        assert_eq!(harness.app.player.track_gain(), 2.0);
+    }
+
+    #[test]
+    fn queue_view_lists_the_queue() {
+        let fixture = Fixture::new();
+        fixture.track("first.wav");
+        fixture.track("second.wav");
+
+        let mut harness = Harness::new(&fixture.root);
+        harness.keys("<Enter>v");
+        assert_eq!(harness.app.focus, Focus::Queue);
+        let screen = harness.screen();
+        assert!(screen.contains("first"));
+        assert!(screen.contains("second"));
+
+        harness.keys("v");
+        assert_eq!(harness.app.focus, Focus::Browser);
+        assert_eq!(harness.app.view, View::Browser);
+    }
+}
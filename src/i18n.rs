@@ -0,0 +1,131 @@
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::OnceLock;
+
+use leek_core::paths;
+
+const BUNDLED: &[(&str, &str)] = &[("en", include_str!("../locales/en.toml"))];
+
+static STRINGS: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+macro_rules! tr {
+    ($key:expr) => {
+        $crate::i18n::text($key)
+    };
+    ($key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::format($key, &[$((stringify!($name), $value.to_string())),+])
+    };
+}
+pub(crate) use tr;
+
+fn flatten(prefix: &str, table: toml::Table, out: &mut HashMap<String, String>) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() {
+            key
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            toml::Value::Table(table) => flatten(&key, table, out),
+            toml::Value::String(text) => {
+                out.insert(key, text);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn parse(source: &str) -> Result<HashMap<String, String>> {
+    let mut strings = HashMap::new();
+    flatten("", toml::from_str(source)?, &mut strings);
+    Ok(strings)
+}
+
+// The bundled file is part of the build, so a mistake in it is a bug and
+// not something to paper over with raw keys.
+fn english() -> HashMap<String, String> {
+    parse(BUNDLED[0].1).expect("locales/en.toml does not parse")
+}
+
+// Untranslated keys fall back to English, so partial translations work.
+// A `locales/<name>.toml` in the config directory overrides bundled files.
+pub fn init(locale: &str) -> Result<()> {
+    let mut strings = english();
+
+    let user_file = paths::config_file()
+        .and_then(|file| {
+            Some(
+                file.parent()?
+                    .join("locales")
+                    .join(format!("{}.toml", locale)),
+            )
+        })
+        .filter(|path| path.exists());
+
+    let source = match user_file {
+        Some(path) => fs::read_to_string(path)?,
+        None => BUNDLED
+            .iter()
+            .find(|(name, _)| *name == locale)
+            .map(|(_, source)| source.to_string())
+            .ok_or_else(|| anyhow!("unknown locale \"{}\"", locale))?,
+    };
+    strings.extend(parse(&source)?);
+
+    STRINGS
+        .set(strings)
+        .map_err(|_| anyhow!("locale already loaded"))
+}
+
+pub fn text(key: &str) -> String {
+    STRINGS
+        .get_or_init(english)
+        .get(key)
+        .cloned()
+        .unwrap_or_else(|| key.to_string())
+}
+
+pub fn format(key: &str, args: &[(&str, String)]) -> String {
+    let mut text = text(key);
+    for (name, value) in args {
+        text = text.replace(&format!("{{{}}}", name), value);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+    use std::path::Path;
+
+    #[test]
+    fn bundled_locales_parse() {
+        for (name, source) in BUNDLED {
+            assert!(
+                parse(source).is_ok(),
+                "locales/{}.toml does not parse",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn every_key_in_the_source_is_in_english() {
+        let strings = english();
+        let key = Regex::new(r#"\btr!\(\s*"([^"]+)""#).unwrap();
+        let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let mut missing = Vec::new();
+        for entry in fs::read_dir(src).unwrap() {
+            let path = entry.unwrap().path();
+            let source = fs::read_to_string(&path).unwrap();
+            for found in key.captures_iter(&source) {
+                if !strings.contains_key(&found[1]) {
+                    missing.push(format!("{}: {}", path.display(), &found[1]));
+                }
+            }
+        }
+        assert!(missing.is_empty(), "missing from en.toml: {:#?}", missing);
+    }
+}
@@ -0,0 +1,123 @@
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum IconSet {
+    #[default]
+    Emoji,
+    Ascii,
+    Nerd,
+}
+
+pub struct Icons {
+    pub logo: &'static str,
+    pub directory: &'static str,
+    pub audio: &'static str,
+    pub codecs: &'static [(&'static str, &'static str)],
+    pub playlist: &'static str,
+    pub other: &'static str,
+    pub collapsed: &'static str,
+    pub expanded: &'static str,
+    pub dragging: &'static str,
+    pub playing: &'static str,
+    pub paused: &'static str,
+    pub gauge_filled: &'static str,
+    pub gauge_unfilled: &'static str,
+    pub unicode_gauge: bool,
+    pub gauge_cue: &'static str,
+    pub gauge_loop_start: &'static str,
+    pub gauge_loop_end: &'static str,
+}
+
+const EMOJI: Icons = Icons {
+    logo: " 🎵",
+    directory: "📁 ",
+    audio: "🎵 ",
+    codecs: &[],
+    playlist: "📜 ",
+    other: "📄 ",
+    collapsed: "▸ ",
+    expanded: "▾ ",
+    dragging: "⇅ ",
+    playing: "▶",
+    paused: "⏸",
+    gauge_filled: "▬",
+    gauge_unfilled: "─",
+    unicode_gauge: true,
+    gauge_cue: "┃",
+    gauge_loop_start: "┫",
+    gauge_loop_end: "┣",
+};
+
+// Plain ASCII for terminals and fonts that draw emoji double-width.
+const ASCII: Icons = Icons {
+    logo: "",
+    directory: "[D] ",
+    audio: "[M] ",
+    codecs: &[],
+    playlist: "[P] ",
+    other: "[?] ",
+    collapsed: "+ ",
+    expanded: "- ",
+    dragging: "<> ",
+    playing: ">",
+    paused: "||",
+    gauge_filled: "=",
+    gauge_unfilled: "-",
+    unicode_gauge: false,
+    gauge_cue: "|",
+    gauge_loop_start: "[",
+    gauge_loop_end: "]",
+};
+
+// Needs a patched Nerd Font; glyphs are from the Font Awesome and Material
+// Design ranges.
+const NERD: Icons = Icons {
+    logo: " \u{f075a}",
+    directory: "\u{f07b} ",
+    audio: "\u{f001} ",
+    codecs: &[
+        ("flac", "\u{f0223} "),
+        ("mp3", "\u{f1c7} "),
+        ("ogg", "\u{f075a} "),
+        ("wav", "\u{f147d} "),
+    ],
+    playlist: "\u{f0cb8} ",
+    other: "\u{f016} ",
+    collapsed: "\u{f0da} ",
+    expanded: "\u{f0d7} ",
+    dragging: "\u{f04e2} ",
+    playing: "\u{f04b}",
+    paused: "\u{f04c}",
+    gauge_filled: "▬",
+    gauge_unfilled: "─",
+    unicode_gauge: true,
+    gauge_cue: "┃",
+    gauge_loop_start: "┫",
+    gauge_loop_end: "┣",
+};
+
+impl Icons {
+    pub fn audio_for(&self, path: &Path) -> &'static str {
+        let ext = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        self.codecs
+            .iter()
+            .find(|(codec, _)| *codec == ext)
+            .map(|(_, icon)| *icon)
+            .unwrap_or(self.audio)
+    }
+}
+
+impl IconSet {
+    pub fn icons(self) -> &'static Icons {
+        match self {
+            IconSet::Emoji => &EMOJI,
+            IconSet::Ascii => &ASCII,
+            IconSet::Nerd => &NERD,
+        }
+    }
+}
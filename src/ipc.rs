@@ -0,0 +1,214 @@
+use anyhow::{Result, anyhow};
+use crossterm::event::KeyEvent;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+
+#[cfg(unix)]
+use leek_core::paths;
+
+#[cfg(unix)]
+use crate::action::Action;
+use crate::events::Event;
+
+// The player's state as a JSON line, kept current by the main loop for
+// `status` and `watch`.
+pub type SharedStatus = Arc<Mutex<String>>;
+
+// How often `watch` looks for a change to send.
+#[cfg(unix)]
+const WATCH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+// A control socket for scripts, one command per line. `leek duck` and
+// `leek unduck` write to it, as can anything that speaks Unix sockets.
+// Any number of clients can be connected at once.
+#[cfg(unix)]
+fn socket_path() -> Option<PathBuf> {
+    Some(paths::state_dir()?.join("leek.sock"))
+}
+
+#[cfg(unix)]
+enum Command {
+    Action(Action),
+    // Replies with the status line once.
+    Status,
+    // Replies with the status line whenever it changes, until the client
+    // hangs up. Clients keep their end open while they watch.
+    Watch,
+}
+
+#[cfg(unix)]
+fn parse(line: &str) -> Option<Command> {
+    let action = match line.trim() {
+        "duck" => Action::Duck,
+        "unduck" => Action::Unduck,
+        "play-pause" => Action::TogglePlay,
+        "next" => Action::NextTrack,
+        "previous" => Action::PrevTrack,
+        "volume-up" => Action::VolumeUp,
+        "volume-down" => Action::VolumeDown,
+        "seek-forward" => Action::SeekForward,
+        "seek-back" => Action::SeekBack,
+        "status" => return Some(Command::Status),
+        "watch" => return Some(Command::Watch),
+        _ => return None,
+    };
+    Some(Command::Action(action))
+}
+
+// Accepts commands on background threads and hands them to the main loop.
+#[cfg(unix)]
+pub fn listen(events: Sender<Event<KeyEvent>>, status: SharedStatus) -> Result<()> {
+    use leek_core::log;
+    use std::fs::{self, Permissions};
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::thread;
+
+    let path = socket_path().ok_or_else(|| anyhow!("no state directory"))?;
+    if UnixStream::connect(&path).is_ok() {
+        return Err(anyhow!(
+            "another leek is already listening on {}",
+            path.display()
+        ));
+    }
+    // Left behind by a leek that didn't exit cleanly.
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+    // Only the user running leek may control it, even when the state
+    // directory is readable by others.
+    fs::set_permissions(&path, Permissions::from_mode(0o600))?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let events = events.clone();
+                    let status = status.clone();
+                    thread::spawn(move || serve(stream, &events, &status));
+                }
+                Err(e) => log::warn(&format!("Control socket: {}", e)),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(unix)]
+fn serve(
+    stream: std::os::unix::net::UnixStream,
+    events: &Sender<Event<KeyEvent>>,
+    status: &SharedStatus,
+) {
+    use leek_core::log;
+    use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+
+    let Ok(reader) = stream.try_clone() else {
+        return;
+    };
+    let mut writer = stream;
+    let current = || status.lock().map(|s| s.clone()).unwrap_or_default();
+    for line in BufReader::new(reader).lines().map_while(Result::ok) {
+        match parse(&line) {
+            Some(Command::Action(action)) => {
+                if events.send(Event::Action(action)).is_err() {
+                    return;
+                }
+            }
+            Some(Command::Status) => {
+                if writeln!(writer, "{}", current()).is_err() {
+                    return;
+                }
+            }
+            Some(Command::Watch) => {
+                // Waiting on a read rather than sleeping notices a client
+                // that hung up while nothing changed, which writing alone
+                // wouldn't until the next change.
+                if writer.set_read_timeout(Some(WATCH_INTERVAL)).is_err() {
+                    return;
+                }
+                let mut sent = String::new();
+                let mut scratch = [0; 64];
+                loop {
+                    let now = current();
+                    if now != sent {
+                        if writeln!(writer, "{}", now).is_err() {
+                            return;
+                        }
+                        sent = now;
+                    }
+                    match writer.read(&mut scratch) {
+                        Ok(0) => return,
+                        Ok(_) => {}
+                        Err(e)
+                            if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                        Err(_) => return,
+                    }
+                }
+            }
+            None => log::warn(&format!("Control socket: unknown command {:?}", line)),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn listen(_events: Sender<Event<KeyEvent>>, _status: SharedStatus) -> Result<()> {
+    Ok(())
+}
+
+// Sends one command, printing any reply.
+#[cfg(unix)]
+pub fn send(command: &str) -> Result<()> {
+    use std::io::Write;
+    use std::net::Shutdown;
+    use std::os::unix::net::UnixStream;
+
+    let Some(parsed) = parse(command) else {
+        return Err(anyhow!("unknown command: {}", command));
+    };
+    let path = socket_path().ok_or_else(|| anyhow!("no state directory"))?;
+    let mut stream =
+        UnixStream::connect(&path).map_err(|e| anyhow!("leek is not running ({})", e))?;
+    writeln!(stream, "{}", command)?;
+    match parsed {
+        Command::Status => {
+            stream.shutdown(Shutdown::Write)?;
+            print_lines(stream)
+        }
+        Command::Watch => print_lines(stream),
+        Command::Action(_) => Ok(()),
+    }
+}
+
+#[cfg(unix)]
+fn print_lines(stream: std::os::unix::net::UnixStream) -> Result<()> {
+    use std::io::{BufRead, BufReader};
+
+    for line in BufReader::new(stream).lines() {
+        println!("{}", line?);
+    }
+    Ok(())
+}
+
+// One connection for commands and one already watching, for `leek attach`.
+#[cfg(unix)]
+pub fn attach() -> Result<(
+    std::os::unix::net::UnixStream,
+    std::os::unix::net::UnixStream,
+)> {
+    use std::io::Write;
+    use std::os::unix::net::UnixStream;
+
+    let path = socket_path().ok_or_else(|| anyhow!("no state directory"))?;
+    let connect = || UnixStream::connect(&path).map_err(|e| anyhow!("leek is not running ({})", e));
+    let control = connect()?;
+    let mut watch = connect()?;
+    writeln!(watch, "watch")?;
+    Ok((control, watch))
+}
+
+#[cfg(not(unix))]
+pub fn send(_command: &str) -> Result<()> {
+    Err(anyhow!("the control socket needs a Unix system"))
+}
@@ -0,0 +1,38 @@
+use serde_json::{Value, json};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use leek_core::log;
+use leek_core::paths;
+
+// An append-only record of playback, one JSON object per line, for
+// scripts to `tail -f` instead of talking to the control socket.
+fn journal_path() -> Option<PathBuf> {
+    Some(paths::data_dir()?.join("journal.jsonl"))
+}
+
+// Appends `{"event", "time", "path"}` and whatever is in `details`.
+pub fn record(event: &str, path: &Path, details: Value) {
+    let Some(journal) = journal_path() else {
+        return;
+    };
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut entry = json!({ "event": event, "time": time, "path": path });
+    if let (Some(entry), Value::Object(details)) = (entry.as_object_mut(), details) {
+        entry.extend(details);
+    }
+
+    let written = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&journal)
+        .and_then(|mut file| writeln!(file, "{}", entry));
+    if let Err(e) = written {
+        log::warn(&format!("Could not write {}: {}", journal.display(), e));
+    }
+}
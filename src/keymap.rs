@@ -0,0 +1,86 @@
+use crossterm::event::KeyCode;
+use serde::{Deserialize, Deserializer};
+
+// Alternative bindings for keys that need Fn combinations on many laptops.
+// Each action takes a list, so the defaults can be kept alongside new keys.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Keymap {
+    #[serde(deserialize_with = "keys")]
+    pub volume_up: Vec<KeyCode>,
+    #[serde(deserialize_with = "keys")]
+    pub volume_down: Vec<KeyCode>,
+    #[serde(deserialize_with = "keys")]
+    pub seek_back: Vec<KeyCode>,
+    #[serde(deserialize_with = "keys")]
+    pub seek_forward: Vec<KeyCode>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            volume_up: vec![KeyCode::PageUp, KeyCode::Char('=')],
+            volume_down: vec![KeyCode::PageDown, KeyCode::Char('-')],
+            seek_back: vec![KeyCode::Char(',')],
+            seek_forward: vec![KeyCode::Char('.')],
+        }
+    }
+}
+
+fn keys<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<KeyCode>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|name| {
+            parse_key(name)
+                .ok_or_else(|| serde::de::Error::custom(format!("unknown key \"{}\"", name)))
+        })
+        .collect()
+}
+
+// Single characters stand for themselves; everything else is a key name.
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+
+    let code = match name.to_lowercase().as_str() {
+        "space" => KeyCode::Char(' '),
+        "enter" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "esc" => KeyCode::Esc,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" | "pgup" => KeyCode::PageUp,
+        "pagedown" | "pgdn" => KeyCode::PageDown,
+        other => KeyCode::F(other.strip_prefix('f')?.parse().ok()?),
+    };
+    Some(code)
+}
+
+pub fn key_name(code: KeyCode) -> String {
+    match code {
+        KeyCode::Char(' ') => String::from("Space"),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::PageUp => String::from("PgUp"),
+        KeyCode::PageDown => String::from("PgDn"),
+        KeyCode::F(n) => format!("F{}", n),
+        other => format!("{:?}", other),
+    }
+}
+
+// "PgUp|=" for the controls list.
+pub fn label(codes: &[KeyCode]) -> String {
+    codes
+        .iter()
+        .map(|&code| key_name(code))
+        .collect::<Vec<_>>()
+        .join("|")
+}
@@ -1,57 +1,128 @@
 use anyhow::Result;
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture, KeyCode},
+    event::{DisableMouseCapture, EnableMouseCapture, KeyEvent},
     execute,
-    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+    terminal::{
+        EnterAlternateScreen, LeaveAlternateScreen, SetTitle, disable_raw_mode, enable_raw_mode,
+    },
 };
 use ratatui::{Terminal, backend::CrosstermBackend};
+use std::env;
 use std::io;
+use std::path::Path;
 
+mod action;
+mod announce;
 mod app;
+#[cfg(unix)]
+mod attach;
+mod bench;
+mod command_line;
+mod commands;
+mod completions;
+mod config;
+mod crash;
 mod events;
+mod filter;
+mod format;
+#[cfg(test)]
+mod harness;
+mod i18n;
+mod icons;
+mod ipc;
+mod journal;
+mod keymap;
+mod media_keys;
+mod metrics;
+mod modal;
+mod suspend;
 mod ui;
 
 use app::App;
 use events::{Event, Events};
 
+fn handle(app: &mut App, event: Event<KeyEvent>) {
+    match event {
+        Event::Input(key) => {
+            app.note_input();
+            if let Some(action) = action::from_key(app, key) {
+                crash::note_action(action);
+                app.dispatch(action);
+            }
+        }
+        Event::Drag(drag) => app.on_drag(drag),
+        Event::Loaded(loaded) => app.on_loaded(loaded),
+        Event::Action(action) => {
+            crash::note_action(action);
+            app.dispatch(action);
+        }
+        Event::Tick => {
+            app.on_tick();
+        }
+    }
+}
+
 fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+    if let Some(result) = commands::run(&args) {
+        return result;
+    }
+    crash::install();
+    leek_core::backend::set_stream_properties();
+
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new()?;
     let events = Events::new();
+    let mut app = App::new(events.sender())?;
+    events.set_tick_rate(app.config.tick_rate());
+    let mut title = String::new();
+    let mut redraw = true;
+    let mut ticks: u64 = 0;
 
     loop {
-        terminal.draw(|f| ui::draw(f, &app))?;
+        if let Some(text) = app.terminal_title()
+            && text != title
+        {
+            execute!(terminal.backend_mut(), SetTitle(&text))?;
+            title = text;
+        }
+        if let Some(text) = app.announcement.take() {
+            announce::announce(terminal.backend_mut(), app.config.announce, &text)?;
+        }
+        if let Some(external) = app.external.take() {
+            let result = suspend::run(&mut terminal, &events, external);
+            app.external_finished(result);
+        }
+        if redraw {
+            terminal.draw(|f| ui::draw(f, &app))?;
+        }
 
-        match events.next()? {
-            Event::Input(key) => {
-                if matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
-                    break;
-                }
-                match key.code {
-                    KeyCode::Char(' ') => app.toggle_play(),
-                    KeyCode::Up | KeyCode::Char('k') => app.prev_item(),
-                    KeyCode::Down | KeyCode::Char('j') => app.next_item(),
-                    KeyCode::PageUp => app.volume_up(),
-                    KeyCode::PageDown => app.volume_down(),
-                    KeyCode::Enter => app.enter_selected(),
-                    KeyCode::Tab => app.play_folder(),
-                    KeyCode::Backspace => app.go_up(),
-                    KeyCode::Left => app.prev_track(),
-                    KeyCode::Right => app.next_track(),
-                    _ => {}
-                }
-            }
+        let event = events.next()?;
+        // Ticks alone only redraw as often as `redraw_ms` asks.
+        redraw = match event {
             Event::Tick => {
-                app.on_tick();
+                ticks += 1;
+                ticks.is_multiple_of(app.config.ticks_per_redraw())
             }
+            _ => true,
+        };
+        handle(&mut app, event);
+        crash::note_playing(
+            app.player.current().map(Path::to_path_buf),
+            app.player.queue.len(),
+        );
+        if app.quit {
+            break;
         }
+        events.set_tick_rate(app.config.tick_rate());
     }
 
+    app.save_session(true);
+
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
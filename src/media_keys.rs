@@ -0,0 +1,69 @@
+use anyhow::Result;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MediaKey {
+    PlayPause,
+    Next,
+    Previous,
+}
+
+// Grabs the media keys on the X server, so they reach leek even when the
+// terminal isn't focused. Other platforms deliver global hotkeys through a
+// GUI event loop, which a terminal program doesn't run.
+#[cfg(target_os = "linux")]
+pub struct MediaKeys {
+    _manager: global_hotkey::GlobalHotKeyManager,
+    keys: Vec<(u32, MediaKey)>,
+}
+
+#[cfg(target_os = "linux")]
+impl MediaKeys {
+    pub fn register() -> Result<Self> {
+        use global_hotkey::GlobalHotKeyManager;
+        use global_hotkey::hotkey::{Code, HotKey};
+
+        let manager = GlobalHotKeyManager::new()?;
+        let mut keys = Vec::new();
+        for (code, key) in [
+            (Code::MediaPlayPause, MediaKey::PlayPause),
+            (Code::MediaTrackNext, MediaKey::Next),
+            (Code::MediaTrackPrevious, MediaKey::Previous),
+        ] {
+            let hotkey = HotKey::new(None, code);
+            manager.register(hotkey)?;
+            keys.push((hotkey.id(), key));
+        }
+        Ok(Self {
+            _manager: manager,
+            keys,
+        })
+    }
+
+    pub fn poll(&self) -> Option<MediaKey> {
+        use global_hotkey::{GlobalHotKeyEvent, HotKeyState};
+
+        while let Ok(event) = GlobalHotKeyEvent::receiver().try_recv() {
+            if event.state != HotKeyState::Pressed {
+                continue;
+            }
+            if let Some((_, key)) = self.keys.iter().find(|(id, _)| *id == event.id) {
+                return Some(*key);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub struct MediaKeys;
+
+#[cfg(not(target_os = "linux"))]
+impl MediaKeys {
+    pub fn register() -> Result<Self> {
+        Err(anyhow::anyhow!("only supported on Linux (X11)"))
+    }
+
+    pub fn poll(&self) -> Option<MediaKey> {
+        None
+    }
+}
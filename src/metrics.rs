@@ -0,0 +1,260 @@
+use anyhow::{Result, bail};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use leek_core::log;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+// Far more than any scraper sends, so a client can't keep one thread
+// reading forever.
+const MAX_REQUEST: u64 = 8 * 1024;
+
+// Counters for a leek left running on a server, served in Prometheus'
+// text format at `/metrics`.
+pub struct Metrics {
+    started: Instant,
+    tracks_played: AtomicU64,
+    decode_errors: AtomicU64,
+    underruns: AtomicU64,
+    stalled: AtomicBool,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics {
+            started: Instant::now(),
+            tracks_played: AtomicU64::new(0),
+            decode_errors: AtomicU64::new(0),
+            underruns: AtomicU64::new(0),
+            stalled: AtomicBool::new(false),
+        }
+    }
+
+    pub fn track_played(&self) {
+        self.tracks_played.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn decode_error(&self) {
+        self.decode_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Called every tick; a remote track that runs out of downloaded audio
+    // counts once per stall.
+    pub fn note_buffering(&self, stalled: bool) {
+        if stalled && !self.stalled.swap(stalled, Ordering::Relaxed) {
+            self.underruns.fetch_add(1, Ordering::Relaxed);
+        } else if !stalled {
+            self.stalled.store(false, Ordering::Relaxed);
+        }
+    }
+
+    fn render(&self) -> String {
+        let metrics = [
+            (
+                "leek_uptime_seconds",
+                "gauge",
+                "Seconds since leek started.",
+                self.started.elapsed().as_secs(),
+            ),
+            (
+                "leek_tracks_played_total",
+                "counter",
+                "Tracks that started playing.",
+                self.tracks_played.load(Ordering::Relaxed),
+            ),
+            (
+                "leek_decode_errors_total",
+                "counter",
+                "Files skipped because they could not be decoded.",
+                self.decode_errors.load(Ordering::Relaxed),
+            ),
+            (
+                "leek_buffer_underruns_total",
+                "counter",
+                "Times a remote track ran out of downloaded audio.",
+                self.underruns.load(Ordering::Relaxed),
+            ),
+        ];
+        metrics
+            .iter()
+            .map(|(name, kind, help, value)| {
+                format!(
+                    "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n",
+                    name = name,
+                    help = help,
+                    kind = kind,
+                    value = value
+                )
+            })
+            .collect()
+    }
+}
+
+// Answers scrapes on `address`, each on a thread of its own. Addresses
+// other than loopback ones are refused unless `token` is set, and with a
+// token every scrape has to send it as `Authorization: Bearer <token>`.
+pub fn serve(address: &str, token: Option<String>, metrics: Arc<Metrics>) -> Result<()> {
+    let loopback = address
+        .to_socket_addrs()?
+        .all(|address| address.ip().is_loopback());
+    if !loopback && token.is_none() {
+        bail!(
+            "{} is reachable from other machines; set metrics_token to serve it",
+            address
+        );
+    }
+    let listener = TcpListener::bind(address)?;
+    let token = Arc::new(token);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::warn(&format!("Metrics endpoint: {}", e));
+                    continue;
+                }
+            };
+            let metrics = metrics.clone();
+            let token = token.clone();
+            thread::spawn(move || {
+                if let Err(e) = respond(stream, token.as_deref(), &metrics) {
+                    log::warn(&format!("Metrics endpoint: {}", e));
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+// Reads a request, giving up once `deadline` passes however slowly the
+// client sends it.
+struct Deadline<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for Deadline<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::Error::new(ErrorKind::TimedOut, "request took too long"));
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+fn respond(stream: TcpStream, token: Option<&str>, metrics: &Metrics) -> Result<()> {
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let deadline = Instant::now() + REQUEST_TIMEOUT;
+    let mut reader = BufReader::new(
+        Deadline {
+            stream: &stream,
+            deadline,
+        }
+        .take(MAX_REQUEST),
+    );
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    let mut authorized = token.is_none();
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("authorization")
+            && value.trim().strip_prefix("Bearer ") == token
+        {
+            authorized = true;
+        }
+    }
+    if reader.get_ref().limit() == 0 {
+        bail!("request over {} bytes", MAX_REQUEST);
+    }
+
+    let mut stream = &stream;
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+    if !authorized {
+        write!(
+            stream,
+            "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Bearer\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        )?;
+    } else if path == "/metrics" {
+        let body = metrics.render();
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )?;
+    } else {
+        write!(
+            stream,
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn scrape(token: Option<&str>, header: &str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let token = token.map(str::to_string);
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            respond(stream, token.as_deref(), &Metrics::new()).unwrap();
+        });
+        let mut client = TcpStream::connect(address).unwrap();
+        write!(client, "GET /metrics HTTP/1.1\r\n{}\r\n", header).unwrap();
+        let mut reply = String::new();
+        client.read_to_string(&mut reply).unwrap();
+        server.join().unwrap();
+        reply
+    }
+
+    #[test]
+    fn refuses_other_machines_without_a_token() {
+        assert!(serve("0.0.0.0:0", None, Arc::new(Metrics::new())).is_err());
+    }
+
+    #[test]
+    fn refuses_oversized_requests() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            respond(stream, None, &Metrics::new())
+        });
+        let mut client = TcpStream::connect(address).unwrap();
+        let padding = "x".repeat(MAX_REQUEST as usize);
+        write!(
+            client,
+            "GET /metrics HTTP/1.1\r\nX-Padding: {}\r\n\r\n",
+            padding
+        )
+        .unwrap();
+        assert!(server.join().unwrap().is_err());
+    }
+
+    #[test]
+    fn checks_the_token() {
+        assert!(scrape(None, "").starts_with("HTTP/1.1 200"));
+        assert!(scrape(Some("secret"), "").starts_with("HTTP/1.1 401"));
+        assert!(
+            scrape(Some("secret"), "Authorization: Bearer wrong\r\n").starts_with("HTTP/1.1 401")
+        );
+        let reply = scrape(Some("secret"), "Authorization: Bearer secret\r\n");
+        assert!(reply.starts_with("HTTP/1.1 200"));
+        assert!(reply.contains("leek_tracks_played_total 0"));
+    }
+}
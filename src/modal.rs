@@ -0,0 +1,166 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use leek_core::db::Cue;
+
+use crate::config::Setup;
+
+// A dialog drawn over the main view. While one is open it takes every key,
+// and `App::submit_modal` acts on the answer according to its purpose.
+#[derive(Clone, Debug)]
+pub struct Modal {
+    pub title: String,
+    pub kind: ModalKind,
+    pub purpose: Purpose,
+}
+
+#[derive(Clone, Debug)]
+pub enum ModalKind {
+    Confirm {
+        message: String,
+    },
+    Input {
+        input: String,
+    },
+    Picker {
+        items: Vec<PickerItem>,
+        index: usize,
+    },
+    // Read-only label/value rows that scroll.
+    Info {
+        rows: Vec<(String, String)>,
+        scroll: usize,
+    },
+    // A picker narrowed down by typing.
+    Palette {
+        input: String,
+        items: Vec<PickerItem>,
+        index: usize,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct PickerItem {
+    pub detail: String,
+    pub label: String,
+}
+
+// What the dialog is asking about.
+#[derive(Clone, Debug)]
+pub enum Purpose {
+    CueName { position: Duration },
+    JumpToCue { cues: Vec<Cue> },
+    DiscardPlaylist,
+    StillListening,
+    SortQueue,
+    // Files under `dir` are renamed relative to `dest`.
+    RenamePattern { dir: PathBuf, dest: PathBuf },
+    CommandLine,
+    Palette { results: Vec<PathBuf> },
+    TrackInfo,
+    PlaybackErrors,
+    // The first-run setup, carrying what was picked so far.
+    SetupMusicDir,
+    SetupPalette(Setup),
+    SetupMediaKeys(Setup),
+}
+
+impl Modal {
+    pub fn confirm(title: String, message: String, purpose: Purpose) -> Modal {
+        Modal {
+            title,
+            kind: ModalKind::Confirm { message },
+            purpose,
+        }
+    }
+
+    pub fn input(title: String, input: String, purpose: Purpose) -> Modal {
+        Modal {
+            title,
+            kind: ModalKind::Input { input },
+            purpose,
+        }
+    }
+
+    pub fn picker(title: String, items: Vec<PickerItem>, purpose: Purpose) -> Modal {
+        Modal {
+            title,
+            kind: ModalKind::Picker { items, index: 0 },
+            purpose,
+        }
+    }
+
+    pub fn info(title: String, rows: Vec<(String, String)>, purpose: Purpose) -> Modal {
+        Modal {
+            title,
+            kind: ModalKind::Info { rows, scroll: 0 },
+            purpose,
+        }
+    }
+
+    pub fn palette(title: String, purpose: Purpose) -> Modal {
+        Modal {
+            title,
+            kind: ModalKind::Palette {
+                input: String::new(),
+                items: Vec::new(),
+                index: 0,
+            },
+            purpose,
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        if let ModalKind::Input { input } | ModalKind::Palette { input, .. } = &mut self.kind {
+            input.push(c);
+        }
+    }
+
+    pub fn pop(&mut self) {
+        if let ModalKind::Input { input } | ModalKind::Palette { input, .. } = &mut self.kind {
+            input.pop();
+        }
+    }
+
+    pub fn next(&mut self) {
+        if let ModalKind::Picker { items, index } | ModalKind::Palette { items, index, .. } =
+            &mut self.kind
+            && !items.is_empty()
+        {
+            *index = (*index + 1) % items.len();
+        }
+        if let ModalKind::Info { rows, scroll } = &mut self.kind {
+            *scroll = (*scroll + 1).min(rows.len().saturating_sub(1));
+        }
+    }
+
+    pub fn prev(&mut self) {
+        if let ModalKind::Picker { items, index } | ModalKind::Palette { items, index, .. } =
+            &mut self.kind
+            && !items.is_empty()
+        {
+            *index = index.checked_sub(1).unwrap_or(items.len() - 1);
+        }
+        if let ModalKind::Info { scroll, .. } = &mut self.kind {
+            *scroll = scroll.saturating_sub(1);
+        }
+    }
+
+    // Drops the selected picker row, returning its position.
+    pub fn remove_selected(&mut self) -> Option<usize> {
+        let ModalKind::Picker { items, index } = &mut self.kind else {
+            return None;
+        };
+        if items.is_empty() {
+            return None;
+        }
+        let removed = *index;
+        items.remove(removed);
+        *index = (*index).min(items.len().saturating_sub(1));
+        Some(removed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(&self.kind, ModalKind::Picker { items, .. } if items.is_empty())
+    }
+}
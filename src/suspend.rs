@@ -0,0 +1,83 @@
+use anyhow::{Result, anyhow};
+use crossterm::{
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+};
+use ratatui::{Terminal, backend::CrosstermBackend};
+use std::env;
+use std::io::{self, Stdout, Write};
+use std::process::Command;
+
+use crate::events::Events;
+
+pub struct External {
+    pub command: Command,
+    // Holds the normal screen until Enter, so the output can be read.
+    pub wait: bool,
+}
+
+impl External {
+    pub fn program(command: Command) -> External {
+        External {
+            command,
+            wait: false,
+        }
+    }
+
+    // `$SHELL`, for as long as the user wants it.
+    pub fn shell() -> External {
+        let shell = env::var_os("SHELL").unwrap_or_else(|| "sh".into());
+        External::program(Command::new(shell))
+    }
+
+    pub fn shell_command(line: &str) -> External {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(line);
+        External {
+            command,
+            wait: true,
+        }
+    }
+}
+
+// Hands the terminal to another program: key reading stops, the normal
+// screen comes back, and everything is restored once the program exits.
+pub fn run(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    events: &Events,
+    external: External,
+) -> Result<()> {
+    let External { mut command, wait } = external;
+    events.pause();
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )?;
+    terminal.show_cursor()?;
+
+    let status = command.status();
+    if wait {
+        print!("\n[Press Enter to return to leek]");
+        io::stdout().flush()?;
+        io::stdin().read_line(&mut String::new())?;
+    }
+
+    enable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        EnterAlternateScreen,
+        EnableMouseCapture
+    )?;
+    terminal.clear()?;
+    events.resume();
+
+    let program = command.get_program().to_string_lossy().into_owned();
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(anyhow!("{} exited with {}", program, status)),
+        Err(e) => Err(anyhow!("{}: {}", program, e)),
+    }
+}
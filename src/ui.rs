@@ -3,10 +3,27 @@ use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Gauge, LineGauge, List, ListItem, ListState, Paragraph, Wrap},
+    widgets::{
+        Block, Borders, Clear, Gauge, LineGauge, List, ListItem, ListState, Paragraph, Wrap,
+    },
 };
 
-use crate::app::{App, FileType};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::time::Duration;
+
+use leek_core::charts::{ChartKind, Window};
+use leek_core::folder::{self, Thumbnail};
+use leek_core::groups::{self, GroupedList, Row};
+use leek_core::library::Track;
+use leek_core::rename::Outcome;
+
+use crate::app::{App, FileType, Focus, Osd, View};
+use crate::config::{Config, NarrowLayout, Table};
+use crate::format::format_duration;
+use crate::i18n::tr;
+use crate::keymap;
+use crate::modal::{Modal, ModalKind};
 
 pub fn draw(f: &mut Frame, app: &App) {
     let chunks = Layout::default()
@@ -21,78 +38,473 @@ pub fn draw(f: &mut Frame, app: &App) {
     draw_header(f, app, chunks[0]);
     draw_main(f, app, chunks[1]);
     draw_footer(f, app, chunks[2]);
+
+    if let Some(osd) = &app.osd {
+        draw_osd(f, app, osd);
+    }
+    if let Some(modal) = &app.modal {
+        draw_modal(f, app, modal);
+    }
+}
+
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+// The new track over everything else, for a moment after it starts.
+fn draw_osd(f: &mut Frame, app: &App, osd: &Osd) {
+    let theme = app.config.theme();
+    let cover = app.covers.get(&osd.path);
+    let show_cover = cover.is_some() && !app.config.text_only();
+    let area = centered(f.area(), 50, if show_cover { 8 } else { 5 });
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
+    let inner = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let text_area = match cover {
+        Some(cover) if show_cover => {
+            let rows = inner.height;
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Length(rows * 2 + 1), Constraint::Min(0)])
+                .split(inner);
+            f.render_widget(
+                Paragraph::new(cover_lines(cover, rows * 2, rows)),
+                chunks[0],
+            );
+            chunks[1]
+        }
+        _ => inner,
+    };
+
+    let mut text = vec![Line::from(Span::styled(
+        osd.title.clone(),
+        Style::default()
+            .fg(theme.accent)
+            .add_modifier(Modifier::BOLD),
+    ))];
+    if let Some(artist) = &osd.artist {
+        text.push(Line::from(Span::styled(
+            artist.clone(),
+            Style::default().fg(theme.text),
+        )));
+    }
+    if let Some(album) = &osd.album {
+        text.push(Line::from(Span::styled(
+            album.clone(),
+            Style::default().fg(theme.secondary),
+        )));
+    }
+    f.render_widget(Paragraph::new(text).wrap(Wrap { trim: true }), text_area);
+}
+
+// Dialogs share one look: a bordered box in the middle of the screen with
+// the key hints along the bottom edge.
+fn draw_modal(f: &mut Frame, app: &App, modal: &Modal) {
+    let theme = app.config.theme();
+    let hint = match &modal.kind {
+        ModalKind::Confirm { .. } => tr!("modal.confirm_hint"),
+        ModalKind::Input { .. } => tr!("modal.input_hint"),
+        ModalKind::Picker { .. } => tr!("modal.picker_hint"),
+        ModalKind::Palette { .. } => tr!("modal.palette_hint"),
+        ModalKind::Info { .. } => tr!("modal.info_hint"),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(modal.title.as_str())
+        .title_bottom(Line::from(hint).alignment(Alignment::Right))
+        .border_style(Style::default().fg(theme.border));
+
+    match &modal.kind {
+        ModalKind::Confirm { message } => {
+            let area = centered(f.area(), 50, 5);
+            let text = Paragraph::new(message.as_str())
+                .style(Style::default().fg(theme.text))
+                .wrap(Wrap { trim: true })
+                .block(block);
+            f.render_widget(Clear, area);
+            f.render_widget(text, area);
+        }
+        ModalKind::Input { input } => {
+            let area = centered(f.area(), 50, 3);
+            let text = Paragraph::new(Span::styled(
+                format!("{}_", input),
+                Style::default().fg(theme.text),
+            ))
+            .block(block);
+            f.render_widget(Clear, area);
+            f.render_widget(text, area);
+        }
+        ModalKind::Picker { items, index } => {
+            let width = items
+                .iter()
+                .map(|item| item.detail.chars().count() + item.label.chars().count() + 9)
+                .max()
+                .unwrap_or_default()
+                .max(50) as u16;
+            let area = centered(f.area(), width, items.len() as u16 + 2);
+            let items: Vec<ListItem> = items
+                .iter()
+                .map(|item| {
+                    ListItem::new(Line::from(vec![
+                        Span::styled(
+                            format!("{:>7}  ", item.detail),
+                            Style::default().fg(theme.border),
+                        ),
+                        Span::styled(item.label.clone(), Style::default().fg(theme.text)),
+                    ]))
+                })
+                .collect();
+
+            let mut state = ListState::default();
+            state.select(Some(*index));
+            let list = List::new(items)
+                .block(block)
+                .highlight_style(
+                    Style::default()
+                        .fg(theme.accent)
+                        .add_modifier(Modifier::BOLD | Modifier::REVERSED),
+                )
+                .highlight_symbol(">> ");
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(list, area, &mut state);
+        }
+        ModalKind::Info { rows, scroll } => {
+            let label_width = rows
+                .iter()
+                .map(|(label, _)| label.chars().count())
+                .max()
+                .unwrap_or_default();
+            let area = centered(f.area(), 90, rows.len() as u16 + 2);
+            let lines: Vec<Line> = rows
+                .iter()
+                .map(|(label, value)| {
+                    Line::from(vec![
+                        Span::styled(
+                            format!("{:<width$}  ", label, width = label_width),
+                            Style::default().fg(theme.border),
+                        ),
+                        Span::styled(value.clone(), Style::default().fg(theme.text)),
+                    ])
+                })
+                .collect();
+            let text = Paragraph::new(lines)
+                .block(block)
+                .scroll((*scroll as u16, 0));
+            f.render_widget(Clear, area);
+            f.render_widget(text, area);
+        }
+        ModalKind::Palette {
+            input,
+            items,
+            index,
+        } => {
+            let area = centered(f.area(), 80, 20);
+            f.render_widget(Clear, area);
+            let inner = block.inner(area);
+            f.render_widget(block, area);
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1), Constraint::Min(0)])
+                .split(inner);
+
+            f.render_widget(
+                Paragraph::new(Span::styled(
+                    format!("> {}_", input),
+                    Style::default().fg(theme.text),
+                )),
+                rows[0],
+            );
+            let items: Vec<ListItem> = items
+                .iter()
+                .map(|item| {
+                    ListItem::new(Line::from(vec![
+                        Span::styled(
+                            format!("{:>7}  ", item.detail),
+                            Style::default().fg(theme.border),
+                        ),
+                        Span::styled(item.label.clone(), Style::default().fg(theme.text)),
+                    ]))
+                })
+                .collect();
+            let mut state = ListState::default();
+            state.select((!items.is_empty()).then_some(*index));
+            let list = List::new(items)
+                .highlight_style(
+                    Style::default()
+                        .fg(theme.accent)
+                        .add_modifier(Modifier::BOLD | Modifier::REVERSED),
+                )
+                .highlight_symbol(">> ");
+            f.render_stateful_widget(list, rows[1], &mut state);
+        }
+    }
 }
 
 fn draw_header(f: &mut Frame, app: &App, area: Rect) {
-    let title_text = format!("LEEK 🎵 - {}", app.current_directory.to_string_lossy());
+    let theme = app.config.theme();
+    let title_text = tr!(
+        "app.title",
+        logo = app.config.icons.icons().logo,
+        dir = app.current_directory.to_string_lossy()
+    );
     let title = Paragraph::new(title_text)
         .style(
             Style::default()
-                .fg(Color::Cyan)
+                .fg(theme.accent)
                 .add_modifier(Modifier::BOLD),
         )
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::LightBlue)),
+                .border_style(Style::default().fg(theme.border)),
         )
         .alignment(Alignment::Center);
     f.render_widget(title, area);
 }
 
 fn draw_main(f: &mut Frame, app: &App, area: Rect) {
-    let chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage(60),
-            Constraint::Percentage(40),
-        ])
-        .split(area);
+    // Side by side, neither pane has room to be read on a narrow terminal.
+    let narrow = area.width < app.config.narrow_columns;
+    let chunks = match app.config.narrow_layout {
+        NarrowLayout::Hide if narrow => Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(0)])
+            .split(area),
+        NarrowLayout::Stack if narrow => Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(area),
+        _ => Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(area),
+    };
+
+    match (app.focus, app.view) {
+        (Focus::Search, _) => draw_search(f, app, chunks[0]),
+        (Focus::Queue, _) => draw_queue(f, app, chunks[0]),
+        (Focus::Playlist, _) => draw_playlist_editor(f, app, chunks[0]),
+        (Focus::Browser, View::Inbox) => draw_inbox(f, app, chunks[0]),
+        (Focus::Browser, View::Tracks) => draw_track_list(f, app, chunks[0]),
+        (Focus::Browser, View::Charts) => draw_charts(f, app, chunks[0]),
+        (Focus::Browser, View::Rename) => draw_rename(f, app, chunks[0]),
+        (Focus::Browser, View::Arrivals) => draw_arrivals(f, app, chunks[0]),
+        (Focus::Browser, View::Browser) => draw_browser(f, app, chunks[0]),
+    }
+
+    if chunks[1].is_empty() {
+        return;
+    }
+    if app.focus == Focus::Browser
+        && app.view == View::Browser
+        && let Some(dir) = app.selected_directory()
+    {
+        let info = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(10), Constraint::Min(0)])
+            .split(chunks[1]);
+        draw_folder_preview(f, app, dir, info[0]);
+        draw_info(f, app, info[1]);
+    } else {
+        draw_info(f, app, chunks[1]);
+    }
+}
+
+fn draw_folder_preview(f: &mut Frame, app: &App, dir: &Path, area: Rect) {
+    let theme = app.config.theme();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(tr!("folder.title"))
+        .border_style(Style::default().fg(theme.border));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let summary = match app.folder_summaries.get(dir) {
+        Some(summary) => summary,
+        None => {
+            let loading =
+                Paragraph::new(tr!("folder.loading")).style(Style::default().fg(theme.muted));
+            f.render_widget(loading, inner);
+            return;
+        }
+    };
+
+    let text_area = match &summary.cover {
+        Some(cover) if !app.config.text_only() => {
+            // Half blocks give two square-ish pixels per cell.
+            let rows = inner.height;
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Length(rows * 2 + 1), Constraint::Min(0)])
+                .split(inner);
+            f.render_widget(
+                Paragraph::new(cover_lines(cover, rows * 2, rows)),
+                chunks[0],
+            );
+            chunks[1]
+        }
+        _ => inner,
+    };
+
+    let text = vec![
+        Line::from(Span::styled(
+            tr!("folder.tracks", count = summary.tracks),
+            Style::default().fg(theme.text),
+        )),
+        Line::from(Span::styled(
+            tr!(
+                "folder.duration",
+                duration = format_duration(summary.duration)
+            ),
+            Style::default().fg(theme.text),
+        )),
+        Line::from(Span::styled(
+            tr!("folder.size", size = folder::format_size(summary.size)),
+            Style::default().fg(theme.text),
+        )),
+        Line::from(Span::styled(
+            tr!("folder.formats", formats = summary.formats.join(", ")),
+            Style::default().fg(theme.secondary),
+        )),
+    ];
+    f.render_widget(Paragraph::new(text).wrap(Wrap { trim: true }), text_area);
+}
+
+// Cue points and loop bounds drawn over the progress bar, which starts one
+// column after its label.
+fn draw_progress_marks(f: &mut Frame, app: &App, area: Rect, label_width: u16) {
+    let theme = app.config.theme();
+    let icons = app.config.icons.icons();
+    let Some(duration) = app.player.duration.filter(|d| !d.is_zero()) else {
+        return;
+    };
+    let start = area.x + label_width + 1;
+    if start >= area.right() {
+        return;
+    }
+    let width = area.right() - start;
 
-    draw_browser(f, app, chunks[0]);
-    draw_info(f, app, chunks[1]);
+    let mut marks: Vec<(Duration, &str, Color)> = app
+        .cue_marks
+        .iter()
+        .map(|&position| (position, icons.gauge_cue, theme.secondary))
+        .collect();
+    if let Some(points) = app.player.loop_points {
+        marks.push((points.start_time(), icons.gauge_loop_start, theme.status));
+        marks.push((points.end_time(), icons.gauge_loop_end, theme.status));
+    }
+
+    let buffer = f.buffer_mut();
+    for (position, symbol, colour) in marks {
+        let ratio = (position.as_secs_f64() / duration.as_secs_f64()).clamp(0.0, 1.0);
+        let column = start + ((width as f64 * ratio) as u16).min(width - 1);
+        buffer[(column, area.y)].set_symbol(symbol).set_fg(colour);
+    }
+}
+
+fn cover_lines(cover: &Thumbnail, columns: u16, rows: u16) -> Vec<Line<'static>> {
+    let sample = |x: u16, y: u16| {
+        let [r, g, b] = cover.pixel(
+            x as u32 * cover.width / columns as u32,
+            y as u32 * cover.height / (rows as u32 * 2),
+        );
+        Color::Rgb(r, g, b)
+    };
+
+    (0..rows)
+        .map(|row| {
+            Line::from(
+                (0..columns)
+                    .map(|column| {
+                        Span::styled(
+                            "\u{2580}",
+                            Style::default()
+                                .fg(sample(column, row * 2))
+                                .bg(sample(column, row * 2 + 1)),
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect()
 }
 
 fn draw_browser(f: &mut Frame, app: &App, area: Rect) {
+    let theme = app.config.theme();
+    let icons = app.config.icons.icons();
+    let visual = app.visual_range(Focus::Browser);
     let items: Vec<ListItem> = app
         .browser_items
         .iter()
         .enumerate()
         .map(|(i, item)| {
             let icon = match item.file_type {
-                FileType::Directory => "📁 ",
-                FileType::AudioFile => "🎵 ",
-                FileType::Other => "📄 ",
+                FileType::Directory => icons.directory,
+                FileType::AudioFile => icons.audio_for(&item.path),
+                FileType::Playlist => icons.playlist,
+                FileType::Other => icons.other,
             };
 
+            let selected = visual.as_ref().is_some_and(|range| range.contains(&i));
             let style = if i == app.browser_index {
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(theme.accent)
                     .add_modifier(Modifier::BOLD | Modifier::REVERSED)
+            } else if selected {
+                Style::default()
+                    .fg(theme.accent)
+                    .add_modifier(Modifier::REVERSED)
             } else {
-                Style::default().fg(Color::White)
+                Style::default().fg(theme.text)
             };
 
-            ListItem::new(Line::from(vec![
-                Span::styled(icon, style),
-                Span::styled(item.name.clone(), style),
-            ]))
+            // Marked in text too, for palettes and terminals where the
+            // highlight is hard to see.
+            let mut spans = Vec::new();
+            if visual.is_some() {
+                spans.push(Span::styled(if selected { "+ " } else { "  " }, style));
+            }
+            spans.push(Span::styled(icon, style));
+            spans.push(Span::styled(item.name.clone(), style));
+            ListItem::new(Line::from(spans))
         })
         .collect();
 
     let mut state = ListState::default();
     state.select(Some(app.browser_index));
 
+    let title = if app.loading.is_some() {
+        tr!("browser.loading")
+    } else if let Some(filter) = &app.browser_filter {
+        tr!(
+            "browser.filtered",
+            filter = filter.text,
+            shown = app.browser_items.len()
+        )
+    } else {
+        tr!("browser.title")
+    };
     let list = List::new(items)
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title(" File Browser ")
-                .border_style(Style::default().fg(Color::LightBlue)),
+                .title(title)
+                .border_style(Style::default().fg(theme.border)),
         )
         .highlight_style(
             Style::default()
-                .fg(Color::Cyan)
+                .fg(theme.accent)
                 .add_modifier(Modifier::BOLD),
         )
         .highlight_symbol(">> ");
@@ -100,77 +512,761 @@ fn draw_browser(f: &mut Frame, app: &App, area: Rect) {
     f.render_stateful_widget(list, area, &mut state);
 }
 
-fn draw_info(f: &mut Frame, app: &App, area: Rect) {
-    let current_song = if !app.queue.is_empty() && app.queue_index < app.queue.len() {
-        app.queue[app.queue_index]
+fn draw_search(f: &mut Frame, app: &App, area: Rect) {
+    let Some(search) = &app.search else {
+        return;
+    };
+
+    let entries: Vec<(&Path, Option<&Track>)> = search
+        .results
+        .iter()
+        .map(|&i| {
+            let track = &app.library.tracks[i];
+            (track.path.as_path(), Some(track))
+        })
+        .collect();
+
+    let title = tr!("search.title", input = search.input);
+    draw_grouped(
+        f,
+        area,
+        &title,
+        &entries,
+        &search.list,
+        Marks::default(),
+        &app.config,
+    );
+}
+
+fn draw_inbox(f: &mut Frame, app: &App, area: Rect) {
+    let entries: Vec<(&Path, Option<&Track>)> = app
+        .inbox
+        .iter()
+        .map(|path| (path.as_path(), app.library.find(path)))
+        .collect();
+
+    draw_grouped(
+        f,
+        area,
+        &tr!("inbox.title", count = app.inbox.len()),
+        &entries,
+        &app.inbox_list,
+        Marks::default(),
+        &app.config,
+    );
+}
+
+fn draw_charts(f: &mut Frame, app: &App, area: Rect) {
+    let theme = app.config.theme();
+    let charts = &app.charts;
+    let items: Vec<ListItem> = charts
+        .entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{:>3}. ", i + 1), Style::default().fg(theme.border)),
+                Span::styled(entry.label.clone(), Style::default().fg(theme.text)),
+                Span::styled(
+                    tr!("charts.plays", count = entry.plays),
+                    Style::default().fg(theme.muted),
+                ),
+            ]))
+        })
+        .collect();
+
+    let kind = match charts.kind {
+        ChartKind::Tracks => tr!("charts.tracks"),
+        ChartKind::Artists => tr!("charts.artists"),
+        ChartKind::Albums => tr!("charts.albums"),
+    };
+    let window = match charts.window {
+        Window::Week => tr!("charts.week"),
+        Window::Month => tr!("charts.month"),
+        Window::Year => tr!("charts.year"),
+        Window::AllTime => tr!("charts.all_time"),
+    };
+
+    let mut state = ListState::default();
+    state.select(Some(charts.index));
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(tr!("charts.title", kind = kind, window = window))
+                .border_style(Style::default().fg(theme.border)),
+        )
+        .highlight_style(
+            Style::default()
+                .fg(theme.accent)
+                .add_modifier(Modifier::BOLD | Modifier::REVERSED),
+        )
+        .highlight_symbol(">> ");
+
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_arrivals(f: &mut Frame, app: &App, area: Rect) {
+    let theme = app.config.theme();
+    let arrivals = &app.arrivals;
+    let items: Vec<ListItem> = if arrivals.list.is_empty() {
+        vec![ListItem::new(Span::styled(
+            tr!("arrivals.none"),
+            Style::default().fg(theme.muted),
+        ))]
+    } else {
+        arrivals
+            .list
+            .iter()
+            .map(|arrival| {
+                let name = arrival
+                    .dir
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy();
+                ListItem::new(Line::from(vec![
+                    Span::styled(name.into_owned(), Style::default().fg(theme.text)),
+                    Span::styled(
+                        tr!("arrivals.tracks", count = arrival.tracks),
+                        Style::default().fg(theme.muted),
+                    ),
+                ]))
+            })
+            .collect()
+    };
+
+    let mut state = ListState::default();
+    if !arrivals.list.is_empty() {
+        state.select(Some(arrivals.index));
+    }
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(tr!("arrivals.title", count = arrivals.list.len()))
+                .border_style(Style::default().fg(theme.border)),
+        )
+        .highlight_style(
+            Style::default()
+                .fg(theme.accent)
+                .add_modifier(Modifier::BOLD | Modifier::REVERSED),
+        )
+        .highlight_symbol(">> ");
+
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_rename(f: &mut Frame, app: &App, area: Rect) {
+    let theme = app.config.theme();
+    let Some(preview) = &app.rename else {
+        return;
+    };
+    let relative = |path: &Path| {
+        path.strip_prefix(&preview.dir)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    };
+    let items: Vec<ListItem> = preview
+        .renames
+        .iter()
+        .map(|rename| {
+            let (note, color) = match rename.outcome {
+                Outcome::Rename => (String::new(), theme.text),
+                Outcome::Unchanged => (tr!("rename.unchanged"), theme.muted),
+                Outcome::MissingTags => (tr!("rename.missing_tags"), theme.error),
+                Outcome::Conflict => (tr!("rename.conflict"), theme.error),
+            };
+            let mut spans = vec![Span::styled(
+                relative(&rename.from),
+                Style::default().fg(color),
+            )];
+            if rename.outcome == Outcome::Rename || rename.outcome == Outcome::Conflict {
+                spans.push(Span::styled(" → ", Style::default().fg(theme.border)));
+                spans.push(Span::styled(
+                    relative(&rename.to),
+                    Style::default().fg(color),
+                ));
+            }
+            spans.push(Span::styled(note, Style::default().fg(theme.muted)));
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+
+    let mut state = ListState::default();
+    state.select(Some(preview.index));
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(tr!(
+                    "rename.title",
+                    count = preview.count(Outcome::Rename),
+                    skipped = preview.renames.len() - preview.count(Outcome::Rename)
+                ))
+                .border_style(Style::default().fg(theme.border)),
+        )
+        .highlight_style(
+            Style::default()
+                .fg(theme.accent)
+                .add_modifier(Modifier::BOLD | Modifier::REVERSED),
+        )
+        .highlight_symbol(">> ");
+
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_track_list(f: &mut Frame, app: &App, area: Rect) {
+    let entries: Vec<(&Path, Option<&Track>)> = app
+        .track_list
+        .paths
+        .iter()
+        .map(|path| (path.as_path(), app.library.find(path)))
+        .collect();
+
+    draw_grouped(
+        f,
+        area,
+        &app.track_list.title,
+        &entries,
+        &app.track_list.list,
+        Marks::default(),
+        &app.config,
+    );
+}
+
+fn draw_queue(f: &mut Frame, app: &App, area: Rect) {
+    let theme = app.config.theme();
+    let entries: Vec<(&Path, Option<&Track>)> = app
+        .player
+        .queue
+        .iter()
+        .map(|path| (path.as_path(), app.library.find(path)))
+        .collect();
+
+    let (rows, offset) = draw_grouped(
+        f,
+        area,
+        &tr!("queue.title"),
+        &entries,
+        &app.queue_list,
+        Marks {
+            playing: Some(app.player.queue_index),
+            selected: app.visual_range(Focus::Queue),
+        },
+        &app.config,
+    );
+    app.queue_layout.set((rows, offset));
+
+    if let Some(drag) = &app.queue_drag
+        && drag.hover >= rows.y
+        && drag.hover < rows.bottom()
+    {
+        let name = app.player.queue[drag.from]
             .file_name()
             .unwrap_or_default()
-            .to_string_lossy()
-            .into_owned()
+            .to_string_lossy();
+        let ghost_area = Rect::new(rows.x, drag.hover, rows.width, 1);
+        let icons = app.config.icons.icons();
+        let ghost = Paragraph::new(format!("{}{}", icons.dragging, name)).style(
+            Style::default()
+                .fg(theme.background)
+                .bg(theme.border)
+                .add_modifier(Modifier::ITALIC),
+        );
+        f.render_widget(Clear, ghost_area);
+        f.render_widget(ghost, ghost_area);
+    }
+}
+
+fn draw_playlist_editor(f: &mut Frame, app: &App, area: Rect) {
+    let theme = app.config.theme();
+    let Some(editor) = &app.playlist_editor else {
+        return;
+    };
+
+    let items: Vec<ListItem> = editor
+        .entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let path = &entry.path;
+            let name = match app.library.find(path).map(|t| &t.tags) {
+                Some(tags) if tags.title.is_some() => format!(
+                    "{} - {}",
+                    tags.artist.clone().unwrap_or_default(),
+                    tags.title.clone().unwrap_or_default()
+                ),
+                _ => path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+            };
+            let (style, missing) = if path.exists() {
+                (Style::default().fg(theme.text), String::new())
+            } else if app.config.accessible {
+                (Style::default().fg(theme.error), tr!("playlist.missing"))
+            } else {
+                (Style::default().fg(theme.error), String::new())
+            };
+            ListItem::new(Line::from(Span::styled(
+                format!("{:3}. {}{}", i + 1, name, missing),
+                style,
+            )))
+        })
+        .collect();
+
+    let mut state = ListState::default();
+    state.select(Some(editor.index));
+
+    let name = editor
+        .path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let modified = if editor.dirty {
+        tr!("playlist.modified")
     } else {
-        "No song playing".to_string()
+        String::new()
     };
+    let title = tr!("playlist.title", name = name, modified = modified);
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .border_style(Style::default().fg(theme.border)),
+        )
+        .highlight_style(
+            Style::default()
+                .fg(theme.accent)
+                .add_modifier(Modifier::BOLD | Modifier::REVERSED),
+        )
+        .highlight_symbol(">> ");
+
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+// The highlight symbol and indent before each track's text.
+const ROW_PREFIX: u16 = 7;
+
+#[derive(Clone, Copy)]
+enum Column {
+    Title,
+    Artist,
+    Album,
+    Duration,
+}
+
+// The table's shown columns and their widths, for rows `width` wide.
+fn table_columns(table: &Table, width: u16) -> Vec<(Column, usize)> {
+    let columns: Vec<(Column, Constraint)> = [
+        (Column::Title, Constraint::Percentage(table.title)),
+        (Column::Artist, Constraint::Percentage(table.artist)),
+        (Column::Album, Constraint::Percentage(table.album)),
+        (Column::Duration, Constraint::Length(table.duration)),
+    ]
+    .into_iter()
+    .filter(|(_, constraint)| {
+        !matches!(
+            constraint,
+            Constraint::Percentage(0) | Constraint::Length(0)
+        )
+    })
+    .collect();
+    let widths = Layout::horizontal(columns.iter().map(|(_, constraint)| *constraint))
+        .spacing(1)
+        .split(Rect::new(0, 0, width, 1));
+    columns
+        .iter()
+        .zip(widths.iter())
+        .map(|((column, _), rect)| (*column, rect.width as usize))
+        .collect()
+}
+
+// Pads or cuts `text` to exactly `width` characters.
+fn fit(text: &str, width: usize, right: bool) -> String {
+    if text.chars().count() > width {
+        let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+        if width > 0 {
+            cut.push('…');
+        }
+        cut
+    } else if right {
+        format!("{:>width$}", text)
+    } else {
+        format!("{:<width$}", text)
+    }
+}
+
+fn table_cells(columns: &[(Column, usize)], cell: impl Fn(Column) -> String) -> String {
+    columns
+        .iter()
+        .map(|&(column, width)| fit(&cell(column), width, matches!(column, Column::Duration)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Entries a grouped list shows apart from the rest.
+#[derive(Default)]
+struct Marks {
+    playing: Option<usize>,
+    selected: Option<RangeInclusive<usize>>,
+}
+
+fn draw_grouped(
+    f: &mut Frame,
+    area: Rect,
+    title: &str,
+    entries: &[(&Path, Option<&Track>)],
+    list: &GroupedList,
+    marks: Marks,
+    config: &Config,
+) -> (Rect, usize) {
+    let theme = config.theme();
+    let icons = config.icons.icons();
+    let groups = groups::build(entries.iter().copied());
+    let rows = groups::rows(&groups, &list.collapsed);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title.to_string())
+        .border_style(Style::default().fg(theme.border));
+    let mut inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let columns = config
+        .table
+        .enabled
+        .then(|| table_columns(&config.table, inner.width.saturating_sub(ROW_PREFIX)));
+    if let Some(columns) = &columns
+        && inner.height > 1
+    {
+        let heading = table_cells(columns, |column| match column {
+            Column::Title => tr!("table.title"),
+            Column::Artist => tr!("table.artist"),
+            Column::Album => tr!("table.album"),
+            Column::Duration => tr!("table.duration"),
+        });
+        f.render_widget(
+            Paragraph::new(format!("{}{}", " ".repeat(ROW_PREFIX as usize), heading)).style(
+                Style::default()
+                    .fg(theme.heading)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Rect::new(inner.x, inner.y, inner.width, 1),
+        );
+        inner.y += 1;
+        inner.height -= 1;
+    }
+
+    let items: Vec<ListItem> = rows
+        .iter()
+        .map(|row| match *row {
+            Row::Header(g) => {
+                let group = &groups[g];
+                let marker = if list.collapsed.contains(&group.album) {
+                    icons.collapsed
+                } else {
+                    icons.expanded
+                };
+                let year = group.year.map(|y| format!(" ({})", y)).unwrap_or_default();
+                let artist = group
+                    .artist
+                    .as_ref()
+                    .map(|a| format!("{} - ", a))
+                    .unwrap_or_default();
+
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        format!("{}{}{}{}", marker, artist, group.album, year),
+                        Style::default()
+                            .fg(theme.border)
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                    Span::styled(
+                        tr!(
+                            "queue.group_summary",
+                            count = group.len,
+                            duration = format_duration(group.duration)
+                        ),
+                        Style::default().fg(theme.muted),
+                    ),
+                ]))
+            }
+            Row::Item(i) => {
+                let (path, track) = entries[i];
+                let tags = track.map(|t| &t.tags);
+                let title = tags.and_then(|t| t.title.clone()).unwrap_or_else(|| {
+                    path.file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned()
+                });
+                let number = tags
+                    .and_then(|t| t.track_number)
+                    .map(|n| format!("{:02} ", n))
+                    .unwrap_or_default();
+                let artist = tags
+                    .and_then(|t| t.artist.clone())
+                    .map(|a| format!("  {}", a))
+                    .unwrap_or_default();
+
+                let mut style = if marks.playing == Some(i) {
+                    Style::default()
+                        .fg(theme.accent)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(theme.text)
+                };
+                let selected = marks
+                    .selected
+                    .as_ref()
+                    .is_some_and(|range| range.contains(&i));
+                if selected {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+
+                let indent = match (config.accessible && marks.playing == Some(i), selected) {
+                    (true, true) => " *+ ",
+                    (true, false) => "  * ",
+                    (false, true) => "  + ",
+                    (false, false) => "    ",
+                };
+
+                if let Some(columns) = &columns {
+                    let cells = table_cells(columns, |column| match column {
+                        Column::Title => format!("{}{}", number, title),
+                        Column::Artist => tags.and_then(|t| t.artist.clone()).unwrap_or_default(),
+                        Column::Album => tags.and_then(|t| t.album.clone()).unwrap_or_default(),
+                        Column::Duration => tags
+                            .and_then(|t| t.duration)
+                            .map(format_duration)
+                            .unwrap_or_default(),
+                    });
+                    return ListItem::new(Line::from(Span::styled(
+                        format!("{}{}", indent, cells),
+                        style,
+                    )));
+                }
+
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{}{}{}", indent, number, title), style),
+                    Span::styled(artist, Style::default().fg(theme.muted)),
+                ]))
+            }
+        })
+        .collect();
+
+    let mut state = ListState::default();
+    state.select(Some(list.cursor));
+
+    let list = List::new(items)
+        .highlight_style(
+            Style::default()
+                .fg(theme.accent)
+                .add_modifier(Modifier::BOLD | Modifier::REVERSED),
+        )
+        .highlight_symbol(">> ");
+
+    f.render_stateful_widget(list, inner, &mut state);
+    (inner, state.offset())
+}
+
+fn draw_info(f: &mut Frame, app: &App, area: Rect) {
+    let theme = app.config.theme();
+    let keys = &app.config.keys;
+    let current_song = app
+        .now_playing()
+        .unwrap_or_else(|| tr!("info.nothing_playing"));
 
-    let status = if app.is_playing {
-        "Playing ▶"
+    let icons = app.config.icons.icons();
+    let status = if app.player.is_playing {
+        format!("{} {}", tr!("info.playing"), icons.playing)
     } else {
-        "Paused ⏸"
+        format!("{} {}", tr!("info.paused"), icons.paused)
     };
 
     let info_text = vec![
         Line::from(vec![Span::styled(
-            "Now Playing:",
-            Style::default().fg(Color::LightBlue),
+            tr!("info.now_playing"),
+            Style::default().fg(theme.border),
         )]),
         Line::from(vec![Span::styled(
             current_song,
             Style::default()
-                .fg(Color::Cyan)
+                .fg(theme.accent)
                 .add_modifier(Modifier::BOLD),
         )]),
         Line::from(""),
         Line::from(vec![Span::styled(
-            format!("Status: {} ", status),
-            Style::default().fg(Color::White),
+            tr!("info.status", status = status),
+            Style::default().fg(theme.text),
         )]),
         Line::from(vec![Span::styled(
-            format!(
-                "Queue Position: {}/{}",
-                app.queue_index + 1,
-                app.queue.len()
+            tr!(
+                "info.queue_position",
+                position = app.player.queue_index + 1,
+                total = app.player.queue.len()
             ),
-            Style::default().fg(Color::Gray),
+            Style::default().fg(theme.secondary),
         )]),
         Line::from(""),
         Line::from(Span::styled(
-            "Controls:",
+            tr!("info.controls"),
             Style::default()
-                .fg(Color::LightCyan)
+                .fg(theme.heading)
                 .add_modifier(Modifier::UNDERLINED),
         )),
         Line::from(Span::styled(
-            "Enter: Enter Dir / Play File",
-            Style::default().fg(Color::DarkGray),
+            tr!("controls.enter"),
+            Style::default().fg(theme.muted),
         )),
         Line::from(Span::styled(
-            "Tab: Play Whole Folder",
-            Style::default().fg(Color::Cyan),
+            tr!("controls.folder"),
+            Style::default().fg(theme.accent),
         )),
         Line::from(Span::styled(
-            "Backspace: Go Up",
-            Style::default().fg(Color::DarkGray),
+            tr!("controls.tab"),
+            Style::default().fg(theme.muted),
         )),
         Line::from(Span::styled(
-            "Space: Play/Pause",
-            Style::default().fg(Color::DarkGray),
+            tr!("controls.backspace"),
+            Style::default().fg(theme.muted),
         )),
         Line::from(Span::styled(
-            "Left/Right: Prev/Next Track",
-            Style::default().fg(Color::DarkGray),
+            tr!("controls.space"),
+            Style::default().fg(theme.muted),
         )),
         Line::from(Span::styled(
-            "PgUp/PgDn: Volume",
-            Style::default().fg(Color::DarkGray),
+            tr!("controls.tracks"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!(
+                "controls.volume",
+                up = keymap::label(&keys.volume_up),
+                down = keymap::label(&keys.volume_down),
+            ),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!(
+                "controls.seek",
+                back = keymap::label(&keys.seek_back),
+                forward = keymap::label(&keys.seek_forward),
+                seconds = app.config.seek_seconds,
+            ),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.update"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.search"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.queue"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.enqueue"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.queue_edit"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.playlist"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.cues"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.inbox"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.recent"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.charts"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.rename"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.arrivals"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.open_with"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.shell"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.jump"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.palette"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.info"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.errors"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.colours"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.track_gain"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.rate"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.filter"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.visual"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.like"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.export"),
+            Style::default().fg(theme.muted),
+        )),
+        Line::from(Span::styled(
+            tr!("controls.replay", seconds = app.config.replay_seconds),
+            Style::default().fg(theme.muted),
         )),
     ];
 
@@ -178,8 +1274,8 @@ fn draw_info(f: &mut Frame, app: &App, area: Rect) {
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title(" Info ")
-                .border_style(Style::default().fg(Color::LightBlue)),
+                .title(tr!("info.title"))
+                .border_style(Style::default().fg(theme.border)),
         )
         .wrap(Wrap { trim: true });
 
@@ -187,54 +1283,103 @@ fn draw_info(f: &mut Frame, app: &App, area: Rect) {
 }
 
 fn draw_footer(f: &mut Frame, app: &App, area: Rect) {
+    let theme = app.config.theme();
+    let icons = app.config.icons.icons();
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(1),
             Constraint::Length(1),
+            Constraint::Length(1),
         ])
         .split(area);
 
-    let (elapsed_sec, duration_sec, ratio) = if let Some(d) = app.duration {
-        let e = app.elapsed.as_secs_f64();
+    let (elapsed_sec, duration_sec, ratio) = if let Some(d) = app.player.duration {
+        let e = app.player.elapsed.as_secs_f64();
         let t = d.as_secs_f64();
         (e, t, (e / t).clamp(0.0, 1.0))
     } else {
         (0.0, 0.0, 0.0)
     };
 
-    let label = format!(
-        "{:02}:{:02} / {:02}:{:02}",
-        (elapsed_sec / 60.0) as u64,
-        (elapsed_sec % 60.0) as u64,
-        (duration_sec / 60.0) as u64,
-        (duration_sec % 60.0) as u64
-    );
-
-    let progress = LineGauge::default()
-        .block(Block::default())
-        .filled_style(Style::default().fg(Color::Cyan))
-        .unfilled_style(Style::default().fg(Color::DarkGray))
-        .filled_symbol("▬")
-        .ratio(ratio)
-        .label(Line::from(Span::styled(
-            label,
-            Style::default()
-                .fg(Color::White)
-                .add_modifier(Modifier::BOLD),
-        )));
+    let label = match &app.config.footer_format {
+        Some(format) => app.format_now_playing(format),
+        None => format!(
+            "{:02}:{:02} / {:02}:{:02}",
+            (elapsed_sec / 60.0) as u64,
+            (elapsed_sec % 60.0) as u64,
+            (duration_sec / 60.0) as u64,
+            (duration_sec % 60.0) as u64
+        ),
+    };
+    // Remote tracks show how much has downloaded until all of it has.
+    let label = match app.player.buffering() {
+        Some(progress) => {
+            let percent = progress.ratio().map_or_else(
+                || String::from("?"),
+                |ratio| (ratio * 100.0).round().to_string(),
+            );
+            if progress.stalled {
+                tr!("footer.buffering", percent = percent)
+            } else {
+                tr!("footer.buffered", position = label, percent = percent)
+            }
+        }
+        None => label,
+    };
 
-    f.render_widget(progress, chunks[0]);
+    let volume_ratio = (app.player.volume as f64 / app.player.max_volume as f64).clamp(0.0, 1.0);
+    // Boosted volume can clip, so it stands out.
+    let volume_colour = if app.player.volume > 100 {
+        theme.error
+    } else {
+        theme.border
+    };
+    let vol_label = tr!("footer.volume", volume = app.player.volume);
 
-    let volume_ratio = (app.volume as f64 / 100.0).clamp(0.0, 1.0);
-    let vol_label = format!("VOL: {}%", app.volume);
+    if app.config.text_only() {
+        let state = if app.player.is_playing {
+            tr!("info.playing")
+        } else {
+            tr!("info.paused")
+        };
+        let position = Paragraph::new(format!(
+            "{} {} ({}%)",
+            state,
+            label,
+            (ratio * 100.0).round() as u64
+        ));
+        f.render_widget(position, chunks[0]);
+        f.render_widget(Paragraph::new(vol_label), chunks[1]);
+    } else {
+        let progress = LineGauge::default()
+            .block(Block::default())
+            .filled_style(Style::default().fg(theme.accent))
+            .unfilled_style(Style::default().fg(theme.muted))
+            .filled_symbol(icons.gauge_filled)
+            .unfilled_symbol(icons.gauge_unfilled)
+            .ratio(ratio)
+            .label(Line::from(Span::styled(
+                label.as_str(),
+                Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+            )));
+        f.render_widget(progress, chunks[0]);
+        draw_progress_marks(f, app, chunks[0], label.chars().count() as u16);
 
-    let vol_gauge = Gauge::default()
-        .block(Block::default())
-        .gauge_style(Style::default().fg(Color::LightBlue).bg(Color::Black))
-        .ratio(volume_ratio)
-        .label(vol_label)
-        .use_unicode(true);
+        let vol_gauge = Gauge::default()
+            .block(Block::default())
+            .gauge_style(Style::default().fg(volume_colour).bg(theme.background))
+            .ratio(volume_ratio)
+            .label(vol_label)
+            .use_unicode(icons.unicode_gauge);
+        f.render_widget(vol_gauge, chunks[1]);
+    }
 
-    f.render_widget(vol_gauge, chunks[1]);
+    if let Some(status) = &app.status {
+        let status_line = Paragraph::new(Line::from(Span::styled(
+            status.as_str(),
+            Style::default().fg(theme.status),
+        )));
+        f.render_widget(status_line, chunks[2]);
+    }
 }